@@ -0,0 +1,145 @@
+use bincode::{Decode, Encode};
+use chrono::Utc;
+use log::error;
+use poise::serenity_prelude::{GuildId, UserId};
+use redb::{ReadableTable, TableDefinition};
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use crate::{
+    bc,
+    storage::{Db, Redb},
+};
+
+/// Message counts are only kept for this many days
+pub const MAX_DAYS: u32 = 90;
+const FLUSH_INTERVAL: Duration = Duration::from_secs(30);
+pub(crate) const ACTIVITY_TABLE: TableDefinition<(u64, u64), bc::Bincode<Activity>> =
+    TableDefinition::new("activity");
+
+/// Number of messages a member wrote per day
+#[derive(Debug, Default, Encode, Decode)]
+pub struct Activity {
+    pub days: BTreeMap<i64, u32>,
+}
+
+impl Activity {
+    pub fn messages_since(&self, days: u32) -> u32 {
+        let first = today() - i64::from(days) + 1;
+        self.days.range(first..).map(|(_, count)| count).sum()
+    }
+}
+
+fn today() -> i64 {
+    Utc::now().timestamp().div_euclid(24 * 60 * 60)
+}
+
+/// Message counts per guild, member and day, written together every [`FLUSH_INTERVAL`] instead of
+/// one transaction per message
+#[derive(Default)]
+pub struct ActivityBuffer {
+    counts: Mutex<HashMap<(u64, u64), BTreeMap<i64, u32>>>,
+    /// Held while counts are written, see [`ActivityBuffer::forget`]
+    flushing: Mutex<()>,
+}
+
+impl ActivityBuffer {
+    pub fn record(&self, guild: GuildId, user: UserId) {
+        *self
+            .counts
+            .lock()
+            .unwrap()
+            .entry((guild.get(), user.get()))
+            .or_default()
+            .entry(today())
+            .or_default() += 1;
+    }
+
+    /// Drops the counts not written yet, of every guild without one. Waits for a running flush, so
+    /// deleting the stored counts afterwards deletes everything
+    pub fn forget(&self, guild: Option<GuildId>, user: Option<UserId>) {
+        let _flushing = self.flushing.lock().unwrap();
+        self.counts
+            .lock()
+            .unwrap()
+            .retain(|&(key_guild, key_user), _| {
+                guild.is_some_and(|guild| guild.get() != key_guild)
+                    || user.is_some_and(|user| user.get() != key_user)
+            });
+    }
+
+    pub fn flush(&self, db: &Redb) -> anyhow::Result<()> {
+        let _flushing = self.flushing.lock().unwrap();
+        let counts = std::mem::take(&mut *self.counts.lock().unwrap());
+        if counts.is_empty() {
+            return Ok(());
+        }
+        if let Err(err) = write_counts(db, &counts) {
+            //  Counted again with the next flush, together with the new messages
+            let mut pending = self.counts.lock().unwrap();
+            for (key, days) in counts {
+                let pending = pending.entry(key).or_default();
+                for (day, count) in days {
+                    *pending.entry(day).or_default() += count;
+                }
+            }
+            return Err(err);
+        }
+        Ok(())
+    }
+}
+
+fn write_counts(db: &Redb, counts: &HashMap<(u64, u64), BTreeMap<i64, u32>>) -> anyhow::Result<()> {
+    let first = today() - i64::from(MAX_DAYS) + 1;
+    let db = db.begin_write()?;
+    {
+        let mut table = db.open_table(ACTIVITY_TABLE)?;
+        for (&key, days) in counts {
+            let mut activity = table.get(key)?.map(|v| v.value()).unwrap_or_default();
+            for (&day, &count) in days {
+                *activity.days.entry(day).or_default() += count;
+            }
+            activity.days = activity.days.split_off(&first);
+            table.insert(key, activity)?;
+        }
+    }
+    db.commit()?;
+    Ok(())
+}
+
+/// Writes the buffered message counts
+pub async fn activity_task(db: Arc<Db>) {
+    loop {
+        tokio::time::sleep(FLUSH_INTERVAL).await;
+        if let Err(err) = db.activity().flush(&db) {
+            error!("Error writing message counts: {}", err);
+        }
+    }
+}
+
+/// Includes the messages that weren't written yet
+pub fn get_activity(db: &Db, guild: GuildId, user: UserId) -> anyhow::Result<Activity> {
+    let mut activity = {
+        let db = db.begin_read()?;
+        let table = db.open_table(ACTIVITY_TABLE)?;
+        table
+            .get((guild.get(), user.get()))?
+            .map(|v| v.value())
+            .unwrap_or_default()
+    };
+    if let Some(days) = db
+        .activity()
+        .counts
+        .lock()
+        .unwrap()
+        .get(&(guild.get(), user.get()))
+    {
+        for (&day, &count) in days {
+            *activity.days.entry(day).or_default() += count;
+        }
+    }
+    Ok(activity)
+}
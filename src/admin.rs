@@ -0,0 +1,337 @@
+use anyhow::Context as _;
+use bincode::Decode;
+use poise::{
+    ChoiceParameter, Context, CreateReply, command,
+    serenity_prelude::{CreateAttachment, GuildId, UserId},
+};
+use redb::{ReadTransaction, ReadableTableMetadata, TableDefinition, TableHandle};
+use std::{collections::HashSet, fmt::Debug, sync::Arc};
+
+use crate::{
+    bc::{Raw, decode_exact},
+    db_read,
+    departed::departed,
+    locale::{language, localized, t},
+    maintenance::format_bytes,
+    mydata::purge_user,
+    storage::{ARCHIVE_TABLE, CONFIG_TABLE, DRAFTS_TABLE, Db, GIVEAWAYS_TABLE, PARTICIPANTS_TABLE},
+    structs::{GiveawayId, Language},
+};
+
+#[derive(Debug, Clone, Copy, ChoiceParameter)]
+pub enum GuildTable {
+    #[name = "Einstellungen"]
+    Config,
+    #[name = "Giveaways"]
+    Giveaways,
+    #[name = "Archiv"]
+    Archive,
+    #[name = "Entwürfe"]
+    Drafts,
+}
+
+impl GuildTable {
+    fn label(self, language: Language) -> String {
+        match self {
+            GuildTable::Config => t!(language, "admin-table-config"),
+            GuildTable::Giveaways => t!(language, "admin-table-giveaways"),
+            GuildTable::Archive => t!(language, "admin-table-archive"),
+            GuildTable::Drafts => t!(language, "admin-table-drafts"),
+        }
+    }
+}
+
+/// Owners besides the owner of the application, from the comma separated ids in `BOT_OWNERS`
+pub fn configured_owners() -> HashSet<UserId> {
+    std::env::var("BOT_OWNERS")
+        .unwrap_or_default()
+        .split(',')
+        .filter_map(|id| id.trim().parse().ok())
+        .map(UserId::new)
+        .collect()
+}
+
+#[command(
+    slash_command,
+    default_member_permissions = "ADMINISTRATOR",
+    owners_only,
+    subcommands("database", "dbstats", "purge"),
+    subcommand_required
+)]
+pub async fn admin(_ctx: Context<'_, Arc<Db>, anyhow::Error>) -> anyhow::Result<()> {
+    Ok(())
+}
+
+#[command(
+    slash_command,
+    owners_only,
+    rename = "db",
+    subcommands("state", "count", "giveaway", "delete"),
+    subcommand_required
+)]
+pub async fn database(_ctx: Context<'_, Arc<Db>, anyhow::Error>) -> anyhow::Result<()> {
+    Ok(())
+}
+
+//  Snowflakes don't fit into integer options, so guilds are given as text
+fn guild_or_current(
+    ctx: Context<'_, Arc<Db>, anyhow::Error>,
+    guild: Option<String>,
+) -> anyhow::Result<GuildId> {
+    match guild {
+        Some(guild) => guild
+            .trim()
+            .parse::<u64>()
+            .ok()
+            .filter(|guild| *guild != 0)
+            .map(GuildId::new)
+            .context(localized!("admin-invalid-guild")),
+        None => ctx.guild_id().context("Not in a guild"),
+    }
+}
+
+/// Shows everything stored for a server
+#[command(slash_command, owners_only)]
+pub async fn state(
+    ctx: Context<'_, Arc<Db>, anyhow::Error>,
+    guild: Option<String>,
+) -> anyhow::Result<()> {
+    ctx.defer_ephemeral().await?;
+    let guild = guild_or_current(ctx, guild)?;
+    let state = db_read(ctx.data(), guild)?;
+    ctx.send(
+        CreateReply::default()
+            .content(t!(
+                language(ctx),
+                "admin-state",
+                guild,
+                giveaways = state.giveaways.len(),
+                drafts = state.drafts.len(),
+                archived = state.archive.len()
+            ))
+            .attachment(CreateAttachment::bytes(
+                format!("{state:#?}"),
+                format!("state-{guild}.txt"),
+            ))
+            .ephemeral(true),
+    )
+    .await?;
+    Ok(())
+}
+
+/// Counts the entries of every table in the database
+#[command(slash_command, owners_only)]
+pub async fn count(ctx: Context<'_, Arc<Db>, anyhow::Error>) -> anyhow::Result<()> {
+    let db_read = ctx.data().begin_read()?;
+    let mut tables = Vec::new();
+    for table in db_read.list_tables()? {
+        let len = db_read.open_untyped_table(table.clone())?.len()?;
+        tables.push((table.name().to_string(), len));
+    }
+    tables.sort();
+    let mut content = t!(language(ctx), "admin-count");
+    for (name, len) in tables {
+        content.push_str(&format!("\n`{name}`: {len}"));
+    }
+    ctx.send(CreateReply::default().content(content).ephemeral(true))
+        .await?;
+    Ok(())
+}
+
+/// Deletes the entries, wins, message counts and invites of a user on every server
+#[command(slash_command, owners_only)]
+pub async fn purge(ctx: Context<'_, Arc<Db>, anyhow::Error>, user: UserId) -> anyhow::Result<()> {
+    ctx.defer_ephemeral().await?;
+    let (guilds, erased) = purge_user(ctx.data(), user).await?;
+    ctx.send(
+        CreateReply::default()
+            .content(t!(language(ctx), "admin-purge", user, erased, guilds))
+            .ephemeral(true),
+    )
+    .await?;
+    Ok(())
+}
+
+/// Shows how big the database and its tables are, to notice growth before the disk is full
+#[command(slash_command, owners_only)]
+pub async fn dbstats(ctx: Context<'_, Arc<Db>, anyhow::Error>) -> anyhow::Result<()> {
+    ctx.defer_ephemeral().await?;
+    let db = ctx.data();
+    let language = language(ctx);
+    let file = std::fs::metadata(db.path())?.len();
+    //  Read through the storage, so it also counts guilds kept in SQL
+    let guilds = db.storage().guilds()?;
+    let giveaways: usize = guilds.iter().map(|(_, state)| state.giveaways.len()).sum();
+    let drafts: usize = guilds.iter().map(|(_, state)| state.drafts.len()).sum();
+    let archived: usize = guilds.iter().map(|(_, state)| state.archive.len()).sum();
+    let mut content = t!(
+        language,
+        "admin-dbstats",
+        size = format_bytes(file),
+        path = db.path(),
+        compacted = format!("<t:{}:R>", db.last_compaction()),
+        guilds = guilds.len(),
+        departed = departed(db)?.len(),
+        giveaways,
+        drafts,
+        archived,
+    );
+    let db_read = db.begin_read()?;
+    let mut tables = Vec::new();
+    for table in db_read.list_tables()? {
+        let untyped = db_read.open_untyped_table(table.clone())?;
+        let stats = untyped.stats()?;
+        let size = stats.stored_bytes() + stats.metadata_bytes() + stats.fragmented_bytes();
+        tables.push((size, table.name().to_string(), untyped.len()?));
+    }
+    //  The biggest tables first, those are the ones worth looking at
+    tables.sort_by(|a, b| b.cmp(a));
+    for (size, name, len) in tables {
+        content.push_str(&t!(
+            language,
+            "admin-dbstats-table",
+            name,
+            size = format_bytes(size),
+            len
+        ));
+    }
+    ctx.send(CreateReply::default().content(content).ephemeral(true))
+        .await?;
+    Ok(())
+}
+
+//  Also describes entries that can't be decoded, those are what this is usually needed for
+fn describe_entry<T>(
+    db_read: &ReadTransaction,
+    table: TableDefinition<(u64, u64), Raw<T>>,
+    guild: GuildId,
+    giveaway: GiveawayId,
+    language: Language,
+) -> anyhow::Result<Option<String>>
+where
+    T: Debug + Decode<()> + 'static,
+{
+    let table = db_read.open_table(table)?;
+    let Some(data) = table.get((guild.get(), giveaway.0))? else {
+        return Ok(None);
+    };
+    let data = data.value();
+    Ok(Some(match decode_exact::<T>(data) {
+        Some(value) => format!("{value:#?}"),
+        None => t!(
+            language,
+            "admin-unreadable",
+            len = data.len(),
+            data = format!("{data:02x?}")
+        ),
+    }))
+}
+
+/// Shows a running, archived or scheduled giveaway as stored, even if it can't be read
+#[command(slash_command, owners_only)]
+pub async fn giveaway(
+    ctx: Context<'_, Arc<Db>, anyhow::Error>,
+    giveaway: GiveawayId,
+    guild: Option<String>,
+) -> anyhow::Result<()> {
+    ctx.defer_ephemeral().await?;
+    let guild = guild_or_current(ctx, guild)?;
+    let language = language(ctx);
+    let db_read = ctx.data().begin_read()?;
+    let found = [
+        (
+            GuildTable::Giveaways,
+            describe_entry(&db_read, GIVEAWAYS_TABLE, guild, giveaway, language)?,
+        ),
+        (
+            GuildTable::Archive,
+            describe_entry(&db_read, ARCHIVE_TABLE, guild, giveaway, language)?,
+        ),
+        (
+            GuildTable::Drafts,
+            describe_entry(&db_read, DRAFTS_TABLE, guild, giveaway, language)?,
+        ),
+    ];
+    let running = found[0].1.is_some();
+    let mut found: Vec<String> = found
+        .into_iter()
+        .filter_map(|(table, entry)| {
+            entry.map(|entry| format!("{}:\n{entry}", table.label(language)))
+        })
+        .collect();
+    //  Running giveaways are stored without their participants
+    if running {
+        let participants = db_read
+            .open_table(PARTICIPANTS_TABLE)?
+            .range((guild.get(), giveaway.0, 0)..=(guild.get(), giveaway.0, u64::MAX))?
+            .count();
+        found.push(t!(language, "admin-participants", participants));
+    }
+    if found.is_empty() {
+        ctx.reply(t!(language, "admin-giveaway-missing", giveaway, guild))
+            .await?;
+        return Ok(());
+    }
+    ctx.send(
+        CreateReply::default()
+            .content(t!(language, "admin-giveaway", giveaway, guild))
+            .attachment(CreateAttachment::bytes(
+                found.join("\n\n"),
+                format!("giveaway-{giveaway}.txt"),
+            ))
+            .ephemeral(true),
+    )
+    .await?;
+    Ok(())
+}
+
+/// Deletes a stored entry of a server, meant for entries that can't be read anymore
+#[command(slash_command, owners_only)]
+pub async fn delete(
+    ctx: Context<'_, Arc<Db>, anyhow::Error>,
+    table: GuildTable,
+    guild: String,
+    giveaway: Option<GiveawayId>,
+) -> anyhow::Result<()> {
+    let guild = guild_or_current(ctx, Some(guild))?;
+    let language = language(ctx);
+    //  Only the settings are stored per guild instead of per giveaway
+    let key = match (table, giveaway) {
+        (GuildTable::Config, _) => (guild.get(), 0),
+        (_, Some(giveaway)) => (guild.get(), giveaway.0),
+        (_, None) => {
+            ctx.send(
+                CreateReply::default()
+                    .content(t!(language, "admin-giveaway-required"))
+                    .ephemeral(true),
+            )
+            .await?;
+            return Ok(());
+        }
+    };
+    let deleted = {
+        let db = ctx.data().begin_write()?;
+        let deleted = match table {
+            GuildTable::Config => db.open_table(CONFIG_TABLE)?.remove(key.0)?.is_some(),
+            GuildTable::Giveaways => {
+                db.open_table(PARTICIPANTS_TABLE)?
+                    .retain_in((key.0, key.1, 0)..=(key.0, key.1, u64::MAX), |_, _| false)?;
+                db.open_table(GIVEAWAYS_TABLE)?.remove(key)?.is_some()
+            }
+            GuildTable::Archive => db.open_table(ARCHIVE_TABLE)?.remove(key)?.is_some(),
+            GuildTable::Drafts => db.open_table(DRAFTS_TABLE)?.remove(key)?.is_some(),
+        };
+        db.commit()?;
+        deleted
+    };
+    ctx.data().forget_settings(guild);
+    let table = table.label(language);
+    let content = if deleted {
+        t!(language, "admin-deleted", table)
+    } else {
+        t!(language, "admin-not-found", table)
+    };
+    ctx.send(CreateReply::default().content(content).ephemeral(true))
+        .await?;
+    Ok(())
+}
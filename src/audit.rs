@@ -0,0 +1,290 @@
+use anyhow::Context as _;
+use bincode::{Decode, Encode};
+use chrono::Utc;
+use log::error;
+use poise::{
+    ApplicationContext, Context, CreateReply, command,
+    serenity_prelude::{
+        ButtonStyle, CreateActionRow, CreateButton, GuildId, ResolvedTarget, ResolvedValue, UserId,
+    },
+};
+use redb::{ReadableTable, TableDefinition};
+use std::sync::Arc;
+
+use crate::{
+    bc,
+    locale::{language, t},
+    storage::{Db, Redb},
+    structs::{Language, UserAction},
+};
+
+/// Administrative actions, keyed by guild and a number counting up per guild
+pub(crate) const AUDIT_TABLE: TableDefinition<(u64, u64), bc::Bincode<AuditEntry>> =
+    TableDefinition::new("audit");
+
+/// Only the latest entries of a guild are kept
+const MAX_ENTRIES: u64 = 1000;
+const PAGE_SIZE: usize = 10;
+const MAX_DETAILS: usize = 150;
+
+/// Commands that only show something aren't recorded
+const READ_ONLY: [&str; 15] = [
+    "info",
+    "giveaway list",
+    "participants list",
+    "participants export",
+    "leaderboard wins",
+    "wins",
+    "stats",
+    "settings show",
+    "verify",
+    "audit",
+    "admin db state",
+    "admin db count",
+    "admin db giveaway",
+    "admin dbstats",
+    "mydata export",
+];
+
+/// Commands erasing a user, their arguments would record the user again
+const ERASING: [&str; 1] = ["admin purge"];
+
+#[derive(Debug, Encode, Decode)]
+pub struct AuditEntry {
+    pub time: i64,
+    pub actor: Actor,
+    pub action: String,
+    pub details: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Encode, Decode)]
+pub enum Actor {
+    /// Actions of the bot itself, like giveaways ending on time
+    Bot,
+    Member(u64),
+    /// A member whose data was erased
+    Erased,
+}
+
+/// The entry is only a trace of the action, so a failed write is logged instead of failing the
+/// action itself
+pub fn record(
+    db: &Redb,
+    guild: GuildId,
+    actor: Option<UserId>,
+    action: impl Into<String>,
+    details: impl Into<String>,
+) {
+    if let Err(err) = write_entry(db, guild, actor, action.into(), details.into()) {
+        error!("Error recording audit entry: {}", err);
+    }
+}
+
+fn write_entry(
+    db: &Redb,
+    guild: GuildId,
+    actor: Option<UserId>,
+    action: String,
+    details: String,
+) -> anyhow::Result<()> {
+    let db = db.begin_write()?;
+    {
+        let mut table = db.open_table(AUDIT_TABLE)?;
+        let number = match table
+            .range((guild.get(), 0)..=(guild.get(), u64::MAX))?
+            .next_back()
+        {
+            Some(last) => last?.0.value().1 + 1,
+            None => 0,
+        };
+        table.insert(
+            (guild.get(), number),
+            AuditEntry {
+                time: Utc::now().timestamp(),
+                actor: actor.map_or(Actor::Bot, |actor| Actor::Member(actor.get())),
+                action,
+                details,
+            },
+        )?;
+        if let Some(oldest) = number.checked_sub(MAX_ENTRIES) {
+            table.remove((guild.get(), oldest))?;
+        }
+    }
+    db.commit()?;
+    Ok(())
+}
+
+impl AuditEntry {
+    /// Whether the user did this or is named in the details
+    pub fn concerns(&self, user: u64) -> bool {
+        self.actor == Actor::Member(user) || self.details.contains(&user.to_string())
+    }
+}
+
+/// Entries the user made or is named in, oldest first
+pub fn entries_of(db: &Redb, guild: GuildId, user: u64) -> anyhow::Result<Vec<AuditEntry>> {
+    let db_read = db.begin_read()?;
+    let table = db_read.open_table(AUDIT_TABLE)?;
+    let mut entries = Vec::new();
+    for entry in table.range((guild.get(), 0)..=(guild.get(), u64::MAX))? {
+        let entry = entry?.1.value();
+        if entry.concerns(user) {
+            entries.push(entry);
+        }
+    }
+    Ok(entries)
+}
+
+/// Keeps the entries concerning the user, but without them, `erased` takes their place in the
+/// details
+pub fn erase_user(db: &Redb, guild: GuildId, user: u64, erased: &str) -> anyhow::Result<()> {
+    let db = db.begin_write()?;
+    {
+        let mut table = db.open_table(AUDIT_TABLE)?;
+        let mut changed = Vec::new();
+        for entry in table.range((guild.get(), 0)..=(guild.get(), u64::MAX))? {
+            let (key, entry) = entry?;
+            let mut entry = entry.value();
+            if !entry.concerns(user) {
+                continue;
+            }
+            if entry.actor == Actor::Member(user) {
+                entry.actor = Actor::Erased;
+            }
+            //  Mentions first, so they don't keep their brackets
+            entry.details = entry
+                .details
+                .replace(&format!("<@{user}>"), erased)
+                .replace(&user.to_string(), erased);
+            changed.push((key.value(), entry));
+        }
+        for (key, entry) in changed {
+            table.insert(key, entry)?;
+        }
+    }
+    db.commit()?;
+    Ok(())
+}
+
+/// Records every successful command except those in [`READ_ONLY`], used as `post_command`
+pub async fn record_command(ctx: Context<'_, Arc<Db>, anyhow::Error>) {
+    let Some(guild) = ctx.guild_id() else {
+        return;
+    };
+    let name = &ctx.command().qualified_name;
+    if READ_ONLY.contains(&name.as_str()) {
+        return;
+    }
+    let details = match ctx {
+        Context::Application(ctx) if !ERASING.contains(&name.as_str()) => describe_args(ctx),
+        _ => String::new(),
+    };
+    record(
+        ctx.data(),
+        guild,
+        Some(ctx.author().id),
+        format!("/{name}"),
+        details,
+    );
+}
+
+fn describe_args(ctx: ApplicationContext<'_, Arc<Db>, anyhow::Error>) -> String {
+    match ctx.interaction.data.target() {
+        Some(ResolvedTarget::User(user, _)) => return format!("<@{}>", user.id),
+        Some(ResolvedTarget::Message(message)) => return message.link(),
+        _ => {}
+    }
+    ctx.args
+        .iter()
+        .map(|option| {
+            let value = match &option.value {
+                ResolvedValue::Boolean(value) => value.to_string(),
+                ResolvedValue::Integer(value) => value.to_string(),
+                ResolvedValue::Number(value) => value.to_string(),
+                ResolvedValue::String(value) => value.to_string(),
+                ResolvedValue::Attachment(attachment) => attachment.filename.clone(),
+                ResolvedValue::Channel(channel) => format!("<#{}>", channel.id),
+                ResolvedValue::Role(role) => format!("<@&{}>", role.id),
+                ResolvedValue::User(user, _) => format!("<@{}>", user.id),
+                _ => "?".to_string(),
+            };
+            format!("{}: {value}", option.name)
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Renders one page of the newest entries with buttons to switch pages
+pub fn audit_page(
+    db: &Redb,
+    guild: GuildId,
+    page: u32,
+    language: Language,
+) -> anyhow::Result<(String, Vec<CreateActionRow>)> {
+    let db_read = db.begin_read()?;
+    let table = db_read.open_table(AUDIT_TABLE)?;
+    let range = (guild.get(), 0)..=(guild.get(), u64::MAX);
+    let total = table.range(range.clone())?.count();
+    let pages = total.div_ceil(PAGE_SIZE).max(1) as u32;
+    let page = page.min(pages - 1);
+    let mut content = t!(language, "audit-header", total, page = page + 1, pages);
+    if total == 0 {
+        content.push_str(&t!(language, "audit-empty"));
+    }
+    for entry in table
+        .range(range)?
+        .rev()
+        .skip(page as usize * PAGE_SIZE)
+        .take(PAGE_SIZE)
+    {
+        let entry = entry?.1.value();
+        let actor = match entry.actor {
+            Actor::Bot => t!(language, "audit-bot"),
+            Actor::Member(actor) => format!("<@{actor}>"),
+            Actor::Erased => t!(language, "audit-erased"),
+        };
+        content.push_str(&format!(
+            "\n<t:{}:f> {actor} **{}**",
+            entry.time, entry.action
+        ));
+        if !entry.details.is_empty() {
+            let mut details: String = entry.details.chars().take(MAX_DETAILS).collect();
+            if details.len() < entry.details.len() {
+                details.push('…');
+            }
+            content.push_str(&format!(" – {details}"));
+        }
+    }
+    let ar = CreateActionRow::Buttons(Vec::from([
+        CreateButton::new(
+            serde_json::to_string(&UserAction::Audit(page.saturating_sub(1))).unwrap(),
+        )
+        .label(t!(language, "button-previous"))
+        .style(ButtonStyle::Secondary)
+        .disabled(page == 0),
+        CreateButton::new(serde_json::to_string(&UserAction::Audit(page + 1)).unwrap())
+            .label(t!(language, "button-next"))
+            .style(ButtonStyle::Secondary)
+            .disabled(page + 1 >= pages),
+    ]));
+    Ok((content, vec![ar]))
+}
+
+/// Shows who created, ended, cancelled or cleared something and who changed settings
+#[command(
+    slash_command,
+    default_member_permissions = "ADMINISTRATOR",
+    guild_only
+)]
+pub async fn audit(ctx: Context<'_, Arc<Db>, anyhow::Error>) -> anyhow::Result<()> {
+    let guild = ctx.guild_id().context("Not in a guild")?;
+    let (content, components) = audit_page(ctx.data(), guild, 0, language(ctx))?;
+    ctx.send(
+        CreateReply::default()
+            .content(content)
+            .components(components)
+            .ephemeral(true),
+    )
+    .await?;
+    Ok(())
+}
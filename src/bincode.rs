@@ -1,4 +1,4 @@
-use std::{any::type_name, cmp::Ordering, fmt::Debug};
+use std::{any::type_name, cmp::Ordering, fmt::Debug, marker::PhantomData};
 
 use bincode::{Decode, Encode, decode_from_slice, encode_to_vec};
 use redb::{Key, TypeName, Value};
@@ -54,3 +54,56 @@ where
         Self::from_bytes(data1).cmp(&Self::from_bytes(data2))
     }
 }
+
+/// The undecoded bytes of a [`Bincode<T>`] value, used to migrate data stored in an older layout
+#[derive(Debug)]
+pub struct Raw<T>(PhantomData<T>);
+
+impl<T> Value for Raw<T>
+where
+    T: Debug,
+{
+    type SelfType<'a>
+        = &'a [u8]
+    where
+        Self: 'a;
+
+    type AsBytes<'a>
+        = &'a [u8]
+    where
+        Self: 'a;
+
+    fn fixed_width() -> Option<usize> {
+        None
+    }
+
+    fn from_bytes<'a>(data: &'a [u8]) -> Self::SelfType<'a>
+    where
+        Self: 'a,
+    {
+        data
+    }
+
+    fn as_bytes<'a, 'b: 'a>(value: &'a Self::SelfType<'b>) -> Self::AsBytes<'a>
+    where
+        Self: 'a,
+        Self: 'b,
+    {
+        value
+    }
+
+    fn type_name() -> TypeName {
+        TypeName::new(&format!("Bincode<{}>", type_name::<T>()))
+    }
+}
+
+/// Decodes `data` only if it is consumed entirely
+pub fn decode_exact<T>(data: &[u8]) -> Option<T>
+where
+    T: Decode<()>,
+{
+    match decode_from_slice(data, bincode::config::standard()) {
+        Ok((value, len)) if len == data.len() => Some(value),
+        _ => None,
+    }
+}
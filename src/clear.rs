@@ -1,96 +1,1326 @@
-use futures::StreamExt;
+use anyhow::Context as _;
+use bincode::{Decode, Encode};
+use chrono::{TimeDelta, Utc};
+use log::error;
 use poise::{
     Context, CreateReply, command,
-    serenity_prelude::{CacheHttp, ChannelId, CreateActionRow, CreateButton, GuildId, UserId},
+    serenity_prelude::{
+        CacheHttp, ChannelId, ComponentInteraction, CreateActionRow, CreateAllowedMentions,
+        CreateAttachment, CreateButton, CreateMessage, DiscordJsonError, EditInteractionResponse,
+        Error, ErrorResponse, GetMessages, GuildId, HttpError, Member, Mentionable, Message,
+        MessageId, RoleId, StatusCode, User, UserId, parse_channel_mention, parse_message_url,
+    },
 };
-use redb::Database;
-use std::sync::Arc;
-use tokio::pin;
+use redb::{ReadableTable, TableDefinition};
+use regex::Regex;
+use serde::Serialize;
+use std::{
+    collections::BTreeMap,
+    fmt::Write as _,
+    sync::{Arc, LazyLock},
+    time::{Duration, Instant},
+};
+
+use crate::metrics::metrics;
+use crate::{
+    bc,
+    locale::{language, localized, t, text},
+    parse_time_arg, parse_users,
+    storage::Db,
+    structs::{Language, Modlog, UserAction},
+};
+
+//  Discord only bulk deletes messages younger than two weeks, at most 100 at once
+const BULK_AGE: TimeDelta = TimeDelta::days(14);
+const BULK_COUNT: usize = 100;
+//  Counting stops after this many messages looked at, so a dry run over a huge server still
+//  answers in time, no matter how few of them match
+const DRY_RUN_LIMIT: usize = 10_000;
+//  Long clears report their progress after this many deletions, or this much time
+const PROGRESS_COUNT: usize = 50;
+const PROGRESS_INTERVAL: Duration = Duration::from_secs(30);
+const REACTIONS_COUNT: u32 = 100;
+//  Keeps the log message within Discord's length limit
+const LOG_CHANNELS: usize = 20;
+//  Files stay below Discord's upload limit of servers without boosts
+const FILE_LIMIT: usize = 8 * 1024 * 1024;
+const RETRY_ATTEMPTS: u32 = 5;
+//  Every user is one more comparison per message, but a raid rarely has more accounts
+const MAX_USERS: usize = 25;
+const MEMBERS_PAGE: u64 = 1000;
+const CONFIRM_TIMEOUT: Duration = Duration::from_secs(60);
+const RETRY_DELAY: Duration = Duration::from_secs(1);
+
+//  Invites count as links only with their scheme, invites_only finds them without it as well
+static LINK: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"https?://\S+").unwrap());
+static INVITE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?i)(discord\.gg|discord(app)?\.com/invite)/[\w-]+").unwrap());
+
+/// Clears waiting for confirmation, the filter doesn't fit into the custom id of the button
+pub(crate) const CLEAR_JOBS_TABLE: TableDefinition<u64, bc::Bincode<ClearJob>> =
+    TableDefinition::new("clear_jobs");
+
+#[derive(Debug, Default, Encode, Decode)]
+pub struct ClearJob {
+    pub guild: u64,
+    /// Without a channel every channel of the guild is cleared
+    pub channel: Option<u64>,
+    /// Messages of any of these users, all users if empty
+    pub users: Vec<u64>,
+    pub pattern: Option<String>,
+    /// Only messages created before/after these unix timestamps
+    pub before: Option<i64>,
+    pub after: Option<i64>,
+    /// With several set, messages of any of these kinds are deleted
+    pub attachments_only: bool,
+    pub embeds_only: bool,
+    pub links_only: bool,
+    pub invites_only: bool,
+    /// Set once confirmed, removing a running job stops it
+    pub running: bool,
+    /// The users were the members of this role, only for the description
+    pub role: Option<u64>,
+    /// Who confirmed the job, gets the report if it's resumed after a restart
+    pub moderator: Option<u64>,
+    /// The current channel and the oldest message looked at so far, to resume after a restart
+    pub cursor: Option<(u64, Option<u64>)>,
+    /// Messages deleted before the last restart
+    pub count: u64,
+    /// Keeps the deleted messages as NDJSON file for later review
+    pub archive: bool,
+    /// When the confirmation was asked for, it expires after a while
+    pub created: i64,
+}
+
+impl ClearJob {
+    fn expired(&self) -> bool {
+        !self.running && Utc::now().timestamp() - self.created > CONFIRM_TIMEOUT.as_secs() as i64
+    }
+
+    /// A channel cleared without any filter gets a notice afterwards
+    pub fn whole_channel(&self) -> Option<ChannelId> {
+        match (&self.pattern, self.before, self.after) {
+            (None, None, None) if self.users.is_empty() && self.kinds().is_empty() => {
+                self.channel.map(ChannelId::new)
+            }
+            _ => None,
+        }
+    }
 
-use crate::structs::UserAction;
+    //  Clearing users and whole channels always needed MANAGE_CHANNELS, filters only
+    //  MANAGE_MESSAGES
+    fn permitted(&self, member: &Member) -> bool {
+        member.permissions.is_some_and(|p| match self.pattern {
+            Some(_) if self.users.is_empty() => p.manage_messages(),
+            _ => p.manage_channels(),
+        })
+    }
+
+    fn matches(&self, mes: &Message, regex: Option<&Regex>) -> bool {
+        (self.users.is_empty() || self.users.binary_search(&mes.author.id.get()).is_ok())
+            && regex.is_none_or(|regex| regex.is_match(&mes.content))
+            && self
+                .before
+                .is_none_or(|before| mes.timestamp.unix_timestamp() < before)
+            && self.of_kind(mes)
+    }
+
+    //  Catalog keys of the kinds of messages the job is limited to
+    fn kinds(&self) -> Vec<&'static str> {
+        [
+            (self.attachments_only, "clear-kind-attachments"),
+            (self.embeds_only, "clear-kind-embeds"),
+            (self.links_only, "clear-kind-links"),
+            (self.invites_only, "clear-kind-invites"),
+        ]
+        .into_iter()
+        .filter(|(only, _)| *only)
+        .map(|(_, key)| key)
+        .collect()
+    }
+
+    fn of_kind(&self, mes: &Message) -> bool {
+        let kinds = [
+            (self.attachments_only, !mes.attachments.is_empty()),
+            (self.embeds_only, !mes.embeds.is_empty()),
+            (self.links_only, LINK.is_match(&mes.content)),
+            (self.invites_only, INVITE.is_match(&mes.content)),
+        ];
+        kinds.iter().all(|(only, _)| !only) || kinds.iter().any(|(only, is)| *only && *is)
+    }
+
+    /// Describes the deleted messages, like "von @Nutzer in #Kanal"
+    pub fn describe(&self, language: Language) -> String {
+        let mut parts = Vec::new();
+        if let Some(role) = self.role {
+            parts.push(t!(language, "clear-by-role", role = format!("<@&{role}>")));
+        } else if !self.users.is_empty() {
+            let users: Vec<_> = self.users.iter().map(|user| format!("<@{user}>")).collect();
+            parts.push(t!(language, "clear-by-users", users = users.join(", ")));
+        }
+        match self.channel {
+            Some(channel) => parts.push(t!(
+                language,
+                "clear-in-channel",
+                channel = format!("<#{channel}>")
+            )),
+            None => parts.push(t!(language, "clear-on-server")),
+        }
+        if let Some(pattern) = &self.pattern {
+            parts.push(t!(language, "clear-matching", pattern));
+        }
+        let kinds: Vec<_> = self
+            .kinds()
+            .into_iter()
+            .map(|key| text(language, key, &[]))
+            .collect();
+        if !kinds.is_empty() {
+            let kinds = kinds.join(&t!(language, "clear-kinds-or"));
+            parts.push(t!(language, "clear-with-kinds", kinds));
+        }
+        if let Some(after) = self.after {
+            parts.push(t!(language, "clear-after", time = format!("<t:{after}:f>")));
+        }
+        if let Some(before) = self.before {
+            parts.push(t!(
+                language,
+                "clear-before",
+                time = format!("<t:{before}:f>")
+            ));
+        }
+        parts.join(" ")
+    }
+}
 
+/// Deletes all messages of the users on this server, further users separated by spaces or commas
 #[poise::command(slash_command, default_member_permissions = "BAN_MEMBERS", guild_only)]
+#[allow(clippy::too_many_arguments)]
 pub async fn clear(
-    ctx: Context<'_, Arc<Database>, anyhow::Error>,
+    ctx: Context<'_, Arc<Db>, anyhow::Error>,
     user: UserId,
+    more_users: Option<String>,
+    before: Option<String>,
+    after: Option<String>,
+    attachments_only: Option<bool>,
+    embeds_only: Option<bool>,
+    links_only: Option<bool>,
+    invites_only: Option<bool>,
+    dry_run: Option<bool>,
+    archive: Option<bool>,
 ) -> anyhow::Result<()> {
-    let ar = CreateActionRow::Buttons(Vec::from([
-        CreateButton::new(
-            serde_json::to_string(&UserAction::Clear(Some((ctx.guild_id().unwrap(), user))))
-                .unwrap(),
-        )
-        .label("Ich bin sicher")
-        .style(poise::serenity_prelude::ButtonStyle::Danger),
-        CreateButton::new(serde_json::to_string(&UserAction::Clear(None)).unwrap())
-            .label("Abbrechen")
-            .style(poise::serenity_prelude::ButtonStyle::Secondary),
-    ]));
-    ctx.send(
-        CreateReply::default()
-            .content(format!(
-                "Sollen wirklich alle Nachrichten auf diesem Server des Nutzers <@{}> gelöscht werden?",
-                user
-            ))
-            .reply(true)
-            .ephemeral(true)
-            .components(vec![ar]),
+    let guild = ctx.guild_id().context("Not in a guild")?;
+    let mut users = vec![user.get()];
+    for user in more_users
+        .as_deref()
+        .map(parse_users)
+        .transpose()?
+        .unwrap_or_default()
+    {
+        if !users.contains(&user) {
+            users.push(user);
+        }
+    }
+    if users.len() > MAX_USERS {
+        anyhow::bail!(localized!("clear-too-many-users", max = MAX_USERS));
+    }
+    let (before, after) = parse_range(&ctx, guild, before, after)?;
+    users.sort_unstable();
+    confirm(
+        ctx,
+        ClearJob {
+            guild: guild.get(),
+            users,
+            before,
+            after,
+            attachments_only: attachments_only.unwrap_or_default(),
+            embeds_only: embeds_only.unwrap_or_default(),
+            links_only: links_only.unwrap_or_default(),
+            invites_only: invites_only.unwrap_or_default(),
+            archive: archive.unwrap_or_default(),
+            ..Default::default()
+        },
+        dry_run.unwrap_or_default(),
     )
-    .await?;
-    Ok(())
+    .await
+}
+
+/// Starts the same confirmation as /clear for the user, without copying the id
+#[command(
+    context_menu_command = "Nachrichten dieses Nutzers löschen",
+    default_member_permissions = "BAN_MEMBERS",
+    guild_only
+)]
+pub async fn clear_menu(
+    ctx: Context<'_, Arc<Db>, anyhow::Error>,
+    user: User,
+) -> anyhow::Result<()> {
+    let guild = ctx.guild_id().context("Not in a guild")?;
+    confirm(
+        ctx,
+        ClearJob {
+            guild: guild.get(),
+            users: vec![user.id.get()],
+            ..Default::default()
+        },
+        false,
+    )
+    .await
+}
+
+/// Deletes all messages of the current members of the role on this server
+#[command(slash_command, default_member_permissions = "BAN_MEMBERS", guild_only)]
+pub async fn clear_role(
+    ctx: Context<'_, Arc<Db>, anyhow::Error>,
+    role: RoleId,
+    before: Option<String>,
+    after: Option<String>,
+    dry_run: Option<bool>,
+    archive: Option<bool>,
+) -> anyhow::Result<()> {
+    let guild = ctx.guild_id().context("Not in a guild")?;
+    let (before, after) = parse_range(&ctx, guild, before, after)?;
+    ctx.defer_ephemeral().await?;
+    let mut users = Vec::new();
+    let mut last = None;
+    loop {
+        let members = guild.members(ctx, Some(MEMBERS_PAGE), last).await?;
+        let Some(member) = members.last() else {
+            break;
+        };
+        last = Some(member.user.id);
+        users.extend(
+            members
+                .iter()
+                .filter(|member| member.roles.contains(&role))
+                .map(|member| member.user.id.get()),
+        );
+        if members.len() < MEMBERS_PAGE as usize {
+            break;
+        }
+    }
+    if users.is_empty() {
+        anyhow::bail!(localized!("clear-role-empty"));
+    }
+    users.sort_unstable();
+    confirm(
+        ctx,
+        ClearJob {
+            guild: guild.get(),
+            users,
+            role: Some(role.get()),
+            before,
+            after,
+            archive: archive.unwrap_or_default(),
+            ..Default::default()
+        },
+        dry_run.unwrap_or_default(),
+    )
+    .await
 }
 
+/// Deletes all messages in this channel
 #[command(
     slash_command,
     default_member_permissions = "MANAGE_CHANNELS",
     guild_only
 )]
-pub async fn clear_all(ctx: Context<'_, Arc<Database>, anyhow::Error>) -> anyhow::Result<()> {
-    let ar = CreateActionRow::Buttons(Vec::from([
-        CreateButton::new(
-            serde_json::to_string(&UserAction::ClearAll(Some(ctx.channel_id()))).unwrap(),
+#[allow(clippy::too_many_arguments)]
+pub async fn clear_all(
+    ctx: Context<'_, Arc<Db>, anyhow::Error>,
+    before: Option<String>,
+    after: Option<String>,
+    attachments_only: Option<bool>,
+    embeds_only: Option<bool>,
+    links_only: Option<bool>,
+    invites_only: Option<bool>,
+    dry_run: Option<bool>,
+    archive: Option<bool>,
+) -> anyhow::Result<()> {
+    let guild = ctx.guild_id().context("Not in a guild")?;
+    let (before, after) = parse_range(&ctx, guild, before, after)?;
+    confirm(
+        ctx,
+        ClearJob {
+            guild: guild.get(),
+            channel: Some(ctx.channel_id().get()),
+            before,
+            after,
+            attachments_only: attachments_only.unwrap_or_default(),
+            embeds_only: embeds_only.unwrap_or_default(),
+            links_only: links_only.unwrap_or_default(),
+            invites_only: invites_only.unwrap_or_default(),
+            archive: archive.unwrap_or_default(),
+            ..Default::default()
+        },
+        dry_run.unwrap_or_default(),
+    )
+    .await
+}
+
+/// Deletes the messages matching the regex, in one or all channels of this server
+#[command(
+    slash_command,
+    default_member_permissions = "MANAGE_MESSAGES",
+    guild_only
+)]
+#[allow(clippy::too_many_arguments)]
+pub async fn clear_filter(
+    ctx: Context<'_, Arc<Db>, anyhow::Error>,
+    pattern: String,
+    channel: Option<ChannelId>,
+    before: Option<String>,
+    after: Option<String>,
+    attachments_only: Option<bool>,
+    embeds_only: Option<bool>,
+    links_only: Option<bool>,
+    invites_only: Option<bool>,
+    dry_run: Option<bool>,
+    archive: Option<bool>,
+) -> anyhow::Result<()> {
+    if let Err(err) = Regex::new(&pattern) {
+        ctx.send(
+            CreateReply::default()
+                .content(t!(language(ctx), "clear-invalid-regex", err))
+                .allowed_mentions(CreateAllowedMentions::new())
+                .ephemeral(true),
         )
-        .label("Ich bin sicher")
-        .style(poise::serenity_prelude::ButtonStyle::Danger),
-        CreateButton::new(serde_json::to_string(&UserAction::ClearAll(None)).unwrap())
-            .label("Abbrechen")
-            .style(poise::serenity_prelude::ButtonStyle::Secondary),
-    ]));
+        .await?;
+        return Ok(());
+    }
+    let guild = ctx.guild_id().context("Not in a guild")?;
+    let (before, after) = parse_range(&ctx, guild, before, after)?;
+    confirm(
+        ctx,
+        ClearJob {
+            guild: guild.get(),
+            channel: channel.map(|channel| channel.get()),
+            pattern: Some(pattern),
+            before,
+            after,
+            attachments_only: attachments_only.unwrap_or_default(),
+            embeds_only: embeds_only.unwrap_or_default(),
+            links_only: links_only.unwrap_or_default(),
+            invites_only: invites_only.unwrap_or_default(),
+            archive: archive.unwrap_or_default(),
+            ..Default::default()
+        },
+        dry_run.unwrap_or_default(),
+    )
+    .await
+}
+
+/// Deletes the last messages in this channel
+#[command(
+    slash_command,
+    default_member_permissions = "MANAGE_MESSAGES",
+    guild_only
+)]
+pub async fn purge(
+    ctx: Context<'_, Arc<Db>, anyhow::Error>,
+    #[min = 1]
+    #[max = 1000]
+    count: u32,
+) -> anyhow::Result<()> {
+    ctx.defer_ephemeral().await?;
+    let guild = ctx.guild_id().context("Not in a guild")?;
+    let channel = ctx.channel_id();
+    let language = language(ctx);
+    let modlog = modlog(ctx.data(), guild)?;
+    let mut cleared = Cleared::new(modlog.is_some_and(|modlog| modlog.transcript), false);
+    let messages = last_messages(&ctx, channel, count as usize).await?;
+    delete_messages(&ctx, channel, messages, &mut cleared).await;
+    let job = ClearJob {
+        guild: guild.get(),
+        channel: Some(channel.get()),
+        ..Default::default()
+    };
     ctx.send(
         CreateReply::default()
-            .content("Soll dieser Kanal wirklich geleert werden?")
-            .reply(true)
-            .ephemeral(true)
-            .components(vec![ar]),
+            .content(cleared.report(&job, language))
+            .allowed_mentions(CreateAllowedMentions::new())
+            .ephemeral(true),
     )
     .await?;
+    log_clear(&ctx, modlog, ctx.author().id, &job, cleared, language).await;
+    Ok(())
+}
+
+/// Removes all reactions from a message, or from the last messages of a channel
+#[command(
+    slash_command,
+    default_member_permissions = "MANAGE_MESSAGES",
+    guild_only
+)]
+pub async fn clear_reactions(
+    ctx: Context<'_, Arc<Db>, anyhow::Error>,
+    target: String,
+    #[min = 1]
+    #[max = 1000]
+    count: Option<u32>,
+) -> anyhow::Result<()> {
+    ctx.defer_ephemeral().await?;
+    let guild = ctx.guild_id().context("Not in a guild")?;
+    let language = language(ctx);
+    let content = if let Some((message_guild, channel, message)) = parse_message_url(&target) {
+        if message_guild != guild {
+            anyhow::bail!(localized!("clear-message-elsewhere"));
+        }
+        channel.delete_reactions(ctx, message).await?;
+        t!(language, "clear-reactions-message")
+    } else {
+        let channel = parse_channel_mention(target.trim())
+            //  Parsing the id directly rejects 0, which ChannelId::new would panic on
+            .or_else(|| target.trim().parse::<ChannelId>().ok())
+            .context(localized!("clear-reactions-target"))?;
+        if channel.to_channel(ctx).await?.guild().map(|c| c.guild_id) != Some(guild) {
+            anyhow::bail!(localized!("clear-channel-elsewhere"));
+        }
+        let count = count.unwrap_or(REACTIONS_COUNT) as usize;
+        let mut cleared = 0;
+        for mes in last_messages(&ctx, channel, count).await? {
+            if !mes.reactions.is_empty() && mes.delete_reactions(ctx).await.is_ok() {
+                cleared += 1;
+            }
+        }
+        t!(
+            language,
+            "clear-reactions-channel",
+            count = cleared,
+            channel = channel.mention()
+        )
+    };
+    ctx.send(CreateReply::default().content(content).ephemeral(true))
+        .await?;
     Ok(())
 }
 
-pub async fn clear_user(
+//  Newest first, as many as the channel has up to the count
+async fn last_messages(
     http: &impl CacheHttp,
+    channel: ChannelId,
+    count: usize,
+) -> anyhow::Result<Vec<Message>> {
+    let mut messages = Vec::with_capacity(count);
+    while messages.len() < count {
+        let mut builder = GetMessages::new().limit((count - messages.len()).min(BULK_COUNT) as u8);
+        if let Some(last) = messages.last() {
+            builder = builder.before(last);
+        }
+        let page = channel.messages(http, builder).await?;
+        if page.is_empty() {
+            break;
+        }
+        messages.extend(page);
+    }
+    Ok(messages)
+}
+
+/// Bulk deletes the recent messages, older ones have to be deleted one by one
+pub async fn delete_messages(
+    http: &impl CacheHttp,
+    channel: ChannelId,
+    messages: Vec<Message>,
+    cleared: &mut Cleared,
+) {
+    //  A minute of leeway, in case the request takes a while
+    let bulk_since = Utc::now() - BULK_AGE + TimeDelta::minutes(1);
+    let (recent, old): (Vec<_>, Vec<_>) = messages
+        .into_iter()
+        .partition(|mes| *mes.timestamp > bulk_since);
+    for chunk in recent.chunks(BULK_COUNT) {
+        match retry(|| channel.delete_messages(http.http(), chunk.iter().map(|mes| mes.id))).await {
+            Ok(()) => chunk.iter().for_each(|mes| {
+                metrics().message_cleared();
+                cleared.record(mes);
+            }),
+            //  A single bad message fails the whole batch, so the rest is deleted one by one
+            Err(err) => {
+                metrics().discord_error(&err);
+                error!("Error deleting messages: {}", err);
+                for mes in chunk {
+                    delete_message(http, mes, cleared).await;
+                }
+            }
+        }
+    }
+    for mes in &old {
+        delete_message(http, mes, cleared).await;
+    }
+}
+
+async fn delete_message(http: &impl CacheHttp, mes: &Message, cleared: &mut Cleared) {
+    match retry(|| mes.delete(http)).await {
+        Ok(()) => {
+            metrics().message_cleared();
+            cleared.record(mes);
+        }
+        //  Unknown message, someone else was faster
+        Err(Error::Http(HttpError::UnsuccessfulRequest(ErrorResponse {
+            error: DiscordJsonError { code: 10008, .. },
+            ..
+        }))) => {}
+        Err(err) => cleared.fail(&err),
+    }
+}
+
+//  Rate limits are waited out by serenity itself, using the retry-after of Discord. Everything
+//  else, that might go away, is retried with exponential backoff.
+async fn retry<F: Future<Output = Result<(), Error>>>(
+    mut request: impl FnMut() -> F,
+) -> Result<(), Error> {
+    let mut delay = RETRY_DELAY;
+    let mut attempt = 1;
+    loop {
+        match request().await {
+            Err(err) if attempt < RETRY_ATTEMPTS && transient(&err) => {
+                tokio::time::sleep(delay).await;
+                delay *= 2;
+                attempt += 1;
+            }
+            result => return result,
+        }
+    }
+}
+
+fn transient(err: &Error) -> bool {
+    match err {
+        Error::Http(HttpError::UnsuccessfulRequest(response)) => {
+            response.status_code == StatusCode::TOO_MANY_REQUESTS
+                || response.status_code.is_server_error()
+        }
+        Error::Http(HttpError::Request(_)) => true,
+        _ => false,
+    }
+}
+
+//  The times are given in the timezone of the guild, just like for giveaways
+fn parse_range(
+    ctx: &Context<'_, Arc<Db>, anyhow::Error>,
     guild: GuildId,
-    user: UserId,
-) -> anyhow::Result<usize> {
-    let mut count = 0usize;
-    for (channel, _) in guild.channels(http.http()).await? {
-        let fut = channel.messages_iter(http.http()).filter(|mes| {
-            futures::future::ready(mes.as_ref().is_ok_and(|mes| mes.author.id == user))
-        });
-        pin!(fut);
-        while let Some(Ok(mes)) = fut.next().await {
-            if mes.delete(http).await.is_ok() {
-                count += 1;
+    before: Option<String>,
+    after: Option<String>,
+) -> anyhow::Result<(Option<i64>, Option<i64>)> {
+    let settings = ctx.data().settings(guild)?;
+    let tz = settings.timezone;
+    let default_time = settings.default_time();
+    let before = before
+        .map(|before| parse_time_arg(&before, tz, default_time).map(|time| time.utc.timestamp()))
+        .transpose()?;
+    let after = after
+        .map(|after| parse_time_arg(&after, tz, default_time).map(|time| time.utc.timestamp()))
+        .transpose()?;
+    if let (Some(before), Some(after)) = (before, after)
+        && after >= before
+    {
+        anyhow::bail!(localized!("clear-range-order"));
+    }
+    Ok((before, after))
+}
+
+//  Stores the job and asks for confirmation, only the button starts the clear. A dry run only
+//  counts the messages instead. The filter shows the pattern, so nothing in it may ping.
+async fn confirm(
+    ctx: Context<'_, Arc<Db>, anyhow::Error>,
+    job: ClearJob,
+    dry_run: bool,
+) -> anyhow::Result<()> {
+    let language = language(ctx);
+    let filter = job.describe(language);
+    if dry_run {
+        ctx.defer_ephemeral().await?;
+        let cleared = clear_messages(&ctx, &job, true, None, false).await?;
+        let count = cleared.count;
+        let content = match cleared.limited {
+            true => t!(
+                language,
+                "clear-dry-run-limit",
+                count,
+                scanned = DRY_RUN_LIMIT,
+                filter
+            ),
+            false => t!(language, "clear-dry-run", count, filter),
+        };
+        ctx.send(
+            CreateReply::default()
+                .content(content)
+                .allowed_mentions(CreateAllowedMentions::new())
+                .ephemeral(true),
+        )
+        .await?;
+        return Ok(());
+    }
+    let id: u64 = rand::random();
+    let job = ClearJob {
+        created: Utc::now().timestamp(),
+        ..job
+    };
+    let content = match job.whole_channel() {
+        Some(_) => t!(language, "clear-confirm-channel"),
+        None => t!(language, "clear-confirm", filter),
+    };
+    {
+        let db = ctx.data().begin_write()?;
+        {
+            let mut table = db.open_table(CLEAR_JOBS_TABLE)?;
+            table.insert(id, job)?;
+        }
+        db.commit()?;
+    }
+    let reply = ctx
+        .send(
+            CreateReply::default()
+                .content(content)
+                .allowed_mentions(CreateAllowedMentions::new())
+                .reply(true)
+                .ephemeral(true)
+                .components(vec![confirm_buttons(id, false, language)]),
+        )
+        .await?;
+    //  Confirmed or aborted prompts were already changed by the button
+    tokio::time::sleep(CONFIRM_TIMEOUT).await;
+    if take_expired_job(ctx.data(), id)? {
+        reply
+            .edit(
+                ctx,
+                CreateReply::default()
+                    .content(t!(language, "clear-expired"))
+                    .components(vec![confirm_buttons(id, true, language)]),
+            )
+            .await?;
+    }
+    Ok(())
+}
+
+fn confirm_buttons(id: u64, disabled: bool, language: Language) -> CreateActionRow {
+    CreateActionRow::Buttons(Vec::from([
+        CreateButton::new(serde_json::to_string(&UserAction::Clear(id, true)).unwrap())
+            .label(t!(language, "confirm-yes"))
+            .style(poise::serenity_prelude::ButtonStyle::Danger)
+            .disabled(disabled),
+        CreateButton::new(serde_json::to_string(&UserAction::Clear(id, false)).unwrap())
+            .label(t!(language, "confirm-no"))
+            .style(poise::serenity_prelude::ButtonStyle::Secondary)
+            .disabled(disabled),
+    ]))
+}
+
+//  Returns true, if the job was still waiting for confirmation and is removed now
+fn take_expired_job(db: &Db, id: u64) -> anyhow::Result<bool> {
+    let db = db.begin_write()?;
+    let expired = {
+        let mut table = db.open_table(CLEAR_JOBS_TABLE)?;
+        let expired = table.get(id)?.is_some_and(|job| job.value().expired());
+        if expired {
+            table.remove(id)?;
+        }
+        expired
+    };
+    db.commit()?;
+    Ok(expired)
+}
+
+/// The stop button reuses the abort of the confirmation, both remove the job
+pub fn stop_button(id: u64, language: Language) -> CreateActionRow {
+    CreateActionRow::Buttons(Vec::from([CreateButton::new(
+        serde_json::to_string(&UserAction::Clear(id, false)).unwrap(),
+    )
+    .label(t!(language, "clear-button-stop"))
+    .style(poise::serenity_prelude::ButtonStyle::Danger)]))
+}
+
+/// Stores how far a running clear got and edits the confirmation to show it. Resumed clears have
+/// no confirmation anymore.
+pub struct Progress<'a> {
+    interaction: Option<&'a ComponentInteraction>,
+    db: &'a Db,
+    id: u64,
+    reported: usize,
+    last: Instant,
+    language: Language,
+}
+
+impl<'a> Progress<'a> {
+    pub fn new(
+        interaction: Option<&'a ComponentInteraction>,
+        db: &'a Db,
+        id: u64,
+        language: Language,
+    ) -> Self {
+        Self {
+            interaction,
+            db,
+            id,
+            reported: 0,
+            last: Instant::now(),
+            language,
+        }
+    }
+
+    //  Returns false, once the job was stopped
+    async fn update(
+        &mut self,
+        http: &impl CacheHttp,
+        count: usize,
+        channel: ChannelId,
+        before: Option<MessageId>,
+    ) -> bool {
+        match checkpoint(self.db, self.id, channel, before, count) {
+            Ok(true) => {}
+            Ok(false) => return false,
+            Err(err) => error!("Error storing clear job: {}", err),
+        }
+        if count < self.reported + PROGRESS_COUNT && self.last.elapsed() < PROGRESS_INTERVAL {
+            return true;
+        }
+        self.reported = count;
+        self.last = Instant::now();
+        let Some(interaction) = self.interaction else {
+            return true;
+        };
+        if let Err(err) = interaction
+            .edit_response(
+                http,
+                EditInteractionResponse::new()
+                    .content(t!(
+                        self.language,
+                        "clear-progress",
+                        count,
+                        channel = channel.mention()
+                    ))
+                    .components(vec![stop_button(self.id, self.language)]),
+            )
+            .await
+        {
+            metrics().discord_error(&err);
+            error!("Error updating progress: {}", err);
+        }
+        true
+    }
+}
+
+//  Returns false, if the job was removed in the meantime
+fn checkpoint(
+    db: &Db,
+    id: u64,
+    channel: ChannelId,
+    before: Option<MessageId>,
+    count: usize,
+) -> anyhow::Result<bool> {
+    let db = db.begin_write()?;
+    let exists = {
+        let mut table = db.open_table(CLEAR_JOBS_TABLE)?;
+        let job = table.get(id)?.map(|v| v.value());
+        match job {
+            Some(mut job) => {
+                job.cursor = Some((channel.get(), before.map(|before| before.get())));
+                job.count = count as u64;
+                table.insert(id, &job)?;
+                true
+            }
+            None => false,
+        }
+    };
+    db.commit()?;
+    Ok(exists)
+}
+
+/// Jobs, that don't exist anymore, are left to the handler to report
+pub fn may_clear(db: &Db, id: u64, member: &Member) -> anyhow::Result<bool> {
+    let db_read = db.begin_read()?;
+    let table = db_read.open_table(CLEAR_JOBS_TABLE)?;
+    Ok(table
+        .get(id)?
+        .is_none_or(|job| job.value().permitted(member)))
+}
+
+/// Marks the job as running, returns None if it doesn't exist, is already running or expired
+pub fn start_clear_job(db: &Db, id: u64, moderator: UserId) -> anyhow::Result<Option<ClearJob>> {
+    let db = db.begin_write()?;
+    let job = {
+        let mut table = db.open_table(CLEAR_JOBS_TABLE)?;
+        let job = table.get(id)?.map(|v| v.value());
+        match job {
+            Some(job) if job.expired() => {
+                table.remove(id)?;
+                None
+            }
+            Some(mut job) if !job.running => {
+                job.running = true;
+                job.moderator = Some(moderator.get());
+                table.insert(id, &job)?;
+                Some(job)
+            }
+            _ => None,
+        }
+    };
+    db.commit()?;
+    Ok(job)
+}
+
+/// Removes the job, which also stops it if it's running
+pub fn take_clear_job(db: &Db, id: u64) -> anyhow::Result<Option<ClearJob>> {
+    let db = db.begin_write()?;
+    let job = {
+        let mut table = db.open_table(CLEAR_JOBS_TABLE)?;
+        table.remove(id)?.map(|v| v.value())
+    };
+    db.commit()?;
+    Ok(job)
+}
+
+/// The messages come newest first, so a channel is done as soon as one is older than the lower
+/// bound. Matches are deleted page by page, a dry run only counts them. Channels are cleared in
+/// the order of their ids, so a resumed job can skip the ones before its cursor.
+pub async fn clear_messages(
+    http: &impl CacheHttp,
+    job: &ClearJob,
+    dry_run: bool,
+    mut progress: Option<Progress<'_>>,
+    transcript: bool,
+) -> anyhow::Result<Cleared> {
+    let regex = job.pattern.as_deref().map(Regex::new).transpose()?;
+    let mut channels: Vec<ChannelId> = match job.channel {
+        Some(channel) => vec![ChannelId::new(channel)],
+        None => GuildId::new(job.guild)
+            .channels(http.http())
+            .await?
+            .into_keys()
+            .collect(),
+    };
+    channels.sort_unstable();
+    if let Some((current, _)) = job.cursor {
+        channels.retain(|channel| channel.get() >= current);
+    }
+    let mut cleared = Cleared::new(transcript && !dry_run, job.archive && !dry_run);
+    cleared.count = job.count as usize;
+    let mut scanned = 0;
+    for channel in channels {
+        let mut before = job
+            .cursor
+            .filter(|(current, _)| *current == channel.get())
+            .and_then(|(_, before)| before)
+            .map(MessageId::new);
+        loop {
+            if let Some(progress) = &mut progress
+                && !progress.update(http, cleared.count, channel, before).await
+            {
+                cleared.stopped = true;
+                return Ok(cleared);
+            }
+            let mut builder = GetMessages::new().limit(BULK_COUNT as u8);
+            if let Some(before) = before {
+                builder = builder.before(before);
+            }
+            //  Categories and channels the bot can't read have no messages to clear
+            let Ok(page) = channel.messages(http, builder).await else {
+                break;
+            };
+            let Some(oldest) = page.last() else {
+                break;
+            };
+            before = Some(oldest.id);
+            scanned += page.len();
+            let complete = page.len() < BULK_COUNT;
+            let mut reached_bound = false;
+            let mut batch = Vec::new();
+            for mes in page {
+                if job
+                    .after
+                    .is_some_and(|after| mes.timestamp.unix_timestamp() <= after)
+                {
+                    reached_bound = true;
+                    break;
+                }
+                if !job.matches(&mes, regex.as_ref()) {
+                    continue;
+                }
+                if dry_run {
+                    cleared.record(&mes);
+                } else {
+                    batch.push(mes);
+                }
+            }
+            if dry_run && scanned >= DRY_RUN_LIMIT {
+                cleared.limited = true;
+                return Ok(cleared);
+            }
+            delete_messages(http, channel, batch, &mut cleared).await;
+            if complete || reached_bound {
+                break;
             }
         }
     }
-    Ok(count)
+    Ok(cleared)
 }
 
-pub async fn clear_channel(http: &impl CacheHttp, channel: ChannelId) -> anyhow::Result<()> {
-    let fut = channel.messages_iter(http.http());
-    pin!(fut);
-    while let Some(Ok(mes)) = fut.next().await {
-        mes.delete(http).await?;
+/// Continues the clears interrupted by a restart. The confirmation can't be edited anymore, so the
+/// report goes to the moderator directly.
+pub async fn resume_clears(db: Arc<Db>, http: impl CacheHttp) {
+    let jobs = match running_jobs(&db) {
+        Ok(jobs) => jobs,
+        Err(err) => {
+            error!("Error reading clear jobs: {}", err);
+            return;
+        }
+    };
+    //  A failing job stays stored and is tried again after the next restart, the others go on
+    for (id, job) in jobs {
+        if let Err(err) = resume_clear(&db, &http, id, &job).await {
+            metrics().discord_error(err.as_ref());
+            error!("Error resuming clear: {}", err);
+        }
+    }
+}
+
+//  Prompts from before the restart have long expired
+fn running_jobs(db: &Db) -> anyhow::Result<Vec<(u64, ClearJob)>> {
+    let db = db.begin_write()?;
+    let jobs = {
+        let mut table = db.open_table(CLEAR_JOBS_TABLE)?;
+        table.retain(|_, job| job.running)?;
+        table
+            .iter()?
+            .filter_map(|entry| entry.ok())
+            .map(|(id, job)| (id.value(), job.value()))
+            .collect()
+    };
+    db.commit()?;
+    Ok(jobs)
+}
+
+async fn resume_clear(
+    db: &Db,
+    http: &impl CacheHttp,
+    id: u64,
+    job: &ClearJob,
+) -> anyhow::Result<()> {
+    let guild = GuildId::new(job.guild);
+    let modlog = modlog(db, guild)?;
+    let language = db.settings(guild)?.language;
+    let cleared = clear_messages(
+        http,
+        job,
+        false,
+        Some(Progress::new(None, db, id, language)),
+        modlog.is_some_and(|modlog| modlog.transcript),
+    )
+    .await?;
+    take_clear_job(db, id)?;
+    if let Some(moderator) = job.moderator.map(UserId::new) {
+        let report = t!(
+            language,
+            "clear-resumed",
+            report = cleared.report(job, language)
+        );
+        if let Err(err) = moderator
+            .direct_message(
+                http,
+                CreateMessage::new()
+                    .content(report)
+                    .allowed_mentions(CreateAllowedMentions::new()),
+            )
+            .await
+        {
+            metrics().discord_error(&err);
+            error!("Error reporting resumed clear: {}", err);
+        }
+        log_clear(http, modlog, moderator, job, cleared, language).await;
     }
     Ok(())
 }
+
+/// What a clear deleted, for the moderation log
+#[derive(Debug, Default)]
+pub struct Cleared {
+    pub count: usize,
+    pub channels: BTreeMap<ChannelId, usize>,
+    /// Only kept, if the moderation log wants a transcript, up to the file limit
+    pub transcript: Option<String>,
+    /// Messages, that didn't fit into the transcript anymore
+    pub transcript_truncated: bool,
+    /// One JSON object per line, only kept if the job archives its messages. Split into parts
+    /// below the file limit.
+    pub archive: Option<Vec<String>>,
+    /// Stopped by a moderator before it was done
+    pub stopped: bool,
+    /// A dry run, that looked at too many messages to go on
+    pub limited: bool,
+    /// Messages, that couldn't be deleted, counted by the reason
+    pub failed: BTreeMap<Failure, usize>,
+}
+
+/// Why a message couldn't be deleted, unexpected errors keep their own message
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Failure {
+    Forbidden,
+    RateLimited,
+    Discord,
+    Connection,
+    Other(String),
+}
+
+impl Failure {
+    fn text(&self, language: Language) -> String {
+        match self {
+            Failure::Forbidden => t!(language, "clear-failure-forbidden"),
+            Failure::RateLimited => t!(language, "clear-failure-rate-limit"),
+            Failure::Discord => t!(language, "clear-failure-discord"),
+            Failure::Connection => t!(language, "clear-failure-connection"),
+            Failure::Other(message) => message.clone(),
+        }
+    }
+}
+
+impl Cleared {
+    pub fn new(transcript: bool, archive: bool) -> Self {
+        Self {
+            transcript: transcript.then(String::new),
+            archive: archive.then(Vec::new),
+            ..Default::default()
+        }
+    }
+
+    fn fail(&mut self, err: &Error) {
+        metrics().discord_error(err);
+        let reason = match err {
+            Error::Http(HttpError::UnsuccessfulRequest(response)) => {
+                match response.status_code.as_u16() {
+                    403 => Failure::Forbidden,
+                    429 => Failure::RateLimited,
+                    500.. => Failure::Discord,
+                    _ => Failure::Other(response.error.message.clone()),
+                }
+            }
+            Error::Model(_) => Failure::Forbidden,
+            Error::Http(HttpError::Request(_)) => Failure::Connection,
+            err => Failure::Other(err.to_string()),
+        };
+        *self.failed.entry(reason).or_default() += 1;
+    }
+
+    /// The reply for the moderator, once the clear is done
+    pub fn report(&self, job: &ClearJob, language: Language) -> String {
+        let filter = job.describe(language);
+        let mut report = match self.stopped {
+            true => t!(language, "clear-report-stopped", count = self.count, filter),
+            false => t!(language, "clear-report", count = self.count, filter),
+        };
+        if let Some(failures) = self.failures(language) {
+            report.push_str(&format!("\n{failures}"));
+        }
+        report
+    }
+
+    /// Summary of the failed deletions, if there were any
+    fn failures(&self, language: Language) -> Option<String> {
+        if self.failed.is_empty() {
+            return None;
+        }
+        let reasons: Vec<_> = self
+            .failed
+            .iter()
+            .map(|(reason, count)| format!("{}: {count}", reason.text(language)))
+            .collect();
+        Some(t!(
+            language,
+            "clear-failures",
+            count = self.failed.values().sum::<usize>(),
+            reasons = reasons.join(", ")
+        ))
+    }
+
+    fn record(&mut self, mes: &Message) {
+        self.count += 1;
+        *self.channels.entry(mes.channel_id).or_default() += 1;
+        if let Some(transcript) = &mut self.transcript {
+            if transcript.len() >= FILE_LIMIT {
+                self.transcript_truncated = true;
+            } else {
+                let _ = writeln!(
+                    transcript,
+                    "[{}] #{} {} ({}): {}",
+                    mes.timestamp, mes.channel_id, mes.author.name, mes.author.id, mes.content
+                );
+                for attachment in &mes.attachments {
+                    let _ = writeln!(transcript, "    {}", attachment.url);
+                }
+            }
+        }
+        if let Some(archive) = &mut self.archive {
+            let archived = ArchivedMessage {
+                id: mes.id.get(),
+                channel: mes.channel_id.get(),
+                author: mes.author.id.get(),
+                author_name: &mes.author.name,
+                timestamp: mes.timestamp.to_string(),
+                content: &mes.content,
+                attachments: mes
+                    .attachments
+                    .iter()
+                    .map(|attachment| attachment.url.as_str())
+                    .collect(),
+            };
+            if let Ok(line) = serde_json::to_string(&archived) {
+                match archive.last_mut() {
+                    Some(part) if part.len() + line.len() < FILE_LIMIT => part.push_str(&line),
+                    _ => archive.push(line),
+                }
+                archive.last_mut().unwrap().push('\n');
+            }
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct ArchivedMessage<'a> {
+    id: u64,
+    channel: u64,
+    author: u64,
+    author_name: &'a str,
+    timestamp: String,
+    content: &'a str,
+    attachments: Vec<&'a str>,
+}
+
+/// The moderation log of the guild, if it has one
+pub fn modlog(db: &Db, guild: GuildId) -> anyhow::Result<Option<Modlog>> {
+    Ok(db.settings(guild)?.modlog)
+}
+
+//  The messages are gone either way, so a failed log is only printed. Without a moderation log
+//  the archive goes to the moderator instead.
+pub async fn log_clear(
+    http: &impl CacheHttp,
+    modlog: Option<Modlog>,
+    moderator: UserId,
+    job: &ClearJob,
+    cleared: Cleared,
+    language: Language,
+) {
+    let archive: Vec<_> = cleared
+        .archive
+        .iter()
+        .flatten()
+        .enumerate()
+        .map(|(part, archive)| {
+            let name = match part {
+                0 => "clear.ndjson".to_string(),
+                part => format!("clear-{}.ndjson", part + 1),
+            };
+            CreateAttachment::bytes(archive.as_bytes(), name)
+        })
+        .collect();
+    let heading = t!(
+        language,
+        "clear-archive",
+        count = cleared.count,
+        filter = job.describe(language)
+    );
+    let Some(modlog) = modlog else {
+        if archive.is_empty() {
+            return;
+        }
+        match moderator.create_dm_channel(http).await {
+            Ok(channel) => send_archive(http, channel.id, archive, heading, language).await,
+            Err(err) => {
+                metrics().discord_error(&err);
+                error!("Error sending archive: {}", err);
+            }
+        }
+        return;
+    };
+    let mut content = match cleared.stopped {
+        true => t!(
+            language,
+            "clear-log-stopped",
+            moderator = moderator.mention(),
+            count = cleared.count,
+            filter = job.describe(language)
+        ),
+        false => t!(
+            language,
+            "clear-log",
+            moderator = moderator.mention(),
+            count = cleared.count,
+            filter = job.describe(language)
+        ),
+    };
+    if let Some(failures) = cleared.failures(language) {
+        content.push_str(&format!("\n{failures}"));
+    }
+    if !cleared.channels.is_empty() {
+        let mut channels: Vec<_> = cleared
+            .channels
+            .iter()
+            .take(LOG_CHANNELS)
+            .map(|(channel, count)| format!("<#{channel}> ({count})"))
+            .collect();
+        if cleared.channels.len() > LOG_CHANNELS {
+            channels.push(t!(
+                language,
+                "clear-log-more-channels",
+                count = cleared.channels.len() - LOG_CHANNELS
+            ));
+        }
+        content.push('\n');
+        content.push_str(&t!(
+            language,
+            "clear-log-channels",
+            channels = channels.join(", ")
+        ));
+    }
+    if cleared.transcript_truncated {
+        content.push('\n');
+        content.push_str(&t!(language, "clear-log-transcript-truncated"));
+    }
+    let mut files = Vec::new();
+    if let Some(transcript) = cleared.transcript
+        && !transcript.is_empty()
+    {
+        files.push(CreateAttachment::bytes(transcript, "clear.txt"));
+    }
+    let channel = ChannelId::new(modlog.channel);
+    let message = CreateMessage::new()
+        .content(content.clone())
+        .allowed_mentions(CreateAllowedMentions::new());
+    if let Err(err) = channel
+        .send_message(http, message.clone().add_files(files))
+        .await
+    {
+        metrics().discord_error(&err);
+        error!("Error logging clear: {}", err);
+        //  The files might have been too large, the log itself matters more
+        content.push('\n');
+        content.push_str(&t!(language, "clear-log-files-failed"));
+        if let Err(err) = channel.send_message(http, message.content(content)).await {
+            metrics().discord_error(&err);
+            error!("Error logging clear: {}", err);
+        }
+    }
+    send_archive(http, channel, archive, heading, language).await;
+}
+
+//  Together with the transcript or each other the parts of a large archive would exceed the upload
+//  limit, so every part gets its own message
+async fn send_archive(
+    http: &impl CacheHttp,
+    channel: ChannelId,
+    parts: Vec<CreateAttachment>,
+    heading: String,
+    language: Language,
+) {
+    let mut content = heading;
+    for part in parts {
+        let message = CreateMessage::new()
+            .content(std::mem::replace(
+                &mut content,
+                t!(language, "clear-archive-part"),
+            ))
+            .allowed_mentions(CreateAllowedMentions::new())
+            .add_file(part);
+        if let Err(err) = channel.send_message(http, message).await {
+            metrics().discord_error(&err);
+            error!("Error sending archive: {}", err);
+        }
+    }
+}
@@ -0,0 +1,92 @@
+use log::error;
+use poise::serenity_prelude::{CacheHttp, CreateMessage, Mentionable, UserId};
+
+use crate::metrics::metrics;
+use crate::{
+    crypto,
+    locale::t,
+    structs::{Language, SecretCode},
+};
+
+/// Encrypts a code, the database alone is useless without the key, see [`crypto`]
+pub fn seal(code: &str) -> anyhow::Result<SecretCode> {
+    Ok(SecretCode {
+        sealed: crypto::seal(code.as_bytes())?,
+        winner: None,
+    })
+}
+
+fn open(code: &SecretCode) -> anyhow::Result<String> {
+    Ok(String::from_utf8(crypto::open(&code.sealed)?)?)
+}
+
+/// Gives every winner, who doesn't have one yet, one of the remaining codes
+pub fn assign_codes(codes: &mut [SecretCode], winners: &[UserId]) -> Vec<(UserId, SecretCode)> {
+    let mut assigned = Vec::new();
+    for &winner in winners {
+        if codes.iter().any(|code| code.winner == Some(winner.get())) {
+            continue;
+        }
+        let Some(code) = codes.iter_mut().find(|code| code.winner.is_none()) else {
+            break;
+        };
+        code.winner = Some(winner.get());
+        assigned.push((winner, code.clone()));
+    }
+    assigned
+}
+
+//  The codes count as consumed either way, so the host is told about failed messages
+pub async fn send_codes(
+    http: &impl CacheHttp,
+    title: &str,
+    language: Language,
+    host: Option<UserId>,
+    assigned: Vec<(UserId, SecretCode)>,
+) {
+    for (winner, code) in assigned {
+        send_code(http, title, language, host, winner, &code).await;
+    }
+}
+
+/// Sends the code to its winner, or tells the host it couldn't be sent
+pub async fn send_code(
+    http: &impl CacheHttp,
+    title: &str,
+    language: Language,
+    host: Option<UserId>,
+    winner: UserId,
+    code: &SecretCode,
+) {
+    let result = match open(code) {
+        Ok(code) => {
+            let text = t!(language, "code-prize", title, code);
+            winner
+                .direct_message(http, CreateMessage::new().content(text))
+                .await
+                .map_err(anyhow::Error::from)
+        }
+        Err(err) => Err(err),
+    };
+    let Err(err) = result else {
+        return;
+    };
+    metrics().discord_error(err.as_ref());
+    error!("Error sending code: {}", err);
+    if let Some(host) = host
+        && let Err(err) = host
+            .direct_message(
+                http,
+                CreateMessage::new().content(t!(
+                    language,
+                    "code-failed",
+                    winner = winner.mention(),
+                    title
+                )),
+            )
+            .await
+    {
+        metrics().discord_error(&err);
+        error!("Error notifying host: {}", err);
+    }
+}
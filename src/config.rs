@@ -0,0 +1,139 @@
+use anyhow::{Context as _, bail};
+use poise::serenity_prelude::validate_token;
+use serde::Deserialize;
+use std::{io::ErrorKind, net::SocketAddr, path::PathBuf};
+
+use crate::logging::LogConfig;
+
+/// Read if it exists, unless another file is given with `--config`
+const DEFAULT_PATH: &str = "config.toml";
+const DEFAULT_DATABASE: &str = "db.redb";
+const DEFAULT_LOG_MB: u64 = 10;
+const DEFAULT_LOG_DAYS: u32 = 14;
+const USAGE: &str = "Usage: do-bot [--config <path>] [export [<guild>]]";
+
+/// The command line, parsed before the config so offline commands don't need a token
+pub struct Args {
+    pub config: Option<String>,
+    pub command: Command,
+}
+
+pub enum Command {
+    /// Connects to Discord, without a subcommand
+    Run,
+    /// Prints the state of one or all guilds as JSON
+    Export { guild: Option<String> },
+}
+
+impl Args {
+    pub fn parse(mut args: impl Iterator<Item = String>) -> anyhow::Result<Self> {
+        let mut config = None;
+        let mut positional = Vec::new();
+        while let Some(arg) = args.next() {
+            match arg.strip_prefix("--config=") {
+                Some(value) => config = Some(value.to_string()),
+                None if arg == "--config" => config = Some(args.next().context(USAGE)?),
+                None if arg.starts_with('-') => bail!("Unknown option {arg}\n{USAGE}"),
+                None => positional.push(arg),
+            }
+        }
+        let mut positional = positional.into_iter();
+        let command = match positional.next().as_deref() {
+            None => Command::Run,
+            Some("export") => Command::Export {
+                guild: positional.next(),
+            },
+            Some(command) => bail!("Unknown command {command}\n{USAGE}"),
+        };
+        if let Some(arg) = positional.next() {
+            bail!("Unexpected argument {arg}\n{USAGE}");
+        }
+        Ok(Self { config, command })
+    }
+}
+
+/// The config file as written, every setting is optional
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ConfigFile {
+    token: Option<String>,
+    database_path: Option<String>,
+    database_url: Option<String>,
+    secret_key: Option<String>,
+    log_dir: Option<PathBuf>,
+    log_max_mb: Option<u64>,
+    log_keep_days: Option<u32>,
+    metrics_addr: Option<SocketAddr>,
+}
+
+/// Settings of the bot itself, from the config file. Environment variables take precedence, so
+/// secrets don't have to be written to a file
+#[derive(Debug)]
+pub struct Config {
+    /// Only needed to connect, see [`Config::token`]
+    token: Option<String>,
+    pub database_path: String,
+    /// Keeps the guild states in SQLite or Postgres, only with the `sql` feature
+    pub database_url: Option<String>,
+    /// 64 hex digits to encrypt secret codes and exports with
+    pub secret_key: Option<String>,
+    /// Only with `log_dir`, the console is always logged to
+    pub log: Option<LogConfig>,
+    /// Where `/metrics` is served for Prometheus, off without it
+    pub metrics_addr: Option<SocketAddr>,
+    path: String,
+}
+
+impl Config {
+    pub fn load(path: Option<&str>) -> anyhow::Result<Self> {
+        let (path, required) = match path {
+            Some(path) => (path.to_string(), true),
+            None => (DEFAULT_PATH.to_string(), false),
+        };
+        let file: ConfigFile = match std::fs::read_to_string(&path) {
+            Ok(text) => {
+                toml::from_str(&text).with_context(|| format!("Invalid config file {path}"))?
+            }
+            Err(err) if err.kind() == ErrorKind::NotFound && !required => ConfigFile::default(),
+            Err(err) => Err(err).with_context(|| format!("Couldn't read config file {path}"))?,
+        };
+        let log_mb = file.log_max_mb.unwrap_or(DEFAULT_LOG_MB);
+        if log_mb == 0 {
+            bail!("`log_max_mb` in {path} has to be at least 1");
+        }
+        Ok(Self {
+            token: env("DISCORD_TOKEN").or(file.token),
+            database_path: env("DATABASE_PATH")
+                .or(file.database_path)
+                .unwrap_or(DEFAULT_DATABASE.to_string()),
+            database_url: env("DATABASE_URL").or(file.database_url),
+            secret_key: env("SECRET_KEY").or(file.secret_key),
+            log: file.log_dir.map(|dir| LogConfig {
+                dir,
+                max_bytes: log_mb * 1024 * 1024,
+                keep_days: file.log_keep_days.unwrap_or(DEFAULT_LOG_DAYS),
+            }),
+            metrics_addr: file.metrics_addr,
+            path,
+        })
+    }
+
+    /// The validated bot token, only commands that connect to Discord ask for it
+    pub fn token(&self) -> anyhow::Result<String> {
+        let token = self.token.clone().with_context(|| {
+            format!(
+                "No bot token, set DISCORD_TOKEN or `token` in {}",
+                self.path
+            )
+        })?;
+        validate_token(&token).context("The bot token isn't a valid Discord token")?;
+        Ok(token)
+    }
+}
+
+fn env(var: &str) -> Option<String> {
+    std::env::var(var)
+        .ok()
+        .map(|value| value.trim().to_string())
+        .filter(|value| !value.is_empty())
+}
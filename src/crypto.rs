@@ -0,0 +1,117 @@
+use anyhow::Context as _;
+use log::info;
+use ring::aead::{Aad, CHACHA20_POLY1305, LessSafeKey, NONCE_LEN, Nonce, UnboundKey};
+use std::{
+    fs::OpenOptions,
+    io::{ErrorKind, Write as _},
+    path::Path,
+    sync::OnceLock,
+};
+
+/// Created next to the database on the first start, unless the key is configured
+const KEY_FILE: &str = "codes.key";
+
+static KEY: OnceLock<Key> = OnceLock::new();
+
+struct Key {
+    key: LessSafeKey,
+    /// From `secret_key` of the config, which keeps it out of the directory of the database
+    configured: bool,
+}
+
+/// Called once at startup, with `secret_key` of the config or else the key file next to the
+/// database, so a broken key stops the bot before anything is encrypted with it
+pub fn configure(key: Option<String>, database_path: &str) -> anyhow::Result<()> {
+    let configured = key.is_some();
+    let bytes = match key {
+        Some(hex) => parse_hex(&hex).context("The secret key isn't 64 hex digits")?,
+        None => {
+            let dir = Path::new(database_path).parent().unwrap_or(Path::new(""));
+            let path = dir.join(KEY_FILE);
+            load_key_file(&path).with_context(|| format!("Couldn't load {}", path.display()))?
+        }
+    };
+    let key = UnboundKey::new(&CHACHA20_POLY1305, &bytes)
+        .map_err(|_| anyhow::anyhow!("Invalid key, it has to be 32 bytes"))?;
+    let _ = KEY.set(Key {
+        key: LessSafeKey::new(key),
+        configured,
+    });
+    Ok(())
+}
+
+fn load_key_file(path: &Path) -> anyhow::Result<Vec<u8>> {
+    match std::fs::read(path) {
+        Ok(bytes) => return Ok(bytes),
+        Err(err) if err.kind() != ErrorKind::NotFound => Err(err)?,
+        Err(_) => {}
+    }
+    //  Older versions kept the key in the working directory, codes sealed with it have to stay
+    //  readable
+    let legacy = Path::new(KEY_FILE);
+    let (bytes, moved) = match std::fs::read(legacy) {
+        Ok(bytes) if legacy != path => (bytes, true),
+        Err(err) if err.kind() != ErrorKind::NotFound => Err(err)?,
+        _ => (rand::random::<[u8; 32]>().to_vec(), false),
+    };
+    let mut options = OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    let mut file = options.open(path)?;
+    file.write_all(&bytes)?;
+    file.sync_all()?;
+    if moved {
+        info!("Moved {} to {}", legacy.display(), path.display());
+        std::fs::remove_file(legacy)?;
+    }
+    Ok(bytes)
+}
+
+/// Only then exports are encrypted, a generated key would make them useless on another host
+pub fn key_configured() -> bool {
+    KEY.get().is_some_and(|key| key.configured)
+}
+
+fn parse_hex(hex: &str) -> Option<Vec<u8>> {
+    if hex.len() != 64 || !hex.is_ascii() {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).ok())
+        .collect()
+}
+
+fn key() -> anyhow::Result<&'static LessSafeKey> {
+    Ok(&KEY.get().context("The encryption key isn't loaded")?.key)
+}
+
+/// Encrypts the data, the random nonce is stored in front of it
+pub fn seal(data: &[u8]) -> anyhow::Result<Vec<u8>> {
+    let key = key()?;
+    let nonce: [u8; NONCE_LEN] = rand::random();
+    let mut sealed = data.to_vec();
+    key.seal_in_place_append_tag(
+        Nonce::assume_unique_for_key(nonce),
+        Aad::empty(),
+        &mut sealed,
+    )
+    .map_err(|_| anyhow::anyhow!("Couldn't encrypt"))?;
+    sealed.splice(0..0, nonce);
+    Ok(sealed)
+}
+
+pub fn open(sealed: &[u8]) -> anyhow::Result<Vec<u8>> {
+    let key = key()?;
+    let (nonce, sealed) = sealed
+        .split_at_checked(NONCE_LEN)
+        .context("Encrypted data too short")?;
+    let nonce =
+        Nonce::try_assume_unique_for_key(nonce).map_err(|_| anyhow::anyhow!("Invalid nonce"))?;
+    let mut sealed = sealed.to_vec();
+    let data = key
+        .open_in_place(nonce, Aad::empty(), &mut sealed)
+        .map_err(|_| anyhow::anyhow!("Couldn't decrypt, is it the right key?"))?;
+    Ok(data.to_vec())
+}
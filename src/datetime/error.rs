@@ -0,0 +1,150 @@
+use nom::{
+    Parser,
+    error::{ContextError, ErrorKind, FromExternalError, ParseError},
+};
+use std::fmt;
+
+use crate::{locale::t, structs::Language};
+
+pub(super) type IResult<I, O, E = Error<I>> = Result<(I, O), nom::Err<E>>;
+
+/// Parser error, that remembers the innermost context of the position it failed at
+#[derive(Debug)]
+pub struct Error<I> {
+    pub(super) input: I,
+    pub(super) context: Option<&'static str>,
+}
+
+impl<'a> ParseError<&'a str> for Error<&'a str> {
+    fn from_error_kind(input: &'a str, _: ErrorKind) -> Self {
+        Error {
+            input,
+            context: None,
+        }
+    }
+
+    fn append(_: &'a str, _: ErrorKind, other: Self) -> Self {
+        other
+    }
+
+    //  The alternative that got further is the more helpful one
+    fn or(self, other: Self) -> Self {
+        match other.input.len().cmp(&self.input.len()) {
+            std::cmp::Ordering::Less => other,
+            std::cmp::Ordering::Equal if self.context.is_none() => other,
+            _ => self,
+        }
+    }
+}
+
+impl<'a> ContextError<&'a str> for Error<&'a str> {
+    fn add_context(_: &'a str, ctx: &'static str, mut other: Self) -> Self {
+        other.context.get_or_insert(ctx);
+        other
+    }
+}
+
+impl<'a, E> FromExternalError<&'a str, E> for Error<&'a str> {
+    fn from_external_error(input: &'a str, kind: ErrorKind, _: E) -> Self {
+        Self::from_error_kind(input, kind)
+    }
+}
+
+/// Which part of the input couldn't be parsed
+#[derive(Clone, Copy, Debug)]
+pub(super) enum Component {
+    Time,
+    Date,
+    Duration,
+    Any,
+}
+
+impl Component {
+    pub(super) fn of(context: &str) -> Option<Self> {
+        match context {
+            "time" | "full_time" => Some(Component::Time),
+            "date" | "full_date" | "special_words" | "calendar_words" | "abs" | "timestamp" => {
+                Some(Component::Date)
+            }
+            _ if context.starts_with("rel") => Some(Component::Duration),
+            _ => None,
+        }
+    }
+
+    fn name(self, language: Language) -> String {
+        match self {
+            Component::Time => t!(language, "time-component-time"),
+            Component::Date => t!(language, "time-component-date"),
+            Component::Duration => t!(language, "time-component-duration"),
+            Component::Any => t!(language, "time-component-any"),
+        }
+    }
+
+    fn examples(self, language: Language) -> String {
+        match self {
+            Component::Time => t!(language, "time-examples-time"),
+            Component::Date => t!(language, "time-examples-date"),
+            Component::Duration => t!(language, "time-examples-duration"),
+            Component::Any => t!(language, "time-examples-any"),
+        }
+    }
+}
+
+/// A failed parse, displayed as a message pointing at the offending part of the input
+#[derive(Debug)]
+pub struct TimeError {
+    pub(super) input: String,
+    pub(super) rest: String,
+    pub(super) component: Component,
+}
+
+impl TimeError {
+    pub fn text(&self, language: Language) -> String {
+        let parsed = self.input[..(self.input.len() - self.rest.len())].trim_end();
+        let token = self.rest.trim_start().split(' ').next().unwrap_or_default();
+        let problem = match (parsed.is_empty(), token.is_empty()) {
+            (_, true) => t!(language, "time-incomplete", input = self.input),
+            (true, false) => t!(language, "time-problem", token),
+            (false, false) => t!(language, "time-problem-after", parsed, token),
+        };
+        t!(
+            language,
+            "time-error",
+            component = self.component.name(language),
+            problem,
+            examples = self.component.examples(language)
+        )
+    }
+}
+
+//  In English for the logs, users get it in their language
+impl fmt::Display for TimeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.text(Language::English))
+    }
+}
+
+impl std::error::Error for TimeError {}
+
+pub(super) trait MapValid<'a, O>:
+    Parser<&'a str, Output = O, Error = Error<&'a str>> + Sized
+{
+    /// Like `map_opt`, but an invalid value like "25:00" fails the whole parse, so the error points at it
+    fn map_valid<O2>(
+        mut self,
+        f: impl Fn(O) -> Option<O2>,
+    ) -> impl Parser<&'a str, Output = O2, Error = Error<&'a str>> {
+        move |inp: &'a str| {
+            let (rest, out) = self.parse(inp)?;
+            match f(out) {
+                Some(out) => Ok((rest, out)),
+                None => Err(nom::Err::Failure(Error::from_error_kind(
+                    inp,
+                    ErrorKind::MapOpt,
+                ))),
+            }
+        }
+    }
+}
+
+impl<'a, O, P: Parser<&'a str, Output = O, Error = Error<&'a str>>> MapValid<'a, O> for P {}
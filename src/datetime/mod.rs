@@ -0,0 +1,205 @@
+use chrono::{DateTime, LocalResult, NaiveTime, TimeDelta, Utc};
+use chrono_tz::Tz;
+use nom::{Parser, branch::alt, bytes::complete::tag, combinator::opt};
+
+use crate::{
+    locale::{Localized, localized, t, text},
+    structs::Language,
+};
+use error::{Component, IResult};
+use parser::{abs_naive, full_rel, full_time, point, tag_maybe_lowercase};
+
+mod error;
+mod parser;
+
+pub use error::TimeError;
+
+/// Which kind of input a time was parsed from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Grammar {
+    /// Like "in 2 Stunden"
+    Relative,
+    /// Like "Morgen um 18:00"
+    Absolute,
+    /// Like "24.12.", the default time of day was used
+    DateOnly,
+    /// Like "in 2 Tagen um 18:00"
+    Mixed,
+    /// Like `<t:1234567890:R>`
+    Timestamp,
+}
+
+/// A parsed point in time, that remembers how it was given
+#[derive(Debug, Clone, Copy)]
+pub struct ParsedTime {
+    pub utc: DateTime<Utc>,
+    pub original_tz: Tz,
+    pub grammar_used: Grammar,
+}
+
+impl ParsedTime {
+    /// Echoes the interpreted time, so the user can check it was understood correctly
+    pub fn format_back(&self, language: Language) -> String {
+        let local = self.utc.with_timezone(&self.original_tz);
+        let absolute = local.format(&t!(language, "time-format-back")).to_string();
+        match self.grammar_used {
+            //  Rounded, so "in 2 Stunden" doesn't come back as 1 hour and 59 minutes
+            Grammar::Relative => t!(
+                language,
+                "time-relative-back",
+                duration =
+                    format_duration(self.utc - Utc::now() + TimeDelta::seconds(30), language),
+                absolute
+            ),
+            _ => absolute,
+        }
+    }
+}
+
+/// Dates without a time like "24.12." use `default_time`
+pub fn parse_time(inp: &str, tz: Tz, default_time: NaiveTime) -> Result<ParsedTime, TimeError> {
+    complete(inp, Component::Any, point(tz, default_time)).map(|(utc, grammar_used)| ParsedTime {
+        utc,
+        original_tz: tz,
+        grammar_used,
+    })
+}
+
+/// Parses a range like "vom 3.1. 18:00 bis 5.1. 20:00", an end without a date is on the day of the start
+pub fn parse_time_range(
+    inp: &str,
+    tz: Tz,
+    default_time: NaiveTime,
+) -> Result<(ParsedTime, ParsedTime), TimeError> {
+    complete(inp, Component::Any, |inp| {
+        let (inp, _) = opt(alt((
+            tag_maybe_lowercase("Vom "),
+            tag_maybe_lowercase("Von "),
+            tag_maybe_lowercase("From "),
+        )))
+        .parse(inp)?;
+        let (inp, start) = point(tz, default_time)(inp)?;
+        let parsed = |(utc, grammar_used)| ParsedTime {
+            utc,
+            original_tz: tz,
+            grammar_used,
+        };
+        let (inp, _) = alt((
+            tag(" bis "),
+            tag(" to "),
+            tag(" until "),
+            tag(" - "),
+            tag("-"),
+        ))
+        .parse(inp)?;
+        let same_day = full_time.map_opt(|time| {
+            let end = start.0.with_timezone(&tz).with_time(time).single()?;
+            Some((end.to_utc(), Grammar::Absolute))
+        });
+        let (inp, end) = alt((point(tz, default_time), same_day)).parse(inp)?;
+        Ok((inp, (parsed(start), parsed(end))))
+    })
+}
+
+/// Parses a time of day like "18:00" or "abends"
+pub fn parse_clock(inp: &str) -> Result<NaiveTime, TimeError> {
+    complete(inp, Component::Time, full_time)
+}
+
+/// Parses a relative duration like "2 Tage", a leading "-" makes it negative
+pub fn parse_duration(inp: &str) -> Result<TimeDelta, TimeError> {
+    complete(inp, Component::Duration, |inp| {
+        (opt(tag("-")), full_rel)
+            .map(|(neg, td)| match neg {
+                Some(_) => -td,
+                None => td,
+            })
+            .parse(inp)
+    })
+}
+
+/// Explains why an absolute time doesn't exist or is ambiguous because of a DST change
+pub fn dst_hint(inp: &str, tz: Tz, default_time: NaiveTime) -> Option<Localized> {
+    let (dt, _) = complete(inp, Component::Date, abs_naive(tz, default_time)).ok()?;
+    let time = dt.format("%H:%M");
+    let date = dt.format("%d.%m.");
+    match dt.and_local_timezone(tz) {
+        LocalResult::Single(_) => None,
+        LocalResult::Ambiguous(a, b) => Some(localized!(
+            "time-dst-twice",
+            time,
+            date,
+            first = a.format("%d.%m.%Y %H:%M %Z"),
+            second = b.format("%d.%m.%Y %H:%M %Z"),
+        )),
+        LocalResult::None => Some(localized!(
+            "time-dst-missing",
+            time,
+            date,
+            later = (dt + TimeDelta::hours(1)).format("%H:%M"),
+        )),
+    }
+}
+
+/// Formats a duration like "2 Tage, 3 Stunden und 5 Minuten"
+pub fn format_duration(td: TimeDelta, language: Language) -> String {
+    let mut parts = Vec::new();
+    for (amount, one, many) in [
+        (td.num_days(), "duration-day", "duration-days"),
+        (td.num_hours() % 24, "duration-hour", "duration-hours"),
+        (td.num_minutes() % 60, "duration-minute", "duration-minutes"),
+    ] {
+        match amount {
+            0 => {}
+            1 => parts.push(text(language, one, &[])),
+            n => parts.push(text(language, many, &[("count", &n)])),
+        }
+    }
+    match parts.len() {
+        0 => t!(language, "duration-less-than-minute"),
+        1 => parts.remove(0),
+        _ => {
+            let last = parts.pop().unwrap();
+            t!(language, "list-and", first = parts.join(", "), last)
+        }
+    }
+}
+
+/// Collapses whitespace and drops trailing punctuation, the dot of a date like "24.12." stays
+fn normalize(inp: &str) -> String {
+    let mut inp = inp.split_whitespace().collect::<Vec<_>>().join(" ");
+    while let Some(last) = inp.pop() {
+        let keep = match last {
+            '!' | '?' | ',' | ';' => false,
+            '.' => inp.ends_with(|c: char| c.is_ascii_digit()),
+            _ => true,
+        };
+        if keep {
+            inp.push(last);
+            break;
+        }
+    }
+    inp
+}
+
+fn complete<O>(
+    input: &str,
+    component: Component,
+    mut parser: impl FnMut(&str) -> IResult<&str, O>,
+) -> Result<O, TimeError> {
+    let input = &normalize(input);
+    let (rest, context) = match parser(input) {
+        Ok(("", res)) => return Ok(res),
+        Ok((rest, _)) => (rest, None),
+        Err(nom::Err::Failure(err)) => (err.input, err.context),
+        //  If nothing could be parsed, the context is just the first alternative that was tried
+        Err(nom::Err::Error(err)) if err.input.len() == input.len() => (err.input, None),
+        Err(nom::Err::Error(err)) => (err.input, err.context),
+        Err(nom::Err::Incomplete(_)) => ("", None),
+    };
+    Err(TimeError {
+        input: input.clone(),
+        rest: rest.to_string(),
+        component: context.and_then(Component::of).unwrap_or(component),
+    })
+}
@@ -0,0 +1,715 @@
+use chrono::{
+    DateTime, Datelike, Days, LocalResult, Months, NaiveDate, NaiveDateTime, NaiveTime, TimeDelta,
+    Utc, Weekday,
+};
+use chrono_tz::Tz;
+use nom::{
+    Parser,
+    branch::{alt, permutation},
+    bytes::complete::{tag, take_while1},
+    character::complete::{alpha1, digit1, satisfy},
+    combinator::{map_res, not, opt},
+    error::{ErrorKind, ParseError, context},
+};
+use std::str::FromStr;
+
+use super::{
+    Grammar,
+    error::{Error, IResult, MapValid},
+};
+
+/// Numbers that can be written as a word like "in zwei Tagen", longer words first
+const NUMBER_WORDS: [(&str, i64); 18] = [
+    ("Dreißig", 30),
+    ("Zwanzig", 20),
+    ("Einer", 1),
+    ("Einem", 1),
+    ("Einen", 1),
+    ("Eine", 1),
+    ("Ein", 1),
+    ("Zwei", 2),
+    ("Drei", 3),
+    ("Vier", 4),
+    ("Fünf", 5),
+    ("Sechs", 6),
+    ("Sieben", 7),
+    ("Acht", 8),
+    ("Neun", 9),
+    ("Zehn", 10),
+    ("Elf", 11),
+    ("Zwölf", 12),
+];
+
+/// Times of day that can be written as a word like "morgen Mittag", longer words first
+const DAY_PARTS: [(&str, u32); 17] = [
+    ("Mitternacht", 0),
+    ("Nachmittags", 15),
+    ("Nachmittag", 15),
+    ("Vormittags", 10),
+    ("Vormittag", 10),
+    ("Mittags", 12),
+    ("Mittag", 12),
+    ("Morgens", 8),
+    ("Früh", 8),
+    ("Abends", 19),
+    ("Abend", 19),
+    ("Nachts", 22),
+    ("Nacht", 22),
+    ("Midnight", 0),
+    ("Noon", 12),
+    ("Morning", 8),
+    ("Evening", 19),
+];
+
+/// A single point in time, in any of the supported grammars
+pub(super) fn point(
+    tz: Tz,
+    default_time: NaiveTime,
+) -> impl Fn(&str) -> IResult<&str, (DateTime<Utc>, Grammar)> {
+    move |inp| {
+        alt((
+            mixed(tz).map(|dt| (dt, Grammar::Mixed)),
+            abs(tz, default_time),
+            full_rel
+                .map_opt(|td| Utc::now().checked_add_signed(td))
+                .map(|dt| (dt, Grammar::Relative)),
+            timestamp.map(|dt| (dt, Grammar::Timestamp)),
+        ))
+        .parse(inp)
+    }
+}
+
+fn mixed(tz: Tz) -> impl Fn(&str) -> IResult<&str, DateTime<Utc>> {
+    move |inp| {
+        alt((
+            (full_part_rel, tag(" "), full_time),
+            (full_time, tag(" "), full_part_rel).map(|(ft, y, t)| (t, y, ft)),
+        ))
+        .map_opt(|(t, _, ft)| {
+            Utc::now()
+                .with_timezone(&tz)
+                .checked_add_signed(t)?
+                .with_time(ft)
+                .single()
+                .map(|dt| dt.to_utc())
+        })
+        .parse(inp)
+    }
+}
+
+fn full_part_rel(inp: &str) -> IResult<&str, TimeDelta> {
+    (
+        opt(tag_maybe_lowercase("In ")),
+        part_rel,
+        opt((separator, full_part_rel)),
+    )
+        .map_opt(|(_, mut time, next)| {
+            if let Some((_, next)) = next {
+                time = time.checked_add(&next)?;
+            }
+            Some(time)
+        })
+        .parse(inp)
+}
+
+fn separator(inp: &str) -> IResult<&str, &str> {
+    //  Nothing in between is fine too, like "2h30min"
+    alt((
+        tag(" und "),
+        tag(" and "),
+        tag(", "),
+        tag(","),
+        tag(" "),
+        tag(""),
+    ))
+    .parse(inp)
+}
+
+fn part_rel(inp: &str) -> IResult<&str, TimeDelta> {
+    permutation((opt(rel_days), opt(rel_weeks)))
+        .map_opt(|(d, w)| {
+            let mut time = TimeDelta::zero();
+            for t in [d, w].into_iter().flatten() {
+                time = time.checked_add(&t)?;
+            }
+            Some(time)
+        })
+        .map_opt(|t| (!t.is_zero()).then_some(t))
+        .parse(inp)
+}
+
+pub(super) fn full_rel(inp: &str) -> IResult<&str, TimeDelta> {
+    (
+        opt(tag_maybe_lowercase("In ")),
+        rel,
+        opt((separator, full_rel)),
+    )
+        .map_opt(|(_, mut time, next)| {
+            if let Some((_, next)) = next {
+                time = time.checked_add(&next)?;
+            }
+            Some(time)
+        })
+        .parse(inp)
+}
+
+fn rel(inp: &str) -> IResult<&str, TimeDelta> {
+    context(
+        "rel",
+        permutation((
+            opt(rel_seconds),
+            opt(rel_minutes),
+            opt(rel_hours),
+            opt(rel_days),
+            opt(rel_weeks),
+        ))
+        .map_opt(|(s, mi, h, d, w)| {
+            let mut time = TimeDelta::zero();
+            for t in [s, mi, h, d, w].into_iter().flatten() {
+                time = time.checked_add(&t)?;
+            }
+            Some(time)
+        })
+        .map_opt(|t| (!t.is_zero()).then_some(t)),
+    )
+    .parse(inp)
+}
+
+fn rel_seconds(inp: &str) -> IResult<&str, TimeDelta> {
+    context(
+        "rel_seconds",
+        (
+            amount,
+            alt((
+                (
+                    tag(" "),
+                    alt((
+                        (tag_maybe_lowercase("Sekunden")),
+                        (tag_maybe_lowercase("Sekunde")),
+                        (tag_maybe_lowercase("Seconds")),
+                        (tag_maybe_lowercase("Second")),
+                    )),
+                )
+                    .map(|_| ()),
+                (opt(tag(" ")), alt((unit("sec"), unit("s")))).map(|_| ()),
+            )),
+        )
+            .map_opt(|(n, _)| n.of(TimeDelta::try_seconds(1)?)),
+    )
+    .parse(inp)
+}
+
+fn rel_minutes(inp: &str) -> IResult<&str, TimeDelta> {
+    context(
+        "rel_minutes",
+        (
+            amount,
+            alt((
+                (
+                    tag(" "),
+                    alt((
+                        tag_maybe_lowercase("Minuten"),
+                        tag_maybe_lowercase("Minutes"),
+                        tag_maybe_lowercase("Minute"),
+                    )),
+                )
+                    .map(|_| ()),
+                (opt(tag(" ")), alt((unit("min"), unit("m")))).map(|_| ()),
+            )),
+        )
+            .map_opt(|(n, _)| n.of(TimeDelta::try_minutes(1)?)),
+    )
+    .parse(inp)
+}
+
+fn rel_hours(inp: &str) -> IResult<&str, TimeDelta> {
+    context(
+        "rel_hours",
+        (
+            amount,
+            alt((
+                (
+                    tag(" "),
+                    alt((
+                        (tag_maybe_lowercase("Stunden")),
+                        (tag_maybe_lowercase("Stunde")),
+                        (tag_maybe_lowercase("Hours")),
+                        (tag_maybe_lowercase("Hour")),
+                    )),
+                )
+                    .map(|_| ()),
+                (opt(tag(" ")), unit("h")).map(|_| ()),
+            )),
+        )
+            .map_opt(|(n, _)| n.of(TimeDelta::try_hours(1)?)),
+    )
+    .parse(inp)
+}
+
+fn rel_days(inp: &str) -> IResult<&str, TimeDelta> {
+    context(
+        "rel_days",
+        (
+            amount,
+            alt((
+                (
+                    tag(" "),
+                    alt((
+                        (tag_maybe_lowercase("Tagen")),
+                        (tag_maybe_lowercase("Tage")),
+                        (tag_maybe_lowercase("Tag")),
+                        (tag_maybe_lowercase("Days")),
+                        (tag_maybe_lowercase("Day")),
+                    )),
+                )
+                    .map(|_| ()),
+                (opt(tag(" ")), unit("d")).map(|_| ()),
+            )),
+        )
+            .map_opt(|(n, _)| n.of(TimeDelta::try_days(1)?)),
+    )
+    .parse(inp)
+}
+
+fn rel_weeks(inp: &str) -> IResult<&str, TimeDelta> {
+    context(
+        "rel_weeks",
+        (
+            amount,
+            alt((
+                (
+                    tag(" "),
+                    alt((
+                        (tag_maybe_lowercase("Wochen")),
+                        (tag_maybe_lowercase("Woche")),
+                        (tag_maybe_lowercase("Weeks")),
+                        (tag_maybe_lowercase("Week")),
+                    )),
+                )
+                    .map(|_| ()),
+                (opt(tag(" ")), unit("w")).map(|_| ()),
+            )),
+        )
+            .map_opt(|(n, _)| n.of(TimeDelta::try_weeks(1)?)),
+    )
+    .parse(inp)
+}
+
+fn abs(
+    tz: Tz,
+    default_time: NaiveTime,
+) -> impl Fn(&str) -> IResult<&str, (DateTime<Utc>, Grammar)> {
+    move |inp| {
+        context(
+            "abs",
+            (abs_naive(tz, default_time), opt((tag(" "), zone))).map_opt(
+                |((dt, grammar), zone)| {
+                    let dt = resolve(dt, zone.map(|(_, zone)| zone), tz)?;
+                    (dt > Utc::now()).then_some((dt.to_utc(), grammar))
+                },
+            ),
+        )
+        .parse(inp)
+    }
+}
+
+/// An abbreviation like "CEST", upper case so it isn't confused with a following word
+fn zone(inp: &str) -> IResult<&str, &str> {
+    take_while1(|c: char| c.is_ascii_uppercase()).parse(inp)
+}
+
+/// A unix timestamp or Discord's `<t:1234567890:R>` syntax, copied from another message
+fn timestamp(inp: &str) -> IResult<&str, DateTime<Utc>> {
+    context(
+        "timestamp",
+        alt((
+            (tag("<t:"), number::<i64>, opt((tag(":"), alpha1)), tag(">")).map(|(_, t, _, _)| t),
+            number::<i64>,
+        ))
+        .map_opt(|t| DateTime::from_timestamp(t, 0))
+        .map_opt(|dt| (dt > Utc::now()).then_some(dt)),
+    )
+    .parse(inp)
+}
+
+pub(super) fn abs_naive(
+    tz: Tz,
+    default_time: NaiveTime,
+) -> impl Fn(&str) -> IResult<&str, (NaiveDateTime, Grammar)> {
+    move |inp| {
+        alt((
+            alt((
+                (full_date, tag(" "), full_time).map(|(d, _, t)| (d, t)),
+                (full_time, tag(" "), full_date).map(|(t, _, d)| (d, t)),
+                (special_words(tz), tag(" "), full_time).map(|(d, _, t)| (d, t)),
+                (full_time, tag(" "), special_words(tz)).map(|(t, _, d)| (d, t)),
+                (calendar_words(tz), tag(" "), full_time).map(|(d, _, t)| (d, t)),
+                (full_time, tag(" "), calendar_words(tz)).map(|(t, _, d)| (d, t)),
+            ))
+            .map(|(d, t)| (NaiveDateTime::new(d, t), Grammar::Absolute)),
+            only_date(tz).map(|d| (NaiveDateTime::new(d, default_time), Grammar::DateOnly)),
+        ))
+        .parse(inp)
+    }
+}
+
+fn only_date(tz: Tz) -> impl Fn(&str) -> IResult<&str, NaiveDate> {
+    move |inp| alt((full_date, special_words(tz), calendar_words(tz))).parse(inp)
+}
+
+/// Local times, that are ambiguous because of a DST change, need the zone abbreviation like "CEST"
+fn resolve(dt: NaiveDateTime, zone: Option<&str>, tz: Tz) -> Option<DateTime<Tz>> {
+    let matches = |dt: &DateTime<Tz>| zone.is_none_or(|zone| dt.format("%Z").to_string() == zone);
+    match dt.and_local_timezone(tz) {
+        LocalResult::Single(dt) => matches(&dt).then_some(dt),
+        LocalResult::Ambiguous(a, b) if zone.is_some() => [a, b].into_iter().find(matches),
+        _ => None,
+    }
+}
+
+fn special_words(tz: Tz) -> impl Fn(&str) -> IResult<&str, NaiveDate> {
+    move |inp| {
+        context(
+            "special_words",
+            alt((
+                tag_maybe_lowercase("Heute").map(|_| Utc::now().with_timezone(&tz).date_naive()),
+                tag_maybe_lowercase("Morgen").map_opt(|_| {
+                    Utc::now()
+                        .with_timezone(&tz)
+                        .date_naive()
+                        .checked_add_days(Days::new(1))
+                }),
+                tag_maybe_lowercase("Übermorgen").map_opt(|_| {
+                    Utc::now()
+                        .with_timezone(&tz)
+                        .date_naive()
+                        .checked_add_days(Days::new(2))
+                }),
+                tag_maybe_lowercase("Today").map(|_| Utc::now().with_timezone(&tz).date_naive()),
+                tag_maybe_lowercase("Tomorrow").map_opt(|_| {
+                    Utc::now()
+                        .with_timezone(&tz)
+                        .date_naive()
+                        .checked_add_days(Days::new(1))
+                }),
+                (
+                    opt(tag_maybe_lowercase("The ")),
+                    tag_maybe_lowercase("Day after tomorrow"),
+                )
+                    .map_opt(|_| {
+                        Utc::now()
+                            .with_timezone(&tz)
+                            .date_naive()
+                            .checked_add_days(Days::new(2))
+                    }),
+            )),
+        )
+        .parse(inp)
+    }
+}
+
+/// Dates relative to the calendar like "nächste Woche Montag", "nächsten Montag" or "nächsten Monat am 1."
+fn calendar_words(tz: Tz) -> impl Fn(&str) -> IResult<&str, NaiveDate> {
+    move |inp| {
+        let today = Utc::now().with_timezone(&tz).date_naive();
+        context(
+            "calendar_words",
+            alt((
+                (
+                    next,
+                    tag_maybe_lowercase("Woche "),
+                    opt(tag("am ")),
+                    weekday,
+                )
+                    .map_opt(|(_, _, _, day)| {
+                        today.checked_add_days(Days::new(
+                            7 + day.num_days_from_monday() as u64
+                                - today.weekday().num_days_from_monday() as u64,
+                        ))
+                    }),
+                (
+                    next,
+                    tag_maybe_lowercase("Monat "),
+                    opt(tag("am ")),
+                    number::<u32>,
+                    tag("."),
+                )
+                    .map_opt(|(_, _, _, day, _)| {
+                        today
+                            .with_day(1)?
+                            .checked_add_months(Months::new(1))?
+                            .with_day(day)
+                    }),
+                (next, weekday).map_opt(|(_, day)| {
+                    let days = match day.days_since(today.weekday()) {
+                        0 => 7,
+                        days => days,
+                    };
+                    today.checked_add_days(Days::new(days as u64))
+                }),
+                (opt(tag_maybe_lowercase("Am ")), weekday).map_opt(|(_, day)| {
+                    today.checked_add_days(Days::new(day.days_since(today.weekday()) as u64))
+                }),
+            )),
+        )
+        .parse(inp)
+    }
+}
+
+fn next(inp: &str) -> IResult<&str, ()> {
+    (
+        tag_maybe_lowercase("Nächste"),
+        opt(alt((tag("n"), tag("r"), tag("s")))),
+        tag(" "),
+    )
+        .map(|_| ())
+        .parse(inp)
+}
+
+fn weekday(inp: &str) -> IResult<&str, Weekday> {
+    alt((
+        tag_maybe_lowercase("Montag").map(|_| Weekday::Mon),
+        tag_maybe_lowercase("Dienstag").map(|_| Weekday::Tue),
+        tag_maybe_lowercase("Mittwoch").map(|_| Weekday::Wed),
+        tag_maybe_lowercase("Donnerstag").map(|_| Weekday::Thu),
+        tag_maybe_lowercase("Freitag").map(|_| Weekday::Fri),
+        tag_maybe_lowercase("Samstag").map(|_| Weekday::Sat),
+        tag_maybe_lowercase("Sonntag").map(|_| Weekday::Sun),
+    ))
+    .parse(inp)
+}
+
+/// An amount of a unit, that might include a half like "zweieinhalb"
+#[derive(Clone, Copy)]
+struct Amount {
+    whole: i64,
+    half: bool,
+}
+
+impl Amount {
+    fn of(self, unit: TimeDelta) -> Option<TimeDelta> {
+        let half = if self.half {
+            unit / 2
+        } else {
+            TimeDelta::zero()
+        };
+        unit.checked_mul(self.whole.try_into().ok()?)?
+            .checked_add(&half)
+    }
+}
+
+fn amount(inp: &str) -> IResult<&str, Amount> {
+    alt((
+        number::<i64>.map(|whole| Amount { whole, half: false }),
+        alt((
+            tag_maybe_lowercase("Anderthalb"),
+            tag_maybe_lowercase("Eineinhalb"),
+        ))
+        .map(|_| Amount {
+            whole: 1,
+            half: true,
+        }),
+        (
+            opt(alt((
+                tag_maybe_lowercase("Einer "),
+                tag_maybe_lowercase("Eine "),
+            ))),
+            alt((tag_maybe_lowercase("Halben"), tag_maybe_lowercase("Halbe"))),
+        )
+            .map(|_| Amount {
+                whole: 0,
+                half: true,
+            }),
+        (number_word, opt(tag("einhalb"))).map(|(whole, half)| Amount {
+            whole,
+            half: half.is_some(),
+        }),
+    ))
+    .parse(inp)
+}
+
+//  Only whole words, "Dreißig" mustn't be read as "Drei" followed by garbage. The half of
+//  "Dreieinhalb" is the only suffix.
+fn number_word(inp: &str) -> IResult<&str, i64> {
+    for (word, n) in NUMBER_WORDS {
+        if let Ok((rest, _)) = tag_maybe_lowercase(word)(inp)
+            && (!rest.starts_with(char::is_alphabetic) || rest.starts_with("einhalb"))
+        {
+            return Ok((rest, n));
+        }
+    }
+    Err(nom::Err::Error(Error::from_error_kind(inp, ErrorKind::Tag)))
+}
+
+/// A unit abbreviation like "h", that isn't the start of a longer word
+fn unit(abbr: &'static str) -> impl Fn(&str) -> IResult<&str, &str> {
+    move |inp| {
+        (tag(abbr), not(satisfy(char::is_alphabetic)))
+            .map(|(abbr, _)| abbr)
+            .parse(inp)
+    }
+}
+
+fn number<T: FromStr>(inp: &str) -> IResult<&str, T> {
+    map_res(digit1, |s: &str| s.parse::<T>()).parse(inp)
+}
+
+fn full_date(inp: &str) -> IResult<&str, NaiveDate> {
+    context(
+        "full_date",
+        (
+            opt(alt((
+                tag_maybe_lowercase("Am "),
+                tag_maybe_lowercase("On "),
+            ))),
+            date,
+        )
+            .map(|(_, d)| d),
+    )
+    .parse(inp)
+}
+
+fn date(inp: &str) -> IResult<&str, NaiveDate> {
+    context("date", alt((numeric_date, named_date))).parse(inp)
+}
+
+/// Dates like "24.12.2026", without a year the next occurrence is used
+fn numeric_date(inp: &str) -> IResult<&str, NaiveDate> {
+    (
+        number::<u32>,
+        tag("."),
+        number::<u32>,
+        tag("."),
+        opt(number::<i32>),
+    )
+        .map_valid(|(day, _, month, _, year)| match year {
+            Some(year) => NaiveDate::from_ymd_opt(year, month, day),
+            None => next_occurrence(month, day),
+        })
+        .parse(inp)
+}
+
+/// Dates like "3. Januar 2026", without a year the next occurrence is used
+fn named_date(inp: &str) -> IResult<&str, NaiveDate> {
+    (
+        number::<u32>,
+        tag("."),
+        opt(tag(" ")),
+        month,
+        opt((tag(" "), year)),
+    )
+        .map_valid(|(day, _, _, month, year)| match year {
+            Some((_, year)) => NaiveDate::from_ymd_opt(year, month, day),
+            None => next_occurrence(month, day),
+        })
+        .parse(inp)
+}
+
+fn next_occurrence(month: u32, day: u32) -> Option<NaiveDate> {
+    let today = Utc::now().date_naive();
+    match NaiveDate::from_ymd_opt(today.year(), month, day) {
+        Some(date) if date >= today => Some(date),
+        _ => NaiveDate::from_ymd_opt(today.year() + 1, month, day),
+    }
+}
+
+fn year(inp: &str) -> IResult<&str, i32> {
+    map_res(digit1, |s: &str| {
+        if s.len() == 4 {
+            s.parse::<i32>().map_err(|_| ())
+        } else {
+            Err(())
+        }
+    })
+    .parse(inp)
+}
+
+fn month(inp: &str) -> IResult<&str, u32> {
+    alt((
+        alt((tag_maybe_lowercase("Januar"), tag_maybe_lowercase("Jänner"))).map(|_| 1),
+        tag_maybe_lowercase("Februar").map(|_| 2),
+        alt((tag_maybe_lowercase("März"), tag_maybe_lowercase("Maerz"))).map(|_| 3),
+        tag_maybe_lowercase("April").map(|_| 4),
+        tag_maybe_lowercase("Mai").map(|_| 5),
+        tag_maybe_lowercase("Juni").map(|_| 6),
+        tag_maybe_lowercase("Juli").map(|_| 7),
+        tag_maybe_lowercase("August").map(|_| 8),
+        tag_maybe_lowercase("September").map(|_| 9),
+        tag_maybe_lowercase("Oktober").map(|_| 10),
+        tag_maybe_lowercase("November").map(|_| 11),
+        tag_maybe_lowercase("Dezember").map(|_| 12),
+    ))
+    .parse(inp)
+}
+
+pub(super) fn full_time(inp: &str) -> IResult<&str, NaiveTime> {
+    context(
+        "full_time",
+        (
+            opt(alt((
+                tag_maybe_lowercase("Um "),
+                tag_maybe_lowercase("At "),
+            ))),
+            time,
+        )
+            .map(|(_, t)| t),
+    )
+    .parse(inp)
+}
+
+fn time(inp: &str) -> IResult<&str, NaiveTime> {
+    context("time", alt((time_12h, time_24h, day_part))).parse(inp)
+}
+
+fn day_part(inp: &str) -> IResult<&str, NaiveTime> {
+    for (word, hour) in DAY_PARTS {
+        if let Ok((rest, _)) = tag_maybe_lowercase(word)(inp) {
+            return Ok((rest, NaiveTime::from_hms_opt(hour, 0, 0).unwrap()));
+        }
+    }
+    Err(nom::Err::Error(Error::from_error_kind(inp, ErrorKind::Tag)))
+}
+
+fn time_24h(inp: &str) -> IResult<&str, NaiveTime> {
+    alt((
+        (
+            number::<u32>,
+            tag(":"),
+            number::<u32>,
+            opt((tag(":"), number::<u32>)),
+            opt((opt(tag(" ")), tag_maybe_lowercase("Uhr"))),
+        )
+            .map_valid(|(hour, _, min, s, _)| {
+                let sec = s.map(|(_, s)| s).unwrap_or_default();
+                NaiveTime::from_hms_opt(hour, min, sec)
+            }),
+        (number::<u32>, opt(tag(" ")), tag_maybe_lowercase("Uhr"))
+            .map_valid(|(hour, _, _)| NaiveTime::from_hms_opt(hour, 0, 0)),
+    ))
+    .parse(inp)
+}
+
+/// English times like "6pm" or "6:30 am", 12am is midnight and 12pm noon
+fn time_12h(inp: &str) -> IResult<&str, NaiveTime> {
+    (
+        number::<u32>,
+        opt((tag(":"), number::<u32>)),
+        opt(tag(" ")),
+        alt((
+            alt((tag("am"), tag("AM"))).map(|_| false),
+            alt((tag("pm"), tag("PM"))).map(|_| true),
+        )),
+    )
+        .map_valid(|(hour, min, _, pm)| {
+            if !(1..=12).contains(&hour) {
+                return None;
+            }
+            let hour = hour % 12 + if pm { 12 } else { 0 };
+            NaiveTime::from_hms_opt(hour, min.map(|(_, m)| m).unwrap_or_default(), 0)
+        })
+        .parse(inp)
+}
+
+pub(super) fn tag_maybe_lowercase(tag_: &str) -> impl Fn(&str) -> IResult<&str, &str> {
+    move |inp| alt((tag(tag_), tag(tag_.to_lowercase().as_str()))).parse(inp)
+}
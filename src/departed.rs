@@ -0,0 +1,106 @@
+use chrono::Utc;
+use poise::serenity_prelude::GuildId;
+use redb::{ReadableTable, TableDefinition};
+use std::collections::HashSet;
+
+use crate::{
+    activity::ACTIVITY_TABLE,
+    audit::AUDIT_TABLE,
+    clear::CLEAR_JOBS_TABLE,
+    finishing::FINISHING_TABLE,
+    invites::{INVITE_USES_TABLE, INVITED_COUNT_TABLE, INVITES_TABLE},
+    leaderboard::WINS_TABLE,
+    storage::{Db, Redb},
+};
+
+/// Guilds the bot was removed from, with the unix timestamp it noticed it
+pub(crate) const DEPARTED_TABLE: TableDefinition<u64, i64> = TableDefinition::new("departed");
+
+/// Everything of a guild is kept this long, in case the bot is invited back
+const GRACE_DAYS: i64 = 30;
+
+/// Keeps the first time, so the grace period doesn't start over when noticed again
+pub fn record_departure(db: &Redb, guild: GuildId) -> anyhow::Result<()> {
+    let db = db.begin_write()?;
+    {
+        let mut table = db.open_table(DEPARTED_TABLE)?;
+        if table.get(guild.get())?.is_none() {
+            table.insert(guild.get(), Utc::now().timestamp())?;
+        }
+    }
+    db.commit()?;
+    Ok(())
+}
+
+/// Returns whether the bot was gone from the guild
+pub fn record_return(db: &Redb, guild: GuildId) -> anyhow::Result<bool> {
+    if !departed(db)?.contains(&guild) {
+        return Ok(false);
+    }
+    let db = db.begin_write()?;
+    db.open_table(DEPARTED_TABLE)?.remove(guild.get())?;
+    db.commit()?;
+    Ok(true)
+}
+
+pub fn departed(db: &Redb) -> anyhow::Result<HashSet<GuildId>> {
+    let db_read = db.begin_read()?;
+    db_read
+        .open_table(DEPARTED_TABLE)?
+        .iter()?
+        .map(|entry| Ok(GuildId::new(entry?.0.value())))
+        .collect()
+}
+
+fn left_before(db: &Redb, guild: GuildId, before: i64) -> anyhow::Result<bool> {
+    let db_read = db.begin_read()?;
+    let table = db_read.open_table(DEPARTED_TABLE)?;
+    Ok(table
+        .get(guild.get())?
+        .is_some_and(|left| left.value() < before))
+}
+
+/// Deletes everything stored for guilds the bot left more than [`GRACE_DAYS`] ago, returns how many
+pub async fn remove_departed(db: &Db) -> anyhow::Result<usize> {
+    let before = Utc::now().timestamp() - GRACE_DAYS * 24 * 60 * 60;
+    let mut removed = 0;
+    for guild in departed(db)? {
+        let _guard = db.lock_guild(guild).await;
+        //  Checked under the lock, the bot might have been invited back meanwhile
+        if !left_before(db, guild, before)? {
+            continue;
+        }
+        db.storage().delete(guild)?;
+        db.activity().forget(Some(guild), None);
+        let id = guild.get();
+        let all = (id, 0)..=(id, u64::MAX);
+        let db_write = db.begin_write()?;
+        db_write
+            .open_table(ACTIVITY_TABLE)?
+            .retain_in(all.clone(), |_, _| false)?;
+        db_write
+            .open_table(WINS_TABLE)?
+            .retain_in(all.clone(), |_, _| false)?;
+        db_write
+            .open_table(INVITES_TABLE)?
+            .retain_in(all.clone(), |_, _| false)?;
+        db_write
+            .open_table(INVITED_COUNT_TABLE)?
+            .retain_in(all.clone(), |_, _| false)?;
+        db_write
+            .open_table(AUDIT_TABLE)?
+            .retain_in(all.clone(), |_, _| false)?;
+        db_write
+            .open_table(FINISHING_TABLE)?
+            .retain_in(all, |_, _| false)?;
+        db_write.open_table(INVITE_USES_TABLE)?.remove(id)?;
+        db_write
+            .open_table(CLEAR_JOBS_TABLE)?
+            .retain(|_, job| job.guild != id)?;
+        db_write.open_table(DEPARTED_TABLE)?.remove(id)?;
+        db_write.commit()?;
+        db.forget_settings(guild);
+        removed += 1;
+    }
+    Ok(removed)
+}
@@ -0,0 +1,228 @@
+use bincode::{Decode, Encode, config, decode_from_slice, encode_to_vec};
+use poise::serenity_prelude::UserId;
+use ring::digest::{Context, SHA256};
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+};
+
+use crate::{
+    crypto,
+    structs::{Commitment, Draw, Entry, Tombstone},
+};
+
+/// How a participant appears in the hash and the order of the draw
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+enum Key {
+    /// Draws without a salt use the user id
+    Id(u64),
+    Leaf(String),
+}
+
+impl fmt::Display for Key {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Key::Id(user) => write!(f, "{user}"),
+            Key::Leaf(leaf) => f.write_str(leaf),
+        }
+    }
+}
+
+/// A participant with their tickets and whether they were excluded
+type Ticket = (Key, u32, bool);
+
+fn key(salt: Option<&str>, user: u64) -> Key {
+    match salt {
+        Some(salt) => {
+            let mut context = Context::new(&SHA256);
+            context.update(salt.as_bytes());
+            context.update(&user.to_be_bytes());
+            Key::Leaf(hex(context.finish().as_ref()))
+        }
+        None => Key::Id(user),
+    }
+}
+
+/// Hash over the seed and every participant with their tickets, sorted by their key
+fn participants_hash(tickets: &[Ticket], seed: u64) -> String {
+    let mut context = Context::new(&SHA256);
+    context.update(&seed.to_be_bytes());
+    for (key, tickets, _) in tickets {
+        context.update(format!("{key}:{tickets}\n").as_bytes());
+    }
+    hex(context.finish().as_ref())
+}
+
+//  Every ticket is one chance to win, but a user can only win once
+fn draw_keys(hash: &str, tickets: &[Ticket], count: usize) -> Vec<Key> {
+    let mut candidates: Vec<(&Key, u32)> = tickets
+        .iter()
+        .filter(|(_, _, excluded)| !excluded)
+        .map(|(key, tickets, _)| (key, *tickets))
+        .collect();
+    let mut winners = Vec::with_capacity(count.min(candidates.len()));
+    for round in 0u64.. {
+        let total: u64 = candidates
+            .iter()
+            .map(|(_, tickets)| u64::from(*tickets))
+            .sum();
+        if winners.len() >= count || total == 0 {
+            break;
+        }
+        let mut context = Context::new(&SHA256);
+        context.update(hash.as_bytes());
+        context.update(&round.to_be_bytes());
+        let bytes: [u8; 8] = context.finish().as_ref()[..8].try_into().unwrap();
+        let mut ticket = u64::from_be_bytes(bytes) % total;
+        let index = candidates
+            .iter()
+            .position(|(_, tickets)| match ticket < u64::from(*tickets) {
+                true => true,
+                false => {
+                    ticket -= u64::from(*tickets);
+                    false
+                }
+            })
+            .unwrap();
+        winners.push(candidates.remove(index).0.clone());
+    }
+    winners
+}
+
+/// Draws the winners only from the hash, so anyone knowing the participants, the seed and the salt
+/// can repeat it
+pub fn fair_draw(
+    participants: &HashMap<UserId, Entry>,
+    count: usize,
+    exclude: &HashSet<UserId>,
+    seed: u64,
+    salt: String,
+) -> (Vec<UserId>, Draw) {
+    let mut users = HashMap::with_capacity(participants.len());
+    let mut tickets: Vec<Ticket> = participants
+        .iter()
+        .map(|(user, entry)| {
+            let key = key(Some(&salt), user.get());
+            users.insert(key.clone(), *user);
+            (key, entry.tickets, exclude.contains(user))
+        })
+        .collect();
+    tickets.sort_unstable();
+    let hash = participants_hash(&tickets, seed);
+    let winners = draw_keys(&hash, &tickets, count)
+        .iter()
+        .map(|key| users[key])
+        .collect();
+    let mut excluded: Vec<u64> = exclude.iter().map(|user| user.get()).collect();
+    excluded.sort_unstable();
+    (
+        winners,
+        Draw {
+            seed,
+            hash,
+            excluded,
+            salt: Some(salt),
+            erased: Vec::new(),
+        },
+    )
+}
+
+/// Repeats a stored draw with the remaining participants and the tombstones of erased ones,
+/// returns the hash and whether the winners start with the ones drawn again
+pub fn repeat_draw(
+    participants: &HashMap<UserId, Entry>,
+    draw: &Draw,
+    count: usize,
+    winners: &[u64],
+) -> (String, bool) {
+    let salt = draw.salt.as_deref();
+    let mut tickets: Vec<Ticket> = participants
+        .iter()
+        .map(|(user, entry)| {
+            let user = user.get();
+            (
+                key(salt, user),
+                entry.tickets,
+                draw.excluded.contains(&user),
+            )
+        })
+        .chain(draw.erased.iter().map(|tombstone| {
+            (
+                Key::Leaf(tombstone.leaf.clone()),
+                tombstone.tickets,
+                tombstone.excluded,
+            )
+        }))
+        .collect();
+    tickets.sort_unstable();
+    let hash = participants_hash(&tickets, draw.seed);
+    let drawn = draw_keys(&hash, &tickets, count);
+    let matches = winners.len() >= drawn.len()
+        && drawn
+            .iter()
+            .zip(winners)
+            .all(|(key, &winner)| *key == self::key(salt, winner));
+    (hash, matches)
+}
+
+/// Removes the user from the draw, a participant is replaced by their tombstone
+pub fn erase(draw: &mut Draw, user: u64, tickets: Option<u32>) {
+    let excluded = draw.excluded.contains(&user);
+    draw.excluded.retain(|&excluded| excluded != user);
+    let Some(tickets) = tickets else {
+        return;
+    };
+    let leaf = match key(draw.salt.as_deref(), user) {
+        Key::Leaf(leaf) => leaf,
+        Key::Id(_) => String::new(),
+    };
+    draw.erased.push(Tombstone {
+        leaf,
+        tickets,
+        excluded,
+    });
+}
+
+#[derive(Encode, Decode)]
+struct Seed {
+    seed: u64,
+    salt: String,
+}
+
+/// Hash of the seed and the salt, the salt is part of it since it decides the order of the draw
+pub fn commitment_hash(seed: u64, salt: &str) -> String {
+    let mut context = Context::new(&SHA256);
+    context.update(salt.as_bytes());
+    context.update(&seed.to_be_bytes());
+    hex(context.finish().as_ref())
+}
+
+/// Chooses the seed and salt of a new giveaway
+pub fn commit() -> anyhow::Result<Commitment> {
+    let seed = Seed {
+        seed: rand::random(),
+        salt: new_salt(),
+    };
+    Ok(Commitment {
+        sealed: crypto::seal(&encode_to_vec(&seed, config::standard())?)?,
+        hash: commitment_hash(seed.seed, &seed.salt),
+    })
+}
+
+/// A random salt for a new draw
+pub fn new_salt() -> String {
+    hex(&rand::random::<[u8; 32]>())
+}
+
+/// The seed and salt the giveaway has to be drawn with
+pub fn reveal(commitment: &Commitment) -> anyhow::Result<(u64, String)> {
+    let seed: Seed = decode_from_slice(&crypto::open(&commitment.sealed)?, config::standard())?.0;
+    if commitment_hash(seed.seed, &seed.salt) != commitment.hash {
+        anyhow::bail!("The sealed seed doesn't match its hash");
+    }
+    Ok((seed.seed, seed.salt))
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
@@ -0,0 +1,179 @@
+use log::error;
+use poise::serenity_prelude::GuildId;
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use crate::metrics::metrics;
+use crate::{
+    storage::Db,
+    structs::{Entry, GiveawayId, MyHttpCache},
+    update_message,
+};
+
+const FLUSH_INTERVAL: Duration = Duration::from_millis(300);
+
+#[derive(Debug, Clone, Copy)]
+pub enum Change {
+    /// Keeps the stored entry, in case it was written in the meantime
+    Enter(Entry),
+    /// Entering again after leaving starts over with a new entry
+    Reenter(Entry),
+    Leave,
+}
+
+impl Change {
+    pub fn apply(self, participants: &mut HashMap<u64, Entry>, user: u64) {
+        match self {
+            Change::Enter(entry) => {
+                participants.entry(user).or_insert(entry);
+            }
+            Change::Reenter(entry) => {
+                participants.insert(user, entry);
+            }
+            Change::Leave => {
+                participants.remove(&user);
+            }
+        }
+    }
+}
+
+/// The latest change per user
+pub type Changes = HashMap<u64, Change>;
+
+/// Entries and exits of giveaways, written together every [`FLUSH_INTERVAL`] instead of one
+/// transaction per button press. [`Db::lock_guild`] writes them first, so everything changing a
+/// giveaway in several steps sees every entry accepted so far
+#[derive(Default)]
+pub struct EntryQueue {
+    pending: Mutex<HashMap<GuildId, HashMap<GiveawayId, Changes>>>,
+    /// Giveaways whose message still shows the old participant count
+    outdated: Mutex<HashMap<GuildId, HashSet<GiveawayId>>>,
+    /// Giveaways being finished, entries queued now would be dropped when they are archived
+    closed: Mutex<HashSet<(GuildId, GiveawayId)>>,
+}
+
+impl EntryQueue {
+    /// Whether the user takes part, `stored` is what the storage says without queued changes
+    pub fn participates(&self, guild: GuildId, id: GiveawayId, user: u64, stored: bool) -> bool {
+        self.pending
+            .lock()
+            .unwrap()
+            .get(&guild)
+            .and_then(|giveaways| giveaways.get(&id))
+            .and_then(|changes| changes.get(&user))
+            .map_or(stored, |change| !matches!(change, Change::Leave))
+    }
+
+    /// Returns false, if the user already takes part, or None if the giveaway is being finished
+    pub fn add(&self, guild: GuildId, id: GiveawayId, user: u64, stored: bool) -> Option<bool> {
+        let mut pending = self.pending.lock().unwrap();
+        //  Checked while holding the queue, so nothing is added after [`EntryQueue::close`]
+        if self.closed.lock().unwrap().contains(&(guild, id)) {
+            return None;
+        }
+        let changes = pending.entry(guild).or_default().entry(id).or_default();
+        let change = match changes.get(&user) {
+            Some(Change::Leave) => Change::Reenter(Entry::new()),
+            Some(_) => return Some(false),
+            None if stored => return Some(false),
+            None => Change::Enter(Entry::new()),
+        };
+        changes.insert(user, change);
+        Some(true)
+    }
+
+    /// Refuses new entries until [`EntryQueue::reopen`] and writes the ones queued so far, so the
+    /// giveaway can be read with every entry that was confirmed
+    pub fn close(&self, db: &Db, guild: GuildId, id: GiveawayId) -> anyhow::Result<()> {
+        {
+            let _pending = self.pending.lock().unwrap();
+            self.closed.lock().unwrap().insert((guild, id));
+        }
+        self.flush(db, guild)
+    }
+
+    /// After the giveaway was archived or put back
+    pub fn reopen(&self, guild: GuildId, id: GiveawayId) {
+        self.closed.lock().unwrap().remove(&(guild, id));
+    }
+
+    //  Removing someone who doesn't take part changes nothing, so it's not checked
+    pub fn remove(&self, guild: GuildId, id: GiveawayId, user: u64) {
+        self.pending
+            .lock()
+            .unwrap()
+            .entry(guild)
+            .or_default()
+            .entry(id)
+            .or_default()
+            .insert(user, Change::Leave);
+    }
+
+    /// Writes the queued changes of the guild, those of giveaways that ended meanwhile are dropped
+    pub fn flush(&self, db: &Db, guild: GuildId) -> anyhow::Result<()> {
+        let Some(giveaways) = self.pending.lock().unwrap().remove(&guild) else {
+            return Ok(());
+        };
+        let ids: Vec<GiveawayId> = giveaways.keys().copied().collect();
+        if let Err(err) = db.storage().update_participants(guild, &giveaways) {
+            //  Tried again with the next flush, newer changes take precedence
+            let mut pending = self.pending.lock().unwrap();
+            let queued = pending.entry(guild).or_default();
+            for (id, changes) in giveaways {
+                let queued = queued.entry(id).or_default();
+                for (user, change) in changes {
+                    queued.entry(user).or_insert(change);
+                }
+            }
+            return Err(err);
+        }
+        self.outdated
+            .lock()
+            .unwrap()
+            .entry(guild)
+            .or_default()
+            .extend(ids);
+        Ok(())
+    }
+
+    pub fn flush_all(&self, db: &Db) -> anyhow::Result<()> {
+        let guilds: Vec<GuildId> = self.pending.lock().unwrap().keys().copied().collect();
+        for guild in guilds {
+            self.flush(db, guild)?;
+        }
+        Ok(())
+    }
+
+    fn guilds(&self) -> HashSet<GuildId> {
+        let mut guilds: HashSet<GuildId> = self.pending.lock().unwrap().keys().copied().collect();
+        guilds.extend(self.outdated.lock().unwrap().keys());
+        guilds
+    }
+}
+
+/// Flushes the queued entries and updates the participant count of the giveaways
+pub async fn entry_task(db: Arc<Db>, http: MyHttpCache) -> anyhow::Result<()> {
+    loop {
+        tokio::time::sleep(FLUSH_INTERVAL).await;
+        for guild in db.entries().guilds() {
+            //  Taking the lock flushes, and the message isn't edited while the giveaway ends
+            let _guard = db.lock_guild(guild).await;
+            let outdated = db
+                .entries()
+                .outdated
+                .lock()
+                .unwrap()
+                .remove(&guild)
+                .unwrap_or_default();
+            for id in outdated {
+                if let Err(err) = update_message(guild, id, &db, &http).await {
+                    metrics().discord_error(err.as_ref());
+                    error!("Error updating participant count: {}", err);
+                }
+            }
+        }
+    }
+}
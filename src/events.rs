@@ -0,0 +1,93 @@
+use chrono::{DateTime, TimeDelta, Utc};
+use log::error;
+use poise::serenity_prelude::{
+    CacheHttp, ChannelId, CreateScheduledEvent, EditScheduledEvent, GuildId, ScheduledEventId,
+    ScheduledEventStatus, ScheduledEventType,
+};
+
+use crate::metrics::metrics;
+
+//  Discord limits the length of the name and the description of events
+const NAME_LENGTH: usize = 100;
+const DESCRIPTION_LENGTH: usize = 1000;
+
+/// Creates an external event pointing to the channel of the giveaway. Without a start the
+/// event is started right away, Discord only accepts start times in the future though.
+pub async fn create_event(
+    http: &impl CacheHttp,
+    guild: GuildId,
+    title: &str,
+    description: &str,
+    channel: ChannelId,
+    start: Option<DateTime<Utc>>,
+    end: DateTime<Utc>,
+) -> anyhow::Result<ScheduledEventId> {
+    let event = guild
+        .create_scheduled_event(
+            http,
+            CreateScheduledEvent::new(
+                ScheduledEventType::External,
+                title.chars().take(NAME_LENGTH).collect::<String>(),
+                start.unwrap_or(Utc::now() + TimeDelta::minutes(1)),
+            )
+            .end_time(end)
+            .description(
+                description
+                    .chars()
+                    .take(DESCRIPTION_LENGTH)
+                    .collect::<String>(),
+            )
+            .location(format!("https://discord.com/channels/{guild}/{channel}"))
+            .audit_log_reason("Giveaway"),
+        )
+        .await?;
+    if start.is_none() {
+        start_event(http, guild, event.id.get()).await;
+    }
+    Ok(event.id)
+}
+
+pub async fn start_event(http: &impl CacheHttp, guild: GuildId, event: u64) {
+    edit_event(
+        http,
+        guild,
+        event,
+        EditScheduledEvent::new().status(ScheduledEventStatus::Active),
+    )
+    .await;
+}
+
+pub async fn move_event_end(http: &impl CacheHttp, guild: GuildId, event: u64, end: DateTime<Utc>) {
+    edit_event(http, guild, event, EditScheduledEvent::new().end_time(end)).await;
+}
+
+/// Finished giveaways complete the event, cancelled ones remove it
+pub async fn end_event(http: &impl CacheHttp, guild: GuildId, event: u64, cancelled: bool) {
+    if cancelled {
+        if let Err(err) = guild.delete_scheduled_event(http.http(), event).await {
+            metrics().discord_error(&err);
+            error!("Error deleting event: {}", err);
+        }
+        return;
+    }
+    edit_event(
+        http,
+        guild,
+        event,
+        EditScheduledEvent::new().status(ScheduledEventStatus::Completed),
+    )
+    .await;
+}
+
+//  Failing to update the event is no reason for the giveaway itself to fail
+async fn edit_event(
+    http: &impl CacheHttp,
+    guild: GuildId,
+    event: u64,
+    builder: EditScheduledEvent<'_>,
+) {
+    if let Err(err) = guild.edit_scheduled_event(http, event, builder).await {
+        metrics().discord_error(&err);
+        error!("Error updating event: {}", err);
+    }
+}
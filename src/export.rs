@@ -0,0 +1,74 @@
+use anyhow::Context as _;
+use poise::{
+    Context, CreateReply, command,
+    serenity_prelude::{CreateAttachment, GuildId},
+};
+use std::{collections::BTreeMap, sync::Arc};
+
+use crate::{
+    crypto, db_read,
+    locale::{language, t},
+    storage::Db,
+};
+
+/// In front of encrypted exports, so /import knows to decrypt them
+pub(crate) const ENCRYPTED_HEADER: &[u8] = b"do-bot encrypted export\n";
+
+/// Exports giveaways, drafts, the archive and the settings of this server as JSON
+#[command(
+    slash_command,
+    default_member_permissions = "ADMINISTRATOR",
+    guild_only
+)]
+pub async fn export(ctx: Context<'_, Arc<Db>, anyhow::Error>) -> anyhow::Result<()> {
+    ctx.defer_ephemeral().await?;
+    let guild = ctx.guild_id().context("Not in a guild")?;
+    let state = db_read(ctx.data(), guild)?;
+    let data = serde_json::to_vec_pretty(&state)?;
+    //  Exports contain the participants and are sent through Discord
+    let language = language(ctx);
+    let (data, name, note) = match crypto::key_configured() {
+        true => (
+            [ENCRYPTED_HEADER, &crypto::seal(&data)?].concat(),
+            format!("export-{guild}.json.enc"),
+            t!(language, "export-encrypted"),
+        ),
+        false => (data, format!("export-{guild}.json"), String::new()),
+    };
+    ctx.send(
+        CreateReply::default()
+            .content(t!(
+                language,
+                "export-done",
+                giveaways = state.giveaways.len(),
+                drafts = state.drafts.len(),
+                archived = state.archive.len(),
+                note
+            ))
+            .attachment(CreateAttachment::bytes(data, name))
+            .ephemeral(true),
+    )
+    .await?;
+    Ok(())
+}
+
+/// `do-bot export [guild]` prints the state of one or all guilds as JSON instead of starting the bot
+pub fn cli(db: &Db, guild: Option<&str>) -> anyhow::Result<()> {
+    let json = match guild {
+        Some(guild) => {
+            let guild: GuildId = guild.parse().context("Invalid guild id")?;
+            serde_json::to_string_pretty(&db_read(db, guild)?)?
+        }
+        None => {
+            let guilds: BTreeMap<_, _> = db
+                .storage()
+                .guilds()?
+                .into_iter()
+                .map(|(guild, state)| (guild.get(), state))
+                .collect();
+            serde_json::to_string_pretty(&guilds)?
+        }
+    };
+    println!("{json}");
+    Ok(())
+}
@@ -0,0 +1,122 @@
+use bincode::{Decode, Encode};
+use poise::serenity_prelude::{GuildId, UserId};
+use redb::{ReadableTable, TableDefinition};
+
+use crate::{
+    bc,
+    leaderboard::add_wins,
+    storage::Redb,
+    structs::{Draw, GiveawayId},
+};
+
+/// Giveaways whose winners are drawn, but that aren't completely finished yet, keyed by guild and
+/// giveaway. If the bot stops in between, they are finished with the same winners on the next
+/// start, skipping the steps that were done already. Removed once the steps after archiving the
+/// giveaway are done as well
+pub(crate) const FINISHING_TABLE: TableDefinition<(u64, u64), bc::Bincode<Finishing>> =
+    TableDefinition::new("finishing");
+
+#[derive(Debug, Encode, Decode)]
+pub struct Finishing {
+    pub drawn_winners: Vec<u64>,
+    pub draw: Draw,
+    /// In the order they were completed
+    pub done: Vec<Step>,
+}
+
+/// What mustn't happen twice when finishing a giveaway
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Encode, Decode)]
+pub enum Step {
+    /// Channel and message announcing the winners
+    Announced(u64, u64),
+    WinsRecorded,
+    /// The secret code of the winner was sent, or the host was told it couldn't be
+    CodeSent(u64),
+    /// The winners got the winner role, their grants are stored in the archive
+    RolesGranted,
+}
+
+impl Finishing {
+    pub fn winners(&self) -> Vec<UserId> {
+        self.drawn_winners
+            .iter()
+            .copied()
+            .map(UserId::new)
+            .collect()
+    }
+
+    pub fn code_sent(&self, winner: u64) -> bool {
+        self.done.contains(&Step::CodeSent(winner))
+    }
+
+    pub fn announcement(&self) -> Option<(u64, u64)> {
+        self.done.iter().find_map(|step| match step {
+            Step::Announced(channel, message) => Some((*channel, *message)),
+            _ => None,
+        })
+    }
+}
+
+pub fn finishing(db: &Redb, guild: GuildId, id: GiveawayId) -> anyhow::Result<Option<Finishing>> {
+    let db_read = db.begin_read()?;
+    let table = db_read.open_table(FINISHING_TABLE)?;
+    Ok(table.get((guild.get(), id.0))?.map(|v| v.value()))
+}
+
+/// Stored before anything is posted, so a restart doesn't lose the giveaway or draw it again
+pub fn mark_finishing(
+    db: &Redb,
+    guild: GuildId,
+    id: GiveawayId,
+    finishing: &Finishing,
+) -> anyhow::Result<()> {
+    let db = db.begin_write()?;
+    db.open_table(FINISHING_TABLE)?
+        .insert((guild.get(), id.0), finishing)?;
+    db.commit()?;
+    Ok(())
+}
+
+/// Stored right after the step, the record itself has to exist already
+pub fn complete_step(db: &Redb, guild: GuildId, id: GiveawayId, step: Step) -> anyhow::Result<()> {
+    let db = db.begin_write()?;
+    {
+        let mut table = db.open_table(FINISHING_TABLE)?;
+        let key = (guild.get(), id.0);
+        let Some(mut finishing) = table.get(key)?.map(|v| v.value()) else {
+            anyhow::bail!("{id} in {guild} isn't being finished");
+        };
+        finishing.done.push(step);
+        table.insert(key, finishing)?;
+    }
+    db.commit()?;
+    Ok(())
+}
+
+/// Records the wins of the drawn winners together with the step, so they're never counted twice
+pub fn record_wins_once(db: &Redb, guild: GuildId, id: GiveawayId) -> anyhow::Result<()> {
+    let db = db.begin_write()?;
+    {
+        let mut table = db.open_table(FINISHING_TABLE)?;
+        let key = (guild.get(), id.0);
+        let Some(mut finishing) = table.get(key)?.map(|v| v.value()) else {
+            anyhow::bail!("{id} in {guild} isn't being finished");
+        };
+        if finishing.done.contains(&Step::WinsRecorded) {
+            return Ok(());
+        }
+        add_wins(&db, guild, &finishing.winners())?;
+        finishing.done.push(Step::WinsRecorded);
+        table.insert(key, finishing)?;
+    }
+    db.commit()?;
+    Ok(())
+}
+
+pub fn unmark_finishing(db: &Redb, guild: GuildId, id: GiveawayId) -> anyhow::Result<()> {
+    let db = db.begin_write()?;
+    db.open_table(FINISHING_TABLE)?
+        .remove((guild.get(), id.0))?;
+    db.commit()?;
+    Ok(())
+}
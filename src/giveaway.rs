@@ -0,0 +1,537 @@
+use anyhow::Context as _;
+use chrono::Utc;
+use poise::{
+    Context, CreateReply, command,
+    serenity_prelude::{
+        AutocompleteChoice, ButtonStyle, CreateActionRow, CreateButton, GuildId, Mentionable,
+        UserId,
+    },
+};
+use std::sync::Arc;
+
+use crate::{
+    archive_or_restore, cancel_giveaway,
+    datetime::parse_duration,
+    db_read, db_write,
+    draw::{commitment_hash, repeat_draw},
+    edit_mirrors,
+    events::move_event_end,
+    locale::{language, t},
+    parse_error,
+    participants::may_view,
+    reroll_giveaway, schedule_end,
+    storage::Db,
+    structs::{Giveaway, GiveawayId, MyHttpCache, RealGiveaway, UserAction},
+};
+
+#[command(
+    slash_command,
+    default_member_permissions = "CREATE_EVENTS",
+    guild_only,
+    subcommands("list", "end_all", "cancel_all", "transfer"),
+    subcommand_required
+)]
+pub async fn giveaway(_ctx: Context<'_, Arc<Db>, anyhow::Error>) -> anyhow::Result<()> {
+    Ok(())
+}
+
+#[command(slash_command, guild_only)]
+pub async fn list(ctx: Context<'_, Arc<Db>, anyhow::Error>) -> anyhow::Result<()> {
+    let guild = ctx.guild_id().context("Not in a guild")?;
+    let language = language(ctx);
+    let state = db_read(ctx.data(), guild)?;
+    let permissions = ctx
+        .author_member()
+        .await
+        .and_then(|member| member.permissions);
+    //  The count of anonymous giveaways is as private as their participants
+    let mut giveaways: Vec<(GiveawayId, bool, RealGiveaway)> = state
+        .giveaways
+        .into_iter()
+        .map(|(id, ga)| (id, may_view(&ga, permissions), ga.into()))
+        .collect();
+    let mut drafts: Vec<_> = state.drafts.into_iter().collect();
+    drafts.sort_by_key(|(_, draft)| draft.start);
+    //  Giveaways without an end come last
+    giveaways.sort_by_key(|(_, _, ga)| (ga.time.is_none(), ga.time));
+
+    let mut lines = Vec::new();
+    for (id, visible, giveaway) in giveaways {
+        let end = giveaway
+            .time
+            .map(|t| {
+                t!(
+                    language,
+                    "list-ends",
+                    time = format!("<t:{}:R>", t.timestamp())
+                )
+            })
+            .unwrap_or_else(|| t!(language, "list-no-end"));
+        let link = giveaway.message.link(giveaway.channel, Some(guild));
+        lines.push(match visible {
+            true => t!(
+                language,
+                "list-giveaway",
+                id,
+                title = giveaway.title,
+                link,
+                participants = giveaway.participants.len(),
+                end,
+            ),
+            false => t!(
+                language,
+                "list-giveaway-anonymous",
+                id,
+                title = giveaway.title,
+                link,
+                end,
+            ),
+        });
+    }
+    let mut message = match lines.len() {
+        0 => t!(language, "list-none"),
+        count => t!(language, "list-running", count),
+    };
+    if !drafts.is_empty() {
+        lines.push(t!(language, "list-scheduled", count = drafts.len()));
+    }
+    for (id, draft) in drafts {
+        lines.push(t!(
+            language,
+            "list-draft",
+            id,
+            title = draft.title,
+            channel = format!("<#{}>", draft.channel),
+            start = format!("<t:{}:R>", draft.start)
+        ));
+    }
+    for line in lines {
+        //  Discord rejects messages longer than 2000 characters
+        if message.chars().count() + line.chars().count() > 1950 {
+            message.push_str("\n...");
+            break;
+        }
+        message.push_str(&line);
+    }
+
+    ctx.send(
+        CreateReply::default()
+            .content(message)
+            .reply(true)
+            .ephemeral(true),
+    )
+    .await?;
+    Ok(())
+}
+
+#[command(slash_command, guild_only, required_permissions = "ADMINISTRATOR")]
+pub async fn end_all(ctx: Context<'_, Arc<Db>, anyhow::Error>) -> anyhow::Result<()> {
+    let guild = ctx.guild_id().context("Not in a guild")?;
+    confirm(
+        ctx,
+        &t!(language(ctx), "end-all-confirm"),
+        UserAction::EndAll(Some(guild)),
+        UserAction::EndAll(None),
+    )
+    .await
+}
+
+#[command(slash_command, guild_only, required_permissions = "ADMINISTRATOR")]
+pub async fn cancel_all(ctx: Context<'_, Arc<Db>, anyhow::Error>) -> anyhow::Result<()> {
+    let guild = ctx.guild_id().context("Not in a guild")?;
+    confirm(
+        ctx,
+        &t!(language(ctx), "cancel-all-confirm"),
+        UserAction::CancelAll(Some(guild)),
+        UserAction::CancelAll(None),
+    )
+    .await
+}
+
+/// The new host can finish and cancel the giveaway without further permissions
+#[command(slash_command, guild_only)]
+pub async fn transfer(
+    ctx: Context<'_, Arc<Db>, anyhow::Error>,
+    #[autocomplete = "active_autocomplete"] giveaway: GiveawayId,
+    user: UserId,
+) -> anyhow::Result<()> {
+    ctx.defer_ephemeral().await?;
+    let guild = ctx.guild_id().context("Not in a guild")?;
+    let language = language(ctx);
+    let _guard = ctx.data().lock_guild(guild).await;
+    //  Scheduled giveaways only show the host once they are posted
+    let updated: Option<Option<RealGiveaway>> = db_write(ctx.data(), guild, move |state| {
+        if let Some(stored) = state.giveaways.get_mut(&giveaway) {
+            stored.host = Some(user.get());
+            return Some(Some(stored.clone().into()));
+        }
+        let draft = state.drafts.get_mut(&giveaway)?;
+        draft.host = user.get();
+        Some(None)
+    })?;
+    let Some(giveaway_data) = updated else {
+        ctx.reply(t!(language, "giveaway-not-running")).await?;
+        return Ok(());
+    };
+    if let Some(giveaway_data) = giveaway_data {
+        giveaway_data
+            .channel
+            .edit_message(
+                ctx,
+                giveaway_data.message,
+                giveaway_data.view().edit_message(false),
+            )
+            .await?;
+        edit_mirrors(
+            &giveaway_data,
+            giveaway_data.view().edit_message(false),
+            &ctx,
+        )
+        .await;
+    }
+    ctx.reply(t!(language, "transfer-done", user = user.mention()))
+        .await?;
+    Ok(())
+}
+
+pub(crate) async fn confirm(
+    ctx: Context<'_, Arc<Db>, anyhow::Error>,
+    question: &str,
+    yes: UserAction,
+    no: UserAction,
+) -> anyhow::Result<()> {
+    let language = language(ctx);
+    let ar = CreateActionRow::Buttons(Vec::from([
+        CreateButton::new(serde_json::to_string(&yes).unwrap())
+            .label(t!(language, "confirm-yes"))
+            .style(ButtonStyle::Danger),
+        CreateButton::new(serde_json::to_string(&no).unwrap())
+            .label(t!(language, "confirm-no"))
+            .style(ButtonStyle::Secondary),
+    ]));
+    ctx.send(
+        CreateReply::default()
+            .content(question)
+            .reply(true)
+            .ephemeral(true)
+            .components(vec![ar]),
+    )
+    .await?;
+    Ok(())
+}
+
+/// Looks for a running giveaway first, then in the archive
+pub fn find_giveaway(db: &Db, guild: GuildId, id: GiveawayId) -> anyhow::Result<Option<Giveaway>> {
+    let mut state = db_read(db, guild)?;
+    Ok(state
+        .giveaways
+        .remove(&id)
+        .or_else(|| state.archive.remove(&id).map(|archived| archived.giveaway)))
+}
+
+pub async fn any_autocomplete<'a>(
+    ctx: Context<'a, Arc<Db>, anyhow::Error>,
+    part: &'a str,
+) -> impl Iterator<Item = AutocompleteChoice> + 'a {
+    active_autocomplete(ctx, part)
+        .await
+        .chain(archive_autocomplete(ctx, part).await)
+        .take(25)
+}
+
+async fn archive_autocomplete<'a>(
+    ctx: Context<'a, Arc<Db>, anyhow::Error>,
+    part: &'a str,
+) -> impl Iterator<Item = AutocompleteChoice> + 'a {
+    let mut archive: Vec<_> = ctx
+        .guild_id()
+        .and_then(|guild| Some(db_read(ctx.data(), guild).ok()?.archive))
+        .unwrap_or_default()
+        .into_iter()
+        .filter(move |(id, archived)| {
+            archived
+                .giveaway
+                .title
+                .to_lowercase()
+                .contains(&part.to_lowercase())
+                || id.to_string().starts_with(part)
+        })
+        .collect();
+    //  Most recently ended first
+    archive.sort_by_key(|(_, archived)| -archived.ended);
+    archive
+        .into_iter()
+        .take(25)
+        .map(|(id, archived)| AutocompleteChoice::new(archived.giveaway.title, id.to_string()))
+}
+
+#[command(
+    slash_command,
+    default_member_permissions = "CREATE_EVENTS",
+    guild_only
+)]
+pub async fn reroll(
+    ctx: Context<'_, Arc<Db>, anyhow::Error>,
+    #[autocomplete = "archive_autocomplete"] giveaway: GiveawayId,
+    #[min = 1]
+    #[max = 100]
+    winners: Option<u32>,
+) -> anyhow::Result<()> {
+    ctx.defer_ephemeral().await?;
+    let guild = ctx.guild_id().context("Not in a guild")?;
+    let _guard = ctx.data().lock_guild(guild).await;
+    let http = MyHttpCache::new(
+        ctx.serenity_context().http.clone(),
+        ctx.serenity_context().cache.clone(),
+    );
+    let language = language(ctx);
+    let content = match reroll_giveaway(
+        guild,
+        giveaway,
+        winners.unwrap_or(1) as usize,
+        ctx.data(),
+        &http,
+    )
+    .await?
+    {
+        None => t!(language, "reroll-not-archived"),
+        Some(winners) if winners.is_empty() => t!(language, "reroll-no-participants"),
+        Some(winners) => t!(language, "reroll-done", count = winners.len()),
+    };
+    ctx.reply(content).await?;
+    Ok(())
+}
+
+async fn active_autocomplete<'a>(
+    ctx: Context<'a, Arc<Db>, anyhow::Error>,
+    part: &'a str,
+) -> impl Iterator<Item = AutocompleteChoice> + 'a {
+    let mut giveaways: Vec<_> = ctx
+        .guild_id()
+        .and_then(|guild| Some(db_read(ctx.data(), guild).ok()?.giveaways))
+        .unwrap_or_default()
+        .into_iter()
+        .filter(move |(id, giveaway)| {
+            giveaway.title.to_lowercase().contains(&part.to_lowercase())
+                || id.to_string().starts_with(part)
+        })
+        .collect();
+    giveaways.sort_by(|(_, a), (_, b)| a.title.cmp(&b.title));
+    giveaways
+        .into_iter()
+        .take(25)
+        .map(|(id, giveaway)| AutocompleteChoice::new(giveaway.title, id.to_string()))
+}
+
+#[command(
+    slash_command,
+    default_member_permissions = "CREATE_EVENTS",
+    guild_only
+)]
+pub async fn grant_entries(
+    ctx: Context<'_, Arc<Db>, anyhow::Error>,
+    #[autocomplete = "active_autocomplete"] giveaway: GiveawayId,
+    user: UserId,
+    #[min = 1] count: u32,
+) -> anyhow::Result<()> {
+    ctx.defer_ephemeral().await?;
+    let guild = ctx.guild_id().context("Not in a guild")?;
+    let _guard = ctx.data().lock_guild(guild).await;
+    let tickets = db_write(ctx.data(), guild, move |state| {
+        state.giveaways.get_mut(&giveaway).map(|giveaway| {
+            giveaway.participants.get_mut(&user.get()).map(|entry| {
+                entry.tickets = entry.tickets.saturating_add(count);
+                entry.tickets
+            })
+        })
+    })?;
+    let language = language(ctx);
+    let content = match tickets {
+        None => t!(language, "giveaway-not-running"),
+        Some(None) => t!(language, "grant-entries-not-entered", user = user.mention()),
+        Some(Some(tickets)) => t!(
+            language,
+            "grant-entries-done",
+            user = user.mention(),
+            tickets
+        ),
+    };
+    ctx.reply(content).await?;
+    Ok(())
+}
+
+#[command(
+    slash_command,
+    default_member_permissions = "CREATE_EVENTS",
+    guild_only
+)]
+pub async fn extend(
+    ctx: Context<'_, Arc<Db>, anyhow::Error>,
+    #[autocomplete = "active_autocomplete"] giveaway: GiveawayId,
+    duration: String,
+) -> anyhow::Result<()> {
+    ctx.defer_ephemeral().await?;
+    let guild = ctx.guild_id().context("Not in a guild")?;
+    let delta = parse_duration(&duration).map_err(parse_error)?;
+    let language = language(ctx);
+    let _guard = ctx.data().lock_guild(guild).await;
+    let now = Utc::now();
+    //  None if it isn't running, the inner one if the new end would be in the past
+    let updated: Option<Option<RealGiveaway>> = db_write(ctx.data(), guild, move |state| {
+        let stored = state.giveaways.get_mut(&giveaway)?;
+        let mut real: RealGiveaway = stored.clone().into();
+        //  Giveaways without an end get one relative to now
+        let time = real.time.unwrap_or(now).checked_add_signed(delta);
+        match time {
+            Some(time) if time > now => {
+                real.time = Some(time);
+                stored.time = Some(time.timestamp());
+                Some(Some(real))
+            }
+            _ => Some(None),
+        }
+    })?;
+    let giveaway_data = match updated {
+        None => {
+            ctx.reply(t!(language, "giveaway-not-running")).await?;
+            return Ok(());
+        }
+        Some(None) => {
+            ctx.reply(t!(language, "extend-past")).await?;
+            return Ok(());
+        }
+        Some(Some(giveaway)) => giveaway,
+    };
+    let time = giveaway_data.time.context("Giveaway without end")?;
+    giveaway_data
+        .channel
+        .edit_message(
+            ctx,
+            giveaway_data.message,
+            giveaway_data.view().edit_message(false),
+        )
+        .await?;
+    edit_mirrors(
+        &giveaway_data,
+        giveaway_data.view().edit_message(false),
+        &ctx,
+    )
+    .await;
+    if let Some(event) = giveaway_data.event {
+        move_event_end(&ctx, guild, event, time).await;
+    }
+    let http = MyHttpCache::new(
+        ctx.serenity_context().http.clone(),
+        ctx.serenity_context().cache.clone(),
+    );
+    schedule_end(guild, giveaway, time, ctx.data().clone(), http);
+    ctx.reply(t!(
+        language,
+        "extend-done",
+        time = format!("<t:{}:R>", time.timestamp())
+    ))
+    .await?;
+    Ok(())
+}
+
+#[command(
+    slash_command,
+    default_member_permissions = "CREATE_EVENTS",
+    guild_only
+)]
+pub async fn cancel(
+    ctx: Context<'_, Arc<Db>, anyhow::Error>,
+    #[autocomplete = "active_autocomplete"] giveaway: GiveawayId,
+    reason: Option<String>,
+    delete: Option<bool>,
+) -> anyhow::Result<()> {
+    ctx.defer_ephemeral().await?;
+    let guild = ctx.guild_id().context("Not in a guild")?;
+    let language = language(ctx);
+    let _guard = ctx.data().lock_guild(guild).await;
+    let giveaway_data: Option<RealGiveaway> = db_write(ctx.data(), guild, move |state| {
+        state.giveaways.remove(&giveaway)
+    })?
+    .map(|v| v.into());
+    let Some(giveaway_data) = giveaway_data else {
+        ctx.reply(t!(language, "giveaway-not-running")).await?;
+        return Ok(());
+    };
+    let reason = reason
+        .map(|reason| reason.trim().to_string())
+        .filter(|reason| !reason.is_empty());
+    let result = cancel_giveaway(
+        &giveaway_data,
+        reason,
+        delete.unwrap_or(false),
+        ctx.serenity_context(),
+    )
+    .await;
+    let content = match result {
+        Ok(_) => t!(language, "cancel-done"),
+        Err(_) => t!(language, "cancel-failed"),
+    };
+    let http = MyHttpCache::new(
+        ctx.serenity_context().http.clone(),
+        ctx.serenity_context().cache.clone(),
+    );
+    archive_or_restore(ctx.data(), &http, guild, giveaway, giveaway_data, result)?;
+    ctx.reply(content).await?;
+    Ok(())
+}
+
+#[command(slash_command, guild_only)]
+pub async fn verify(
+    ctx: Context<'_, Arc<Db>, anyhow::Error>,
+    #[autocomplete = "archive_autocomplete"] giveaway: GiveawayId,
+) -> anyhow::Result<()> {
+    ctx.defer_ephemeral().await?;
+    let guild = ctx.guild_id().context("Not in a guild")?;
+    let language = language(ctx);
+    let archived = db_read(ctx.data(), guild)?.archive.remove(&giveaway);
+    let Some(archived) = archived else {
+        ctx.reply(t!(language, "giveaway-not-archived")).await?;
+        return Ok(());
+    };
+    let Some(draw) = &archived.draw else {
+        ctx.reply(t!(language, "verify-no-draw")).await?;
+        return Ok(());
+    };
+    //  Draws without a salt hashed the user IDs, those of erased participants are gone
+    if draw.salt.is_none() && !draw.erased.is_empty() {
+        ctx.reply(t!(language, "verify-erased", erased = draw.erased.len()))
+            .await?;
+        return Ok(());
+    }
+    let participants = RealGiveaway::from(archived.giveaway.clone()).participants;
+    let count = archived.slots.len();
+    let (hash, winners_match) = repeat_draw(&participants, draw, count, &archived.winners);
+    //  Giveaways from older versions published no hash of their seed
+    let commitment = archived.giveaway.commitment.as_ref();
+    let committed = draw
+        .salt
+        .as_deref()
+        .map(|salt| commitment_hash(draw.seed, salt));
+    //  Rerolls come after the initial winners and aren't part of the verifiable draw
+    let content = if hash != draw.hash {
+        t!(language, "verify-hash-mismatch", stored = draw.hash, hash)
+    } else if commitment.is_some_and(|commitment| Some(&commitment.hash) != committed.as_ref()) {
+        t!(language, "verify-commitment-mismatch")
+    } else if !winners_match {
+        t!(language, "verify-winners-mismatch")
+    } else {
+        let mut content = t!(
+            language,
+            "verify-correct",
+            participants = participants.len() + draw.erased.len(),
+            seed = draw.seed,
+            hash
+        );
+        if let Some(commitment) = commitment {
+            content.push_str(&t!(language, "verify-committed", hash = commitment.hash));
+        }
+        content
+    };
+    ctx.reply(content).await?;
+    Ok(())
+}
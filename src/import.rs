@@ -0,0 +1,314 @@
+use anyhow::Context as _;
+use chrono::DateTime;
+use chrono_tz::Tz;
+use poise::{ChoiceParameter, Context, command, serenity_prelude::Attachment};
+use std::{collections::hash_map, sync::Arc};
+
+use crate::{
+    crypto, db_write,
+    export::ENCRYPTED_HEADER,
+    locale::{error_text, language, localized, t},
+    resume_tasks,
+    storage::Db,
+    structs::{
+        ArchivedGiveaway, AutoExtend, Draft, Giveaway, GuildState, MAX_WINNERS, MyHttpCache,
+        Prizes, Requirements, Settings, WinnerRole,
+    },
+};
+
+#[derive(Debug, Clone, Copy, ChoiceParameter)]
+pub enum ImportMode {
+    #[name = "Zusammenführen"]
+    Merge,
+    #[name = "Ersetzen"]
+    Replace,
+}
+
+/// Restores a file from /export, merged into or replacing the data of this server
+#[command(
+    slash_command,
+    default_member_permissions = "ADMINISTRATOR",
+    guild_only,
+    owners_only
+)]
+pub async fn import(
+    ctx: Context<'_, Arc<Db>, anyhow::Error>,
+    file: Attachment,
+    mode: ImportMode,
+) -> anyhow::Result<()> {
+    ctx.defer_ephemeral().await?;
+    let guild = ctx.guild_id().context("Not in a guild")?;
+    let language = language(ctx);
+    let data = file.download().await?;
+    let imported = match validate(&data) {
+        Ok(imported) => imported,
+        Err(err) => {
+            ctx.reply(t!(
+                language,
+                "import-invalid",
+                error = error_text(&err, language)
+            ))
+            .await?;
+            return Ok(());
+        }
+    };
+    let totals = (
+        imported.giveaways.len(),
+        imported.drafts.len(),
+        imported.archive.len(),
+    );
+    let _guard = ctx.data().lock_guild(guild).await;
+    let (restored, skipped) = db_write(ctx.data(), guild, move |state| {
+        restore(state, imported, mode)
+    })?;
+    let (giveaways, drafts, archived) = match mode {
+        ImportMode::Merge => (
+            restored.giveaways.len(),
+            restored.drafts.len(),
+            restored.archive.len(),
+        ),
+        ImportMode::Replace => totals,
+    };
+
+    let http = MyHttpCache::new(
+        ctx.serenity_context().http.clone(),
+        ctx.serenity_context().cache.clone(),
+    );
+    resume_tasks(guild, restored, ctx.data(), &http)?;
+    let content = match skipped {
+        0 => t!(language, "import-done", giveaways, drafts, archived),
+        skipped => t!(
+            language,
+            "import-done-skipped",
+            giveaways,
+            drafts,
+            archived,
+            skipped
+        ),
+    };
+    ctx.reply(content).await?;
+    Ok(())
+}
+
+fn validate(data: &[u8]) -> anyhow::Result<GuildState> {
+    let state: GuildState = match data.strip_prefix(ENCRYPTED_HEADER) {
+        Some(sealed) => serde_json::from_slice(&crypto::open(sealed)?)?,
+        None => serde_json::from_slice(data)?,
+    };
+    if state.timezone.parse::<Tz>().is_err() {
+        anyhow::bail!(localized!(
+            "import-unknown-timezone",
+            timezone = state.timezone
+        ));
+    }
+    //  Everything else is trusted once it's stored, e.g. IDs become Discord IDs, which can't be 0
+    if !valid_settings(&state.settings) {
+        anyhow::bail!(localized!("import-invalid-settings"));
+    }
+    let valid = state
+        .giveaways
+        .iter()
+        .map(|(id, giveaway)| (id, valid_giveaway(giveaway)))
+        .chain(
+            state
+                .drafts
+                .iter()
+                .map(|(id, draft)| (id, valid_draft(draft))),
+        )
+        .chain(
+            state
+                .archive
+                .iter()
+                .map(|(id, archived)| (id, valid_archived(archived))),
+        );
+    for (id, valid) in valid {
+        if !valid {
+            anyhow::bail!(localized!("import-invalid-giveaway", id));
+        }
+    }
+    Ok(state)
+}
+
+fn valid_id(id: u64) -> bool {
+    id != 0
+}
+
+fn valid_time(time: i64) -> bool {
+    DateTime::from_timestamp(time, 0).is_some()
+}
+
+fn valid_settings(settings: &Settings) -> bool {
+    [
+        settings.role,
+        settings.announcement_channel,
+        settings.ping_role,
+        settings.alt_check.and_then(|check| check.log_channel),
+        settings.modlog.map(|modlog| modlog.channel),
+    ]
+    .into_iter()
+    .flatten()
+    .all(valid_id)
+}
+
+fn valid_prizes(prizes: &Prizes) -> bool {
+    prizes.0.iter().all(|prize| prize.count > 0) && prizes.total() <= MAX_WINNERS
+}
+
+fn valid_requirements(requirements: &Requirements) -> bool {
+    requirements
+        .role
+        .iter()
+        .chain(&requirements.excluded_roles)
+        .all(|&role| valid_id(role))
+}
+
+//  Every extension has to end at a valid time as well
+fn valid_end(time: Option<i64>, auto_extend: Option<AutoExtend>) -> bool {
+    let Some(time) = time else {
+        return true;
+    };
+    let extended = auto_extend.map_or(Some(time), |auto_extend| {
+        if auto_extend.extend_by <= 0 {
+            return None;
+        }
+        auto_extend
+            .extend_by
+            .checked_mul(i64::from(auto_extend.max_extensions))
+            .and_then(|extension| time.checked_add(extension))
+    });
+    valid_time(time) && extended.is_some_and(valid_time)
+}
+
+fn valid_winner_role(winner_role: Option<WinnerRole>) -> bool {
+    winner_role.is_none_or(|winner_role| valid_id(winner_role.role))
+}
+
+fn valid_giveaway(giveaway: &Giveaway) -> bool {
+    valid_id(giveaway.channel)
+        && valid_id(giveaway.message)
+        && giveaway.host.is_none_or(valid_id)
+        && giveaway.event.is_none_or(valid_id)
+        && giveaway.created.is_none_or(valid_time)
+        && valid_end(giveaway.time, giveaway.auto_extend)
+        && giveaway
+            .mirrors
+            .iter()
+            .all(|&(channel, message)| valid_id(channel) && valid_id(message))
+        && giveaway
+            .participants
+            .iter()
+            .all(|(&user, entry)| valid_id(user) && valid_time(entry.entered))
+        && valid_prizes(&giveaway.prizes)
+        && valid_requirements(&giveaway.requirements)
+        && valid_winner_role(giveaway.winner_role)
+}
+
+fn valid_draft(draft: &Draft) -> bool {
+    valid_id(draft.channel)
+        && valid_id(draft.host)
+        && draft.event.is_none_or(valid_id)
+        && valid_time(draft.start)
+        && valid_end(draft.time, draft.auto_extend)
+        && draft
+            .mirror_channels
+            .iter()
+            .all(|&channel| valid_id(channel))
+        && valid_prizes(&draft.prizes)
+        && valid_requirements(&draft.requirements)
+        && valid_winner_role(draft.winner_role)
+}
+
+//  Prizes of archived giveaways were rerolled already, only their IDs and times matter
+fn valid_archived(archived: &ArchivedGiveaway) -> bool {
+    let giveaway = &archived.giveaway;
+    valid_id(giveaway.channel)
+        && valid_id(giveaway.message)
+        && giveaway.host.is_none_or(valid_id)
+        && giveaway.time.is_none_or(valid_time)
+        && giveaway.created.is_none_or(valid_time)
+        && giveaway.participants.keys().all(|&user| valid_id(user))
+        && valid_winner_role(giveaway.winner_role)
+        && valid_time(archived.ended)
+        && archived.winners.iter().all(|&user| valid_id(user))
+        && archived
+            .announcement
+            .is_none_or(|(channel, message)| valid_id(channel) && valid_id(message))
+        && archived
+            .slots
+            .iter()
+            .all(|slot| slot.user.is_none_or(valid_id) && slot.deadline.is_none_or(valid_time))
+        && archived
+            .role_grants
+            .iter()
+            .all(|&(user, granted)| valid_id(user) && valid_time(granted))
+}
+
+//  Returns the entries whose tasks need to be started and how many entries were skipped while merging
+fn restore(state: &mut GuildState, imported: GuildState, mode: ImportMode) -> (GuildState, usize) {
+    let mut restored = GuildState::default();
+    let mut skipped = 0;
+    match mode {
+        ImportMode::Merge => {
+            for (id, giveaway) in imported.giveaways {
+                match state.giveaways.entry(id) {
+                    hash_map::Entry::Occupied(_) => skipped += 1,
+                    hash_map::Entry::Vacant(entry) => {
+                        entry.insert(giveaway.clone());
+                        restored.giveaways.insert(id, giveaway);
+                    }
+                }
+            }
+            for (id, draft) in imported.drafts {
+                match state.drafts.entry(id) {
+                    hash_map::Entry::Occupied(_) => skipped += 1,
+                    hash_map::Entry::Vacant(entry) => {
+                        entry.insert(draft.clone());
+                        restored.drafts.insert(id, draft);
+                    }
+                }
+            }
+            for (id, archived) in imported.archive {
+                match state.archive.entry(id) {
+                    hash_map::Entry::Occupied(_) => skipped += 1,
+                    hash_map::Entry::Vacant(entry) => {
+                        entry.insert(archived.clone());
+                        restored.archive.insert(id, archived);
+                    }
+                }
+            }
+        }
+        ImportMode::Replace => {
+            //  Entries that are still the same already have their tasks running
+            restored.giveaways = imported
+                .giveaways
+                .iter()
+                .filter(|(id, giveaway)| {
+                    state
+                        .giveaways
+                        .get(id)
+                        .is_none_or(|old| old.time != giveaway.time)
+                })
+                .map(|(id, giveaway)| (*id, giveaway.clone()))
+                .collect();
+            restored.drafts = imported
+                .drafts
+                .iter()
+                .filter(|(id, draft)| {
+                    state
+                        .drafts
+                        .get(id)
+                        .is_none_or(|old| old.start != draft.start)
+                })
+                .map(|(id, draft)| (*id, draft.clone()))
+                .collect();
+            restored.archive = imported
+                .archive
+                .iter()
+                .filter(|(id, _)| !state.archive.contains_key(id))
+                .map(|(id, archived)| (*id, archived.clone()))
+                .collect();
+            *state = imported;
+        }
+    }
+    (restored, skipped)
+}
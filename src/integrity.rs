@@ -0,0 +1,214 @@
+use chrono::{DateTime, Utc};
+use futures::{StreamExt, stream};
+use log::{error, info};
+use poise::serenity_prelude::{
+    ChannelId, DiscordJsonError, Error, ErrorResponse, GuildId, HttpError, MessageId,
+};
+use std::{fmt, sync::Arc, time::Duration};
+
+use crate::{
+    db_read, finish_task,
+    finishing::finishing,
+    giveaway_message_deleted,
+    metrics::metrics,
+    resume_tasks,
+    storage::Db,
+    structs::{Giveaway, GiveawayId, GuildState, MyHttpCache, RealGiveaway},
+};
+
+/// Guilds checked at the same time, each waits for Discord most of the time
+const CONCURRENT_GUILDS: usize = 16;
+const FIRST_RETRY: Duration = Duration::from_secs(60);
+const MAX_RETRY: Duration = Duration::from_secs(60 * 60);
+
+/// What the check on startup found, printed once every guild is done
+#[derive(Debug, Default)]
+struct Report {
+    giveaways: usize,
+    /// Message or channel deleted while the bot was offline, handled like a deleted message
+    dangling: usize,
+    /// Ended while the bot was offline
+    overdue: usize,
+    /// Overdue, but still running after finishing them, they are tried again later
+    failed: usize,
+    /// The message couldn't be fetched for another reason, e.g. missing permissions
+    unchecked: usize,
+    errors: usize,
+}
+
+impl Report {
+    fn add(&mut self, other: Report) {
+        self.giveaways += other.giveaways;
+        self.dangling += other.dangling;
+        self.overdue += other.overdue;
+        self.failed += other.failed;
+        self.unchecked += other.unchecked;
+        self.errors += other.errors;
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Checked {} running giveaways: {} dangling, {} overdue ({} failed to finish), {} \
+            couldn't be checked, {} errors",
+            self.giveaways, self.dangling, self.overdue, self.failed, self.unchecked, self.errors
+        )
+    }
+}
+
+enum MessageState {
+    Exists,
+    Missing,
+    Unknown,
+}
+
+async fn message_state(http: &MyHttpCache, channel: ChannelId, message: MessageId) -> MessageState {
+    match channel.message(http, message).await {
+        Ok(_) => MessageState::Exists,
+        //  Unknown channel or message
+        Err(Error::Http(HttpError::UnsuccessfulRequest(ErrorResponse {
+            error:
+                DiscordJsonError {
+                    code: 10003 | 10008,
+                    ..
+                },
+            ..
+        }))) => MessageState::Missing,
+        Err(err) => {
+            metrics().discord_error(&err);
+            error!("Error checking giveaway message: {}", err);
+            MessageState::Unknown
+        }
+    }
+}
+
+//  Returns the giveaway, if it's still running and needs its tasks
+async fn check_giveaway(
+    guild: GuildId,
+    id: GiveawayId,
+    giveaway: Giveaway,
+    db: &Arc<Db>,
+    http: &MyHttpCache,
+    report: &mut Report,
+) -> anyhow::Result<Option<Giveaway>> {
+    //  Completed with the drawn winners by resume_tasks
+    if finishing(db, guild, id)?.is_some() {
+        return Ok(Some(giveaway));
+    }
+    let real: RealGiveaway = giveaway.clone().into();
+    match message_state(http, real.channel, real.message).await {
+        MessageState::Exists => {}
+        MessageState::Missing => {
+            report.dangling += 1;
+            giveaway_message_deleted(db, http, guild, real.channel, real.message).await?;
+            //  Unless it was reposted, it's archived now
+            return Ok(db_read(db, guild)?.giveaways.remove(&id));
+        }
+        MessageState::Unknown => report.unchecked += 1,
+    }
+    let Some(time) = real.time.filter(|time| *time <= Utc::now()) else {
+        return Ok(Some(giveaway));
+    };
+    report.overdue += 1;
+    //  Awaited instead of spawned, so a failure ends up in the report. An extended giveaway is
+    //  scheduled again by finish_task itself
+    finish_task(guild, id, time, db.clone(), http.clone()).await?;
+    if still_due(db, guild, id, time)? {
+        report.failed += 1;
+        if !drawn(db, guild, id)? {
+            let task = retry_finish(guild, id, time, db.clone(), http.clone());
+            db.spawn_for_guild(guild, task);
+        }
+    }
+    Ok(None)
+}
+
+//  Still running with the same end, so finishing it failed
+fn still_due(db: &Db, guild: GuildId, id: GiveawayId, time: DateTime<Utc>) -> anyhow::Result<bool> {
+    Ok(db_read(db, guild)?
+        .giveaways
+        .get(&id)
+        .is_some_and(|ga| ga.time == Some(time.timestamp())))
+}
+
+//  Once the winners are drawn, finishing it is retried by resume_finish
+fn drawn(db: &Db, guild: GuildId, id: GiveawayId) -> anyhow::Result<bool> {
+    Ok(finishing(db, guild, id)?.is_some())
+}
+
+//  Waits twice as long after every failure, until it's finished, the winners are drawn or it was
+//  changed some other way
+async fn retry_finish(
+    guild: GuildId,
+    id: GiveawayId,
+    time: DateTime<Utc>,
+    db: Arc<Db>,
+    http: MyHttpCache,
+) {
+    let mut delay = FIRST_RETRY;
+    loop {
+        tokio::time::sleep(delay).await;
+        if let Err(err) = finish_task(guild, id, time, db.clone(), http.clone()).await {
+            error!("Error finishing overdue giveaway {id}: {}", err);
+        }
+        let retry =
+            still_due(&db, guild, id, time).and_then(|due| Ok(due && !drawn(&db, guild, id)?));
+        match retry {
+            Ok(true) => delay = (delay * 2).min(MAX_RETRY),
+            Ok(false) => return,
+            Err(err) => error!("Error checking overdue giveaway {id}: {}", err),
+        }
+    }
+}
+
+//  Repairs the running giveaways first, then the tasks of everything else are resumed as usual
+async fn check_guild(
+    guild: GuildId,
+    mut state: GuildState,
+    db: &Arc<Db>,
+    http: &MyHttpCache,
+    report: &mut Report,
+) -> anyhow::Result<()> {
+    let giveaways = std::mem::take(&mut state.giveaways);
+    for (id, giveaway) in giveaways {
+        report.giveaways += 1;
+        let running = match check_giveaway(guild, id, giveaway.clone(), db, http, report).await {
+            Ok(running) => running,
+            Err(err) => {
+                metrics().discord_error(err.as_ref());
+                error!("Error checking giveaway {id}: {}", err);
+                report.errors += 1;
+                Some(giveaway)
+            }
+        };
+        if let Some(running) = running {
+            state.giveaways.insert(id, running);
+        }
+    }
+    resume_tasks(guild, state, db, http)
+}
+
+/// Checks that the messages of running giveaways still exist and finishes overdue ones, before
+/// the tasks of the guilds are resumed. Guilds are checked concurrently, so a slow one doesn't
+/// hold back the others
+pub async fn startup_check(guilds: Vec<(GuildId, GuildState)>, db: Arc<Db>, http: MyHttpCache) {
+    let (db, http) = (&db, &http);
+    let mut reports = stream::iter(guilds)
+        .map(|(guild, state)| async move {
+            let mut report = Report::default();
+            if let Err(err) = check_guild(guild, state, db, http, &mut report).await {
+                metrics().discord_error(err.as_ref());
+                error!("Error checking guild {guild}: {}", err);
+                report.errors += 1;
+            }
+            report
+        })
+        .buffer_unordered(CONCURRENT_GUILDS);
+    let mut report = Report::default();
+    while let Some(guild) = reports.next().await {
+        report.add(guild);
+    }
+    info!("{report}");
+}
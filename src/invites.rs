@@ -0,0 +1,194 @@
+use bincode::{Decode, Encode};
+use poise::serenity_prelude::{CacheHttp, GuildId, RichInvite, UserId};
+use redb::{ReadableTable, Table, TableDefinition, WriteTransaction};
+use std::collections::HashMap;
+
+use crate::{bc, storage::Redb};
+
+/// Who invited a member, keyed by guild and the invited member
+pub(crate) const INVITES_TABLE: TableDefinition<(u64, u64), u64> = TableDefinition::new("invites");
+/// How many members of [`INVITES_TABLE`] an inviter has, keyed by guild and inviter, so invite
+/// requirements don't scan every invited member
+pub(crate) const INVITED_COUNT_TABLE: TableDefinition<(u64, u64), u32> =
+    TableDefinition::new("invited_count");
+/// The last known uses of every invite of a guild, to find the one used by a new member
+pub(crate) const INVITE_USES_TABLE: TableDefinition<u64, bc::Bincode<InviteUses>> =
+    TableDefinition::new("invite_uses");
+
+#[derive(Debug, Default, Encode, Decode)]
+pub struct InviteUses {
+    /// Inviter and uses, keyed by invite code
+    pub codes: HashMap<String, (u64, u64)>,
+}
+
+impl From<Vec<RichInvite>> for InviteUses {
+    fn from(invites: Vec<RichInvite>) -> Self {
+        Self {
+            codes: invites
+                .into_iter()
+                .filter_map(|invite| {
+                    let inviter = invite.inviter?.id.get();
+                    Some((invite.code, (inviter, invite.uses)))
+                })
+                .collect(),
+        }
+    }
+}
+
+fn store_uses(db: &Redb, guild: GuildId, uses: InviteUses) -> anyhow::Result<()> {
+    let db = db.begin_write()?;
+    {
+        let mut table = db.open_table(INVITE_USES_TABLE)?;
+        table.insert(guild.get(), uses)?;
+    }
+    db.commit()?;
+    Ok(())
+}
+
+/// Listing invites needs the MANAGE_GUILD permission, without it nobody is tracked
+pub async fn refresh_invites(
+    db: &Redb,
+    guild: GuildId,
+    http: &impl CacheHttp,
+) -> anyhow::Result<()> {
+    let invites = guild.invites(http.http()).await?;
+    store_uses(db, guild, invites.into())
+}
+
+pub fn invite_created(
+    db: &Redb,
+    guild: GuildId,
+    code: String,
+    inviter: Option<UserId>,
+) -> anyhow::Result<()> {
+    let Some(inviter) = inviter else {
+        return Ok(());
+    };
+    let db = db.begin_write()?;
+    {
+        let mut table = db.open_table(INVITE_USES_TABLE)?;
+        let mut uses = table
+            .get(guild.get())?
+            .map(|v| v.value())
+            .unwrap_or_default();
+        uses.codes.insert(code, (inviter.get(), 0));
+        table.insert(guild.get(), uses)?;
+    }
+    db.commit()?;
+    Ok(())
+}
+
+//  The used invite is the one with more uses than before, or the one that vanished after its
+//  last use. If several changed at once, the member can't be attributed to anyone.
+pub async fn member_joined(
+    db: &Redb,
+    guild: GuildId,
+    member: UserId,
+    http: &impl CacheHttp,
+) -> anyhow::Result<()> {
+    let before = {
+        let db_read = db.begin_read()?;
+        let table = db_read.open_table(INVITE_USES_TABLE)?;
+        table
+            .get(guild.get())?
+            .map(|v| v.value())
+            .unwrap_or_default()
+    };
+    let after: InviteUses = guild.invites(http.http()).await?.into();
+    let mut candidates: Vec<u64> = after
+        .codes
+        .iter()
+        .filter(|(code, (_, uses))| {
+            before
+                .codes
+                .get(*code)
+                .is_none_or(|(_, before)| uses > before)
+                && *uses > 0
+        })
+        .map(|(_, (inviter, _))| *inviter)
+        .collect();
+    if candidates.is_empty() {
+        candidates = before
+            .codes
+            .iter()
+            .filter(|(code, _)| !after.codes.contains_key(*code))
+            .map(|(_, (inviter, _))| *inviter)
+            .collect();
+    }
+    store_uses(db, guild, after)?;
+    let [inviter] = candidates[..] else {
+        return Ok(());
+    };
+    if inviter == member.get() {
+        return Ok(());
+    }
+    let db = db.begin_write()?;
+    {
+        let mut table = db.open_table(INVITES_TABLE)?;
+        let mut counts = db.open_table(INVITED_COUNT_TABLE)?;
+        //  A member rejoining without being noticed leaving counted for the old inviter
+        if let Some(old) = table.insert((guild.get(), member.get()), inviter)? {
+            adjust_count(&mut counts, guild.get(), old.value(), -1)?;
+        }
+        adjust_count(&mut counts, guild.get(), inviter, 1)?;
+    }
+    db.commit()?;
+    Ok(())
+}
+
+/// Members, who left again, don't count for their inviter anymore
+pub fn member_left(db: &Redb, guild: GuildId, member: UserId) -> anyhow::Result<()> {
+    let db = db.begin_write()?;
+    {
+        let mut table = db.open_table(INVITES_TABLE)?;
+        if let Some(inviter) = table.remove((guild.get(), member.get()))? {
+            let mut counts = db.open_table(INVITED_COUNT_TABLE)?;
+            adjust_count(&mut counts, guild.get(), inviter.value(), -1)?;
+        }
+    }
+    db.commit()?;
+    Ok(())
+}
+
+fn adjust_count(
+    counts: &mut Table<(u64, u64), u32>,
+    guild: u64,
+    inviter: u64,
+    change: i64,
+) -> anyhow::Result<()> {
+    let key = (guild, inviter);
+    let count = counts.get(key)?.map_or(0, |v| v.value());
+    match u32::try_from(i64::from(count) + change).unwrap_or(0) {
+        0 => counts.remove(key)?,
+        count => counts.insert(key, count)?,
+    };
+    Ok(())
+}
+
+/// Rebuilds [`INVITED_COUNT_TABLE`] from [`INVITES_TABLE`], for every guild without one
+pub fn recount_invites(w: &WriteTransaction, guild: Option<u64>) -> anyhow::Result<()> {
+    let range = match guild {
+        Some(guild) => (guild, 0)..=(guild, u64::MAX),
+        None => (0, 0)..=(u64::MAX, u64::MAX),
+    };
+    let mut counts: HashMap<(u64, u64), u32> = HashMap::new();
+    for entry in w.open_table(INVITES_TABLE)?.range(range.clone())? {
+        let (key, inviter) = entry?;
+        *counts.entry((key.value().0, inviter.value())).or_default() += 1;
+    }
+    let mut table = w.open_table(INVITED_COUNT_TABLE)?;
+    table.retain_in(range, |_, _| false)?;
+    for (key, count) in counts {
+        table.insert(key, count)?;
+    }
+    Ok(())
+}
+
+/// Number of members invited by the user, that are still on the guild
+pub fn invited_count(db: &Redb, guild: GuildId, user: UserId) -> anyhow::Result<u32> {
+    let db_read = db.begin_read()?;
+    let table = db_read.open_table(INVITED_COUNT_TABLE)?;
+    Ok(table
+        .get((guild.get(), user.get()))?
+        .map_or(0, |v| v.value()))
+}
@@ -0,0 +1,147 @@
+use anyhow::Context as _;
+use bincode::{Decode, Encode};
+use chrono::Utc;
+use poise::{
+    Context, CreateReply, command,
+    serenity_prelude::{CreateAllowedMentions, GuildId, Mentionable, UserId},
+};
+use redb::{ReadableTable, TableDefinition, WriteTransaction};
+use std::{collections::HashSet, sync::Arc};
+
+use crate::{
+    bc,
+    locale::{language, t},
+    storage::{Db, Redb},
+};
+
+/// Giveaways a member has won, keyed by guild and user
+pub(crate) const WINS_TABLE: TableDefinition<(u64, u64), bc::Bincode<Wins>> =
+    TableDefinition::new("wins");
+
+const TOP_COUNT: usize = 10;
+
+#[derive(Debug, Default, Encode, Decode)]
+pub struct Wins {
+    /// Timestamp of every win
+    pub times: Vec<i64>,
+}
+
+pub fn record_wins(db: &Redb, guild: GuildId, winners: &[UserId]) -> anyhow::Result<()> {
+    let db = db.begin_write()?;
+    add_wins(&db, guild, winners)?;
+    db.commit()?;
+    Ok(())
+}
+
+/// Like [`record_wins`], within a transaction that changes more than the wins
+pub fn add_wins(w: &WriteTransaction, guild: GuildId, winners: &[UserId]) -> anyhow::Result<()> {
+    let now = Utc::now().timestamp();
+    let mut table = w.open_table(WINS_TABLE)?;
+    for winner in winners {
+        let key = (guild.get(), winner.get());
+        let mut wins = table.get(key)?.map(|v| v.value()).unwrap_or_default();
+        wins.times.push(now);
+        table.insert(key, wins)?;
+    }
+    Ok(())
+}
+
+/// Everyone who won a giveaway on this guild within the last number of days
+pub fn recent_winners(
+    db: &Redb,
+    guild: GuildId,
+    days: Option<u32>,
+) -> anyhow::Result<HashSet<UserId>> {
+    let Some(days) = days else {
+        return Ok(HashSet::new());
+    };
+    let since = Utc::now().timestamp() - i64::from(days) * 24 * 60 * 60;
+    let db_read = db.begin_read()?;
+    let table = db_read.open_table(WINS_TABLE)?;
+    let mut winners = HashSet::new();
+    for entry in table.range((guild.get(), 0)..=(guild.get(), u64::MAX))? {
+        let (key, wins) = entry?;
+        if wins.value().times.iter().any(|&time| time >= since) {
+            winners.insert(UserId::new(key.value().1));
+        }
+    }
+    Ok(winners)
+}
+
+pub fn last_win(db: &Redb, guild: GuildId, user: UserId) -> anyhow::Result<Option<i64>> {
+    let db_read = db.begin_read()?;
+    let table = db_read.open_table(WINS_TABLE)?;
+    Ok(table
+        .get((guild.get(), user.get()))?
+        .and_then(|v| v.value().times.into_iter().max()))
+}
+
+#[command(
+    slash_command,
+    guild_only,
+    subcommands("wins_top"),
+    subcommand_required
+)]
+pub async fn leaderboard(_ctx: Context<'_, Arc<Db>, anyhow::Error>) -> anyhow::Result<()> {
+    Ok(())
+}
+
+#[command(slash_command, guild_only, rename = "wins")]
+pub async fn wins_top(ctx: Context<'_, Arc<Db>, anyhow::Error>) -> anyhow::Result<()> {
+    let guild = ctx.guild_id().context("Not in a guild")?;
+    let mut wins: Vec<(u64, usize)> = {
+        let db_read = ctx.data().begin_read()?;
+        let table = db_read.open_table(WINS_TABLE)?;
+        table
+            .range((guild.get(), 0)..=(guild.get(), u64::MAX))?
+            .map(|entry| entry.map(|(key, wins)| (key.value().1, wins.value().times.len())))
+            .collect::<Result<_, _>>()?
+    };
+    wins.sort_by_key(|&(user, wins)| (std::cmp::Reverse(wins), user));
+    let language = language(ctx);
+    let content = match wins.is_empty() {
+        true => t!(language, "leaderboard-empty"),
+        false => {
+            let mut content = t!(language, "leaderboard-header");
+            for (i, (user, wins)) in wins.into_iter().take(TOP_COUNT).enumerate() {
+                content.push_str(&format!("\n{}. <@{user}>: {wins}", i + 1));
+            }
+            content
+        }
+    };
+    ctx.send(
+        CreateReply::default()
+            .content(content)
+            .reply(true)
+            //  Showing the leaderboard shouldn't ping everyone on it
+            .allowed_mentions(CreateAllowedMentions::new()),
+    )
+    .await?;
+    Ok(())
+}
+
+#[command(slash_command, guild_only)]
+pub async fn wins(ctx: Context<'_, Arc<Db>, anyhow::Error>, user: UserId) -> anyhow::Result<()> {
+    let guild = ctx.guild_id().context("Not in a guild")?;
+    let wins = {
+        let db_read = ctx.data().begin_read()?;
+        let table = db_read.open_table(WINS_TABLE)?;
+        table
+            .get((guild.get(), user.get()))?
+            .map(|v| v.value().times.len())
+            .unwrap_or(0)
+    };
+    ctx.send(
+        CreateReply::default()
+            .content(t!(
+                language(ctx),
+                "leaderboard-wins",
+                user = user.mention(),
+                wins
+            ))
+            .reply(true)
+            .allowed_mentions(CreateAllowedMentions::new()),
+    )
+    .await?;
+    Ok(())
+}
@@ -0,0 +1,403 @@
+use bincode::{Decode, Encode, config, encode_to_vec};
+use log::{info, warn};
+use redb::{ReadableTable, TableDefinition, WriteTransaction};
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::Debug,
+};
+
+use crate::{
+    audit::{Actor, AuditEntry},
+    bc::{Raw, decode_exact},
+    finishing::{Finishing, Step},
+    migrations::{RAW_GUILDS_TABLE, convert_guilds},
+    storage::{ARCHIVE_TABLE, GIVEAWAYS_TABLE, PARTICIPANTS_TABLE, update_guild},
+    structs::{
+        AltCheck, ArchivedGiveaway, Buttons, Draft, Draw, Entry, Giveaway, GiveawayId, GuildState,
+        Language, Modlog, Prizes, Requirements, Settings, Slot, Style,
+    },
+};
+
+/// The layout used before bonus entries were added
+#[derive(Debug, Encode, Decode)]
+struct GuildStateV0 {
+    timezone: String,
+    giveaways: HashMap<GiveawayId, GiveawayV0>,
+}
+
+#[derive(Debug, Encode, Decode)]
+struct GiveawayV0 {
+    title: String,
+    description: String,
+    participants: HashSet<u64>,
+    winners: u32,
+    channel: u64,
+    message: u64,
+    time: Option<i64>,
+}
+
+impl From<GiveawayV0> for Giveaway {
+    fn from(value: GiveawayV0) -> Self {
+        Giveaway {
+            title: value.title,
+            description: value.description,
+            participants: value
+                .participants
+                .into_iter()
+                //  The entry time wasn't recorded back then
+                .map(|user| {
+                    (
+                        user,
+                        Entry {
+                            tickets: 1,
+                            entered: 0,
+                        },
+                    )
+                })
+                .collect(),
+            prizes: Prizes::unnamed(value.winners),
+            channel: value.channel,
+            message: value.message,
+            time: value.time,
+            requirements: Requirements::default(),
+            style: Style::default(),
+            created: None,
+            claim_hours: 0,
+            host: None,
+            notify_losers: false,
+            auto_extend: None,
+            winner_role: None,
+            secret_codes: Vec::new(),
+            event: None,
+            question: None,
+            mirrors: Vec::new(),
+            commitment: None,
+        }
+    }
+}
+
+impl From<GuildStateV0> for GuildState {
+    fn from(value: GuildStateV0) -> Self {
+        GuildState {
+            timezone: value.timezone,
+            giveaways: value
+                .giveaways
+                .into_iter()
+                .map(|(id, giveaway)| (id, giveaway.into()))
+                .collect(),
+            ..Default::default()
+        }
+    }
+}
+
+/// Migration from version 0, when giveaways had no bonus entries yet
+pub fn migrate_v0(w: &WriteTransaction) -> anyhow::Result<()> {
+    let migrated = convert_guilds(w, |state: GuildStateV0| state.into())?;
+    if migrated > 0 {
+        info!("Migrated {migrated} guilds to the current layout");
+    }
+    Ok(())
+}
+
+/// The layout used before the archive retention was configurable
+#[derive(Debug, Encode, Decode)]
+struct GuildStateV1 {
+    timezone: String,
+    plain_text: bool,
+    giveaways: HashMap<GiveawayId, Giveaway>,
+    archive: HashMap<GiveawayId, ArchivedGiveawayV4>,
+    drafts: HashMap<GiveawayId, Draft>,
+    settings: SettingsV1,
+}
+
+#[derive(Debug, Encode, Decode)]
+struct SettingsV1 {
+    winners: Option<u32>,
+    duration: Option<i64>,
+    role: Option<u64>,
+    announcement_channel: Option<u64>,
+    language: Language,
+    color: Option<u32>,
+    ping_role: Option<u64>,
+    reminder: Option<u32>,
+    reminder_ping: bool,
+    countdown: bool,
+    max_giveaways: Option<u32>,
+    repost_deleted: bool,
+    buttons: Buttons,
+    alt_check: Option<AltCheck>,
+    modlog: Option<Modlog>,
+    default_time: Option<u32>,
+}
+
+impl From<GuildStateV1> for GuildState {
+    fn from(value: GuildStateV1) -> Self {
+        let settings = value.settings;
+        GuildState {
+            timezone: value.timezone,
+            plain_text: value.plain_text,
+            giveaways: value.giveaways,
+            archive: convert_archive(value.archive),
+            drafts: value.drafts,
+            settings: Settings {
+                winners: settings.winners,
+                duration: settings.duration,
+                role: settings.role,
+                announcement_channel: settings.announcement_channel,
+                language: settings.language,
+                color: settings.color,
+                ping_role: settings.ping_role,
+                reminder: settings.reminder,
+                reminder_ping: settings.reminder_ping,
+                countdown: settings.countdown,
+                max_giveaways: settings.max_giveaways,
+                repost_deleted: settings.repost_deleted,
+                buttons: settings.buttons,
+                alt_check: settings.alt_check,
+                modlog: settings.modlog,
+                default_time: settings.default_time,
+                archive_days: None,
+            },
+        }
+    }
+}
+
+/// Migration from version 1, before the archive retention setting
+pub fn migrate_v1(w: &WriteTransaction) -> anyhow::Result<()> {
+    let migrated = convert_guilds(w, |state: GuildStateV1| state.into())?;
+    if migrated > 0 {
+        info!("Migrated {migrated} guilds to the current layout");
+    }
+    Ok(())
+}
+
+/// Migration from version 2, when every guild was stored as one value in the guilds table
+pub fn migrate_v2(w: &WriteTransaction) -> anyhow::Result<()> {
+    let mut guilds = Vec::new();
+    let mut skipped = 0;
+    {
+        let table = w.open_table(RAW_GUILDS_TABLE)?;
+        for entry in table.iter()? {
+            let (guild, data) = entry?;
+            let data = data.value();
+            let state = decode_exact::<GuildState>(data)
+                .or_else(|| decode_exact::<GuildStateV4>(data).map(Into::into));
+            match state {
+                Some(state) => guilds.push((guild.value(), state)),
+                None => skipped += 1,
+            }
+        }
+    }
+    let count = guilds.len();
+    for (guild, state) in guilds {
+        let mut state = Some(state);
+        update_guild(w, guild, &mut |stored| {
+            if let Some(state) = state.take() {
+                *stored = state;
+            }
+        })?;
+    }
+    if count > 0 {
+        info!("Moved {count} guilds into separate tables");
+    }
+    //  Guilds that couldn't be read are left behind instead of being lost
+    if skipped == 0 {
+        w.delete_table(RAW_GUILDS_TABLE)?;
+    } else {
+        warn!("Kept {skipped} guilds in an unknown layout in the guilds table");
+    }
+    Ok(())
+}
+
+/// Migration from version 3, when the participants were stored inside their giveaway
+pub fn migrate_v3(w: &WriteTransaction) -> anyhow::Result<()> {
+    let mut giveaways = Vec::new();
+    {
+        let table = w.open_table(GIVEAWAYS_TABLE)?;
+        for entry in table.iter()? {
+            let (key, data) = entry?;
+            //  Giveaways in an unknown layout are skipped when read anyway
+            if let Some(giveaway) = decode_exact::<Giveaway>(data.value())
+                && !giveaway.participants.is_empty()
+            {
+                giveaways.push((key.value(), giveaway));
+            }
+        }
+    }
+    let count = giveaways.len();
+    let mut giveaways_table = w.open_table(GIVEAWAYS_TABLE)?;
+    let mut participants_table = w.open_table(PARTICIPANTS_TABLE)?;
+    for ((guild, id), mut giveaway) in giveaways {
+        for (user, entry) in std::mem::take(&mut giveaway.participants) {
+            let data = encode_to_vec(entry, config::standard())?;
+            participants_table.insert((guild, id, user), data.as_slice())?;
+        }
+        let data = encode_to_vec(&giveaway, config::standard())?;
+        giveaways_table.insert((guild, id), data.as_slice())?;
+    }
+    if count > 0 {
+        info!("Moved the participants of {count} giveaways into their own table");
+    }
+    Ok(())
+}
+
+/// The draw before erased participants could be replaced by a tombstone
+#[derive(Debug, Encode, Decode)]
+struct DrawV4 {
+    seed: u64,
+    hash: String,
+    excluded: Vec<u64>,
+}
+
+impl From<DrawV4> for Draw {
+    fn from(value: DrawV4) -> Self {
+        Draw {
+            seed: value.seed,
+            hash: value.hash,
+            excluded: value.excluded,
+            salt: None,
+            erased: Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, Encode, Decode)]
+struct ArchivedGiveawayV4 {
+    giveaway: Giveaway,
+    winners: Vec<u64>,
+    ended: i64,
+    announcement: Option<(u64, u64)>,
+    slots: Vec<Slot>,
+    cancelled: bool,
+    cancel_reason: Option<String>,
+    draw: Option<DrawV4>,
+    role_grants: Vec<(u64, i64)>,
+}
+
+impl From<ArchivedGiveawayV4> for ArchivedGiveaway {
+    fn from(value: ArchivedGiveawayV4) -> Self {
+        ArchivedGiveaway {
+            giveaway: value.giveaway,
+            winners: value.winners,
+            ended: value.ended,
+            announcement: value.announcement,
+            slots: value.slots,
+            cancelled: value.cancelled,
+            cancel_reason: value.cancel_reason,
+            draw: value.draw.map(Into::into),
+            role_grants: value.role_grants,
+        }
+    }
+}
+
+fn convert_archive(
+    archive: HashMap<GiveawayId, ArchivedGiveawayV4>,
+) -> HashMap<GiveawayId, ArchivedGiveaway> {
+    archive
+        .into_iter()
+        .map(|(id, archived)| (id, archived.into()))
+        .collect()
+}
+
+/// The layout of a guild in SQL storage until version 4
+#[derive(Debug, Encode, Decode)]
+struct GuildStateV4 {
+    timezone: String,
+    plain_text: bool,
+    giveaways: HashMap<GiveawayId, Giveaway>,
+    archive: HashMap<GiveawayId, ArchivedGiveawayV4>,
+    drafts: HashMap<GiveawayId, Draft>,
+    settings: Settings,
+}
+
+impl From<GuildStateV4> for GuildState {
+    fn from(value: GuildStateV4) -> Self {
+        GuildState {
+            timezone: value.timezone,
+            plain_text: value.plain_text,
+            giveaways: value.giveaways,
+            archive: convert_archive(value.archive),
+            drafts: value.drafts,
+            settings: value.settings,
+        }
+    }
+}
+
+#[derive(Debug, Encode, Decode)]
+struct FinishingV4 {
+    drawn_winners: Vec<u64>,
+    draw: DrawV4,
+    done: Vec<Step>,
+}
+
+#[derive(Debug, Encode, Decode)]
+struct AuditEntryV4 {
+    time: i64,
+    actor: Option<u64>,
+    action: String,
+    details: String,
+}
+
+const RAW_FINISHING_TABLE: TableDefinition<(u64, u64), Raw<Finishing>> =
+    TableDefinition::new("finishing");
+const RAW_AUDIT_TABLE: TableDefinition<(u64, u64), Raw<AuditEntry>> = TableDefinition::new("audit");
+
+/// Rewrites every value of a table stored in the layout `Old`
+fn convert_table<Old, New>(
+    w: &WriteTransaction,
+    definition: TableDefinition<(u64, u64), Raw<New>>,
+    convert: impl Fn(Old) -> New,
+) -> anyhow::Result<()>
+where
+    Old: Decode<()>,
+    New: Debug + Encode,
+{
+    let mut converted = Vec::new();
+    {
+        let table = w.open_table(definition)?;
+        for entry in table.iter()? {
+            let (key, data) = entry?;
+            match decode_exact::<Old>(data.value()) {
+                Some(old) => converted.push((key.value(), convert(old))),
+                None => warn!(
+                    "Unknown layout in {definition} for {:?}, skipping",
+                    key.value()
+                ),
+            }
+        }
+    }
+    let mut table = w.open_table(definition)?;
+    for (key, new) in converted {
+        let data = encode_to_vec(&new, config::standard())?;
+        table.insert(key, data.as_slice())?;
+    }
+    Ok(())
+}
+
+/// Migration from version 4, before draws had a salt and the audit log knew erased members
+pub fn migrate_v4(w: &WriteTransaction) -> anyhow::Result<()> {
+    convert_table(w, ARCHIVE_TABLE, |old: ArchivedGiveawayV4| {
+        ArchivedGiveaway::from(old)
+    })?;
+    convert_table(w, RAW_FINISHING_TABLE, |old: FinishingV4| Finishing {
+        drawn_winners: old.drawn_winners,
+        draw: old.draw.into(),
+        done: old.done,
+    })?;
+    convert_table(w, RAW_AUDIT_TABLE, |old: AuditEntryV4| AuditEntry {
+        time: old.time,
+        actor: old.actor.map_or(Actor::Bot, Actor::Member),
+        action: old.action,
+        details: old.details,
+    })?;
+    Ok(())
+}
+
+/// Decodes a guild state stored in the current or any older layout, for storage that isn't migrated
+#[cfg(feature = "sql")]
+pub fn decode_guild(data: &[u8]) -> Option<GuildState> {
+    decode_exact::<GuildState>(data)
+        .or_else(|| decode_exact::<GuildStateV4>(data).map(Into::into))
+        .or_else(|| decode_exact::<GuildStateV1>(data).map(Into::into))
+        .or_else(|| decode_exact::<GuildStateV0>(data).map(Into::into))
+}
@@ -0,0 +1,190 @@
+use log::warn;
+use poise::{Context, serenity_prelude::GuildId};
+use std::{
+    collections::{BTreeSet, HashMap},
+    fmt::{self, Display},
+    sync::{Arc, LazyLock},
+};
+
+use crate::{datetime::TimeError, storage::Db, structs::Language};
+
+/// Every text the bot sends, `{name}` is replaced by the argument of the same name
+static GERMAN: LazyLock<HashMap<String, String>> = LazyLock::new(|| {
+    toml::from_str(include_str!("../locales/de.toml")).expect("Invalid German catalog")
+});
+static ENGLISH: LazyLock<HashMap<String, String>> = LazyLock::new(|| {
+    toml::from_str(include_str!("../locales/en.toml")).expect("Invalid English catalog")
+});
+
+fn catalog(language: Language) -> &'static HashMap<String, String> {
+    match language {
+        Language::German => &GERMAN,
+        Language::English => &ENGLISH,
+    }
+}
+
+/// Looks up a text in the catalog of the language, missing ones fall back to German
+pub fn text(language: Language, key: &str, args: &[(&str, &dyn Display)]) -> String {
+    let Some(text) = catalog(language).get(key).or_else(|| GERMAN.get(key)) else {
+        warn!("Unknown text `{key}`");
+        return key.to_string();
+    };
+    fill(text, args)
+}
+
+/// The help of `/info`, too long for a line in the catalog
+pub fn info(language: Language, args: &[(&str, &dyn Display)]) -> String {
+    let text = match language {
+        Language::German => include_str!("../locales/info.de.txt"),
+        Language::English => include_str!("../locales/info.en.txt"),
+    };
+    fill(text, args)
+}
+
+fn fill(text: &str, args: &[(&str, &dyn Display)]) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find(['{', '}']) {
+        out.push_str(&rest[..start]);
+        rest = &rest[start..];
+        //  Doubled braces are literal ones
+        if rest.starts_with("{{") || rest.starts_with("}}") {
+            out.push_str(&rest[..1]);
+            rest = &rest[2..];
+            continue;
+        }
+        let arg = rest[1..].find('}').and_then(|end| {
+            let name = &rest[1..end + 1];
+            let (_, value) = args.iter().find(|(arg, _)| *arg == name)?;
+            Some((end + 2, value))
+        });
+        match arg {
+            Some((len, value)) => {
+                out.push_str(&value.to_string());
+                rest = &rest[len..];
+            }
+            None => {
+                out.push_str(&rest[..1]);
+                rest = &rest[1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+/// `t!(language, "key", name = value, other)` looks up the text of `key` and fills in the
+/// arguments, a lone name is passed as itself
+macro_rules! t {
+    (@value $name:ident = $value:expr) => {
+        $value
+    };
+    (@value $name:ident) => {
+        $name
+    };
+    //  Bound first, so the arguments are dropped before an `.await` on the text
+    ($language:expr, $key:literal $(, $name:ident $(= $value:expr)?)* $(,)?) => {{
+        let text = $crate::locale::text(
+            $language,
+            $key,
+            &[$((
+                stringify!($name),
+                &($crate::locale::t!(@value $name $(= $value)?)) as &dyn std::fmt::Display,
+            )),*],
+        );
+        text
+    }};
+}
+pub(crate) use t;
+
+/// Like [`t!`], but for an error that is translated once the language of the reply is known
+macro_rules! localized {
+    ($key:literal $(, $name:ident $(= $value:expr)?)* $(,)?) => {
+        $crate::locale::Localized::new(
+            $key,
+            vec![$((
+                stringify!($name),
+                ($crate::locale::t!(@value $name $(= $value)?)).to_string(),
+            )),*],
+        )
+    };
+}
+pub(crate) use localized;
+
+/// An error meant for the user, displayed in English for the logs
+#[derive(Debug)]
+pub struct Localized {
+    key: &'static str,
+    args: Vec<(&'static str, String)>,
+}
+
+impl Localized {
+    pub fn new(key: &'static str, args: Vec<(&'static str, String)>) -> Self {
+        Self { key, args }
+    }
+
+    pub fn text(&self, language: Language) -> String {
+        let args: Vec<_> = self
+            .args
+            .iter()
+            .map(|(name, value)| (*name, value as &dyn Display))
+            .collect();
+        text(language, self.key, &args)
+    }
+}
+
+impl Display for Localized {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.text(Language::English))
+    }
+}
+
+impl std::error::Error for Localized {}
+
+/// The message for the user, errors of the bot itself are shown untranslated
+pub fn error_text(err: &anyhow::Error, language: Language) -> String {
+    if let Some(err) = err.downcast_ref::<Localized>() {
+        err.text(language)
+    } else if let Some(err) = err.downcast_ref::<TimeError>() {
+        err.text(language)
+    } else {
+        err.to_string()
+    }
+}
+
+impl Language {
+    /// Discord's locale of a user, for replies outside of guilds
+    pub fn from_locale(locale: &str) -> Self {
+        match locale.starts_with("de") {
+            true => Language::German,
+            false => Language::English,
+        }
+    }
+}
+
+/// The language set with `/settings language`, or the user's own in direct messages
+pub fn language_of(db: &Db, guild: Option<GuildId>, locale: &str) -> Language {
+    match guild {
+        Some(guild) => db
+            .settings(guild)
+            .map(|cached| cached.settings.language)
+            .unwrap_or_default(),
+        None => Language::from_locale(locale),
+    }
+}
+
+pub fn language(ctx: Context<'_, Arc<Db>, anyhow::Error>) -> Language {
+    language_of(ctx.data(), ctx.guild_id(), ctx.locale().unwrap_or_default())
+}
+
+/// Loads the catalogs and warns about texts that aren't translated
+pub fn check() {
+    let german: BTreeSet<_> = GERMAN.keys().collect();
+    let english: BTreeSet<_> = ENGLISH.keys().collect();
+    for key in german.difference(&english) {
+        warn!("No English text for `{key}`");
+    }
+    for key in english.difference(&german) {
+        warn!("No German text for `{key}`");
+    }
+}
@@ -0,0 +1,215 @@
+use anyhow::Context as _;
+use chrono::{NaiveDate, TimeDelta, Utc};
+use log::{Level, LevelFilter, Log, Metadata, Record};
+use std::{
+    backtrace::{Backtrace, BacktraceStatus},
+    fs::{File, OpenOptions},
+    io::Write,
+    path::PathBuf,
+    sync::{Mutex, OnceLock, PoisonError, TryLockError},
+};
+
+const CRATE: &str = env!("CARGO_CRATE_NAME");
+const PREFIX: &str = "do-bot-";
+
+//  Shared with the panic hook, which mustn't wait for it
+static FILE: OnceLock<Mutex<LogFile>> = OnceLock::new();
+
+/// Where and how long log files are kept, from the `log_*` settings of the config file
+#[derive(Debug)]
+pub struct LogConfig {
+    pub dir: PathBuf,
+    /// Another file is started for the day once the current one is this big
+    pub max_bytes: u64,
+    /// Files of older days are deleted
+    pub keep_days: u32,
+}
+
+/// One file per day, `do-bot-2025-01-31.log`, continued in `do-bot-2025-01-31.1.log` and so on
+struct LogFile {
+    config: LogConfig,
+    file: Option<File>,
+    day: NaiveDate,
+    part: u32,
+    size: u64,
+}
+
+impl LogFile {
+    fn path(&self) -> PathBuf {
+        let name = match self.part {
+            0 => format!("{PREFIX}{}.log", self.day),
+            part => format!("{PREFIX}{}.{part}.log", self.day),
+        };
+        self.config.dir.join(name)
+    }
+
+    fn write(&mut self, line: &str) -> anyhow::Result<()> {
+        let today = Utc::now().date_naive();
+        if today != self.day {
+            self.day = today;
+            self.part = 0;
+            self.file = None;
+            //  Old files staying around a bit longer is no reason to lose the line
+            if let Err(err) = self.prune() {
+                eprintln!("Error pruning log files: {}", err);
+            }
+        }
+        if self.file.is_some() && !self.fits(line) {
+            self.part += 1;
+            self.file = None;
+        }
+        let file = match &mut self.file {
+            Some(file) => file,
+            None => loop {
+                let file = OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(self.path())?;
+                self.size = file.metadata()?.len();
+                if self.fits(line) {
+                    break self.file.insert(file);
+                }
+                self.part += 1;
+            },
+        };
+        file.write_all(line.as_bytes())?;
+        self.size += line.len() as u64;
+        Ok(())
+    }
+
+    //  A line longer than the limit gets a file of its own
+    fn fits(&self, line: &str) -> bool {
+        self.size == 0 || self.size + line.len() as u64 <= self.config.max_bytes
+    }
+
+    //  Continues after a restart with the last file of the day
+    fn last_part(&self) -> anyhow::Result<u32> {
+        let prefix = format!("{PREFIX}{}.", self.day);
+        let mut last = 0;
+        for entry in std::fs::read_dir(&self.config.dir)? {
+            let name = entry?.file_name();
+            if let Some(part) = name
+                .to_str()
+                .and_then(|name| name.strip_prefix(&prefix))
+                .and_then(|name| name.strip_suffix(".log"))
+                .and_then(|part| part.parse().ok())
+            {
+                last = last.max(part);
+            }
+        }
+        Ok(last)
+    }
+
+    fn prune(&self) -> anyhow::Result<()> {
+        let oldest = self.day - TimeDelta::days(i64::from(self.config.keep_days));
+        for entry in std::fs::read_dir(&self.config.dir)? {
+            let entry = entry?;
+            let name = entry.file_name();
+            let Some(day) = name
+                .to_str()
+                .and_then(|name| name.strip_prefix(PREFIX))
+                .and_then(|name| name.get(..10))
+                .and_then(|day| NaiveDate::parse_from_str(day, "%Y-%m-%d").ok())
+            else {
+                continue;
+            };
+            if day < oldest {
+                std::fs::remove_file(entry.path())?;
+            }
+        }
+        Ok(())
+    }
+}
+
+struct Logger;
+
+impl Log for Logger {
+    //  Other crates, e.g. serenity, only with warnings and errors
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        match metadata.target().starts_with(CRATE) {
+            true => metadata.level() <= Level::Info,
+            false => metadata.level() <= Level::Warn,
+        }
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        let message = match record.target().starts_with(CRATE) {
+            true => record.args().to_string(),
+            false => format!("{}: {}", record.target(), record.args()),
+        };
+        match record.level() {
+            Level::Error | Level::Warn => eprintln!("{message}"),
+            _ => println!("{message}"),
+        }
+        if let Some(file) = FILE.get() {
+            //  A panic while writing poisons the file, it's still usable afterwards
+            write_line(
+                &mut file.lock().unwrap_or_else(PoisonError::into_inner),
+                record.level(),
+                &message,
+            );
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+fn write_line(file: &mut LogFile, level: Level, message: &str) {
+    let line = format!(
+        "{} {level:<5} {message}\n",
+        Utc::now().format("%Y-%m-%dT%H:%M:%SZ"),
+    );
+    //  There is nowhere else to log this to
+    if let Err(err) = file.write(&line) {
+        eprintln!("Error writing log file: {}", err);
+    }
+}
+
+//  The panic might have happened while this thread was writing the log file, waiting for it would
+//  never end. Then the panic only goes to stderr.
+fn log_panic(message: &str) {
+    eprintln!("{message}");
+    let Some(file) = FILE.get() else {
+        return;
+    };
+    match file.try_lock() {
+        Ok(mut file) => write_line(&mut file, Level::Error, message),
+        Err(TryLockError::Poisoned(file)) => {
+            write_line(&mut file.into_inner(), Level::Error, message)
+        }
+        Err(TryLockError::WouldBlock) => eprintln!("Log file busy, the panic isn't in it"),
+    }
+}
+
+/// Logs to the console and, if configured, to files. Panics are logged as well, so they also end
+/// up in the files
+pub fn init(config: Option<LogConfig>) -> anyhow::Result<()> {
+    if let Some(config) = config {
+        std::fs::create_dir_all(&config.dir).with_context(|| {
+            format!("Couldn't create the log directory {}", config.dir.display())
+        })?;
+        let mut file = LogFile {
+            config,
+            file: None,
+            day: Utc::now().date_naive(),
+            part: 0,
+            size: 0,
+        };
+        file.prune()?;
+        file.part = file.last_part()?;
+        let _ = FILE.set(Mutex::new(file));
+    }
+    log::set_boxed_logger(Box::new(Logger))?;
+    log::set_max_level(LevelFilter::Info);
+    std::panic::set_hook(Box::new(|info| {
+        let backtrace = Backtrace::capture();
+        match backtrace.status() {
+            BacktraceStatus::Captured => log_panic(&format!("{info}\n{backtrace}")),
+            _ => log_panic(&info.to_string()),
+        }
+    }));
+    Ok(())
+}
@@ -1,513 +1,3203 @@
+use activity::{ACTIVITY_TABLE, activity_task, get_activity};
+use admin::{admin, configured_owners};
 use anyhow::Context as _;
-use chrono::{DateTime, Utc};
+use audit::{AUDIT_TABLE, audit, audit_page, record, record_command};
+use chrono::{DateTime, NaiveTime, TimeDelta, Utc};
 use chrono_tz::Tz;
-use clear::{clear, clear_all, clear_channel, clear_user};
-use datetime::parse_time;
+use clear::{
+    CLEAR_JOBS_TABLE, Progress, clear, clear_all, clear_filter, clear_menu, clear_messages,
+    clear_reactions, clear_role, log_clear, may_clear, modlog, purge, resume_clears,
+    start_clear_job, stop_button, take_clear_job,
+};
+use codes::{assign_codes, seal, send_code, send_codes};
+use config::{Args, Command, Config};
+use datetime::{
+    Grammar, ParsedTime, TimeError, dst_hint, format_duration, parse_duration, parse_time,
+    parse_time_range,
+};
+use departed::{DEPARTED_TABLE, record_departure, record_return};
+use draw::{commit, fair_draw, new_salt, reveal};
+use entries::entry_task;
+use events::{create_event, end_event, move_event_end, start_event};
+use export::export;
+use finishing::{
+    FINISHING_TABLE, Finishing, Step, complete_step, finishing, mark_finishing, record_wins_once,
+    unmark_finishing,
+};
+use giveaway::{cancel, extend, find_giveaway, giveaway, grant_entries, reroll, verify};
+use import::import;
+use integrity::startup_check;
+use invites::{
+    INVITE_USES_TABLE, INVITED_COUNT_TABLE, INVITES_TABLE, invite_created, invited_count,
+    member_joined, member_left, refresh_invites,
+};
+use leaderboard::{WINS_TABLE, last_win, leaderboard, recent_winners, record_wins, wins};
+use locale::{error_text, language, language_of, localized, t};
+use log::{error, info};
+use maintenance::{compact, maintenance_task, prune_task};
+use metrics::{bind_metrics, count_command, metrics, serve_metrics};
+use migrations::SCHEMA_VERSION;
+use mydata::{erase_user, mydata};
+use participants::{may_view, participant_page, participants};
 use poise::{
     Context, CreateReply,
     serenity_prelude::{
-        CacheHttp, ClientBuilder, ComponentInteraction, ComponentInteractionData,
-        ComponentInteractionDataKind, CreateActionRow, CreateButton, CreateInteractionResponse,
-        CreateInteractionResponseFollowup, CreateInteractionResponseMessage, CreateMessage,
+        ActionRowComponent, AutocompleteChoice, ButtonStyle, CacheHttp, ChannelId, ClientBuilder,
+        ComponentInteraction, ComponentInteractionData, ComponentInteractionDataKind,
+        CreateActionRow, CreateAllowedMentions, CreateButton, CreateInputText,
+        CreateInteractionResponse, CreateInteractionResponseFollowup, CreateMessage, CreateModal,
         DiscordJsonError, EditInteractionResponse, EditMessage, ErrorResponse, FullEvent,
-        GatewayIntents, GuildId, Interaction, UserId,
+        GatewayError, GatewayIntents, GuildChannel, GuildId, InputTextStyle, Interaction, Member,
+        Mentionable, MessageId, ModalInteraction, Permissions, Reaction, ReactionType, RoleId,
+        UserId, parse_channel_mention, parse_role_mention, parse_user_mention,
     },
 };
-use rand::seq::IteratorRandom;
-use redb::{Database, ReadableTable, TableDefinition};
-use std::{cmp::min, collections::HashSet, sync::Arc, time::Duration};
-use structs::{Giveaway, GiveawayId, GuildState, MyHttpCache, RealGiveaway, UserAction};
+use rand::distr::{Distribution, weighted::WeightedIndex};
+use redb::Database;
+use settings::{parse_color, settings};
+use stats::stats;
+use std::{
+    collections::{HashMap, HashSet},
+    num::NonZeroU64,
+    sync::Arc,
+    time::Duration,
+};
+use storage::{
+    ARCHIVE_TABLE, CONFIG_TABLE, CachedSettings, DRAFTS_TABLE, Db, GIVEAWAYS_TABLE,
+    PARTICIPANTS_TABLE,
+};
+use structs::{
+    AltAction, AltCheck, ArchivedGiveaway, AutoExtend, CustomButton, Draft, Draw, ENTRY_EMOJI,
+    Entry, EntryQuestion, Giveaway, GiveawayButton, GiveawayId, GiveawayMessage, GuildState,
+    Language, MyHttpCache, Prizes, RealGiveaway, Requirements, Slot, Style, UserAction, WinnerRole,
+};
 
+mod activity;
+mod admin;
+mod audit;
 #[path = "bincode.rs"]
 mod bc;
 mod clear;
+mod codes;
+mod config;
+mod crypto;
 mod datetime;
+mod departed;
+mod draw;
+mod entries;
+mod events;
+mod export;
+mod finishing;
+mod giveaway;
+mod import;
+mod integrity;
+mod invites;
+mod leaderboard;
+mod legacy;
+mod locale;
+mod logging;
+mod maintenance;
+mod metrics;
+mod migrations;
+mod mydata;
+mod participants;
+mod settings;
+mod stats;
+mod storage;
 mod structs;
 
-pub(crate) const TOKEN: &str = include_str!("../token");
-pub(crate) const DATABASE_PATH: &str = "db.redb";
-pub(crate) const TABLE: TableDefinition<u64, bc::Bincode<GuildState>> =
-    TableDefinition::new("guilds");
-
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
-    println!("Starting...");
-    let mut db = Database::create(DATABASE_PATH)?;
+    let args = Args::parse(std::env::args().skip(1))?;
+    let mut config = Config::load(args.config.as_deref())?;
+    //  Checked before anything else, so a missing token doesn't wait for the migrations. Offline
+    //  commands don't need one.
+    let token = match args.command {
+        Command::Run => Some(config.token()?),
+        Command::Export { .. } => None,
+    };
+    logging::init(config.log.take())?;
+    locale::check();
+    crypto::configure(config.secret_key.take(), &config.database_path)?;
+    let mut db = Database::create(&config.database_path)
+        .with_context(|| format!("Couldn't open the database {}", config.database_path))?;
     db.compact()?;
     {
         let w = db.begin_write()?;
-        let t = w.open_table(TABLE)?;
+        let t = w.open_table(CONFIG_TABLE)?;
+        drop(t);
+        let t = w.open_table(GIVEAWAYS_TABLE)?;
+        drop(t);
+        let t = w.open_table(ARCHIVE_TABLE)?;
+        drop(t);
+        let t = w.open_table(DRAFTS_TABLE)?;
+        drop(t);
+        let t = w.open_table(PARTICIPANTS_TABLE)?;
+        drop(t);
+        let t = w.open_table(ACTIVITY_TABLE)?;
+        drop(t);
+        let t = w.open_table(WINS_TABLE)?;
+        drop(t);
+        let t = w.open_table(INVITES_TABLE)?;
+        drop(t);
+        let t = w.open_table(INVITE_USES_TABLE)?;
+        drop(t);
+        let t = w.open_table(INVITED_COUNT_TABLE)?;
+        drop(t);
+        let t = w.open_table(CLEAR_JOBS_TABLE)?;
+        drop(t);
+        let t = w.open_table(AUDIT_TABLE)?;
+        drop(t);
+        let t = w.open_table(FINISHING_TABLE)?;
+        drop(t);
+        let t = w.open_table(DEPARTED_TABLE)?;
         drop(t);
         w.commit()?;
     }
+    let version = migrations::migrate(&db)?;
+    if version < SCHEMA_VERSION {
+        info!("Migrated the database from schema version {version} to {SCHEMA_VERSION}");
+    }
+    let db = Db::open(db, config.database_path, config.database_url).await?;
+    let token = match args.command {
+        Command::Export { guild } => return export::cli(&db, guild.as_deref()),
+        Command::Run => token.unwrap(),
+    };
+    info!("Starting...");
+    let metrics_listener = match config.metrics_addr {
+        Some(addr) => Some(bind_metrics(addr).await?),
+        None => None,
+    };
     let db = Arc::new(db);
-    dump_db(&db);
+    let shutdown_db = db.clone();
 
     let framework = poise::Framework::builder()
         .options(poise::FrameworkOptions {
-            commands: vec![create(), timezone(), info(), clear(), clear_all()],
+            commands: vec![
+                create(),
+                timezone(),
+                embeds(),
+                info(),
+                clear(),
+                clear_all(),
+                clear_filter(),
+                clear_role(),
+                clear_menu(),
+                purge(),
+                clear_reactions(),
+                giveaway(),
+                reroll(),
+                grant_entries(),
+                extend(),
+                cancel(),
+                verify(),
+                participants(),
+                leaderboard(),
+                wins(),
+                stats(),
+                settings(),
+                export(),
+                import(),
+                compact(),
+                audit(),
+                admin(),
+                mydata(),
+            ],
+            owners: configured_owners(),
+            post_command: |ctx| {
+                Box::pin(async move {
+                    count_command(ctx, true);
+                    record_command(ctx).await;
+                })
+            },
+            on_error: |error| Box::pin(on_error(error)),
             event_handler: |ctx, event, framework, data| {
                 Box::pin(event_handler(ctx, event, framework, data))
             },
             ..Default::default()
         })
-        .setup(|ctx, _ready, framework| {
+        .setup(|ctx, ready, framework| {
             Box::pin(async move {
                 poise::builtins::register_globally(ctx, &framework.options().commands).await?;
 
                 let http = MyHttpCache::new(ctx.http.clone(), ctx.cache.clone());
-                {
-                    let db_read = db.begin_read()?;
-                    let table = db_read.open_table(TABLE)?;
-                    let mut iter = table.iter()?;
-                    while let Some(Ok(guild)) = iter.next() {
-                        let guild_id = GuildId::from(guild.0.value());
-                        let guild = guild.1.value();
-                        for giveaway in guild.giveaways {
-                            let giveaway_id = giveaway.0;
-                            let giveaway: RealGiveaway = giveaway.1.into();
-                            let db = db.clone();
-                            let http = http.clone();
-                            if let Some(time) = giveaway.time {
-                                tokio::spawn(async move {
-                                    finish_task(guild_id, giveaway_id, time, db, http)
-                                        .await
-                                        .unwrap();
-                                });
-                            }
-                        }
+                let joined: HashSet<GuildId> = ready.guilds.iter().map(|guild| guild.id).collect();
+                let mut resumed = Vec::new();
+                for (guild_id, guild) in db.storage().guilds()? {
+                    //  Removed while the bot was offline, so no event told about it
+                    if !joined.contains(&guild_id) {
+                        record_departure(&db, guild_id)?;
+                        continue;
                     }
+                    record_return(&db, guild_id)?;
+                    resumed.push((guild_id, guild));
+                }
+                {
+                    let db = db.clone();
+                    let http = http.clone();
+                    tokio::spawn(async move {
+                        startup_check(resumed, db, http).await;
+                    });
+                }
+
+                {
+                    let db = db.clone();
+                    let http = http.clone();
+                    tokio::spawn(async move {
+                        resume_clears(db, http).await;
+                    });
+                }
+                {
+                    let db = db.clone();
+                    tokio::spawn(async move {
+                        maintenance_task(db).await;
+                    });
+                }
+                {
+                    let db = db.clone();
+                    let http = http.clone();
+                    tokio::spawn(async move {
+                        entry_task(db, http).await.unwrap();
+                    });
+                }
+                {
+                    let db = db.clone();
+                    tokio::spawn(async move {
+                        prune_task(db).await;
+                    });
+                }
+                {
+                    let db = db.clone();
+                    tokio::spawn(async move {
+                        activity_task(db).await;
+                    });
+                }
+                if let Some(listener) = metrics_listener {
+                    tokio::spawn(async move {
+                        serve_metrics(listener).await;
+                    });
                 }
 
-                println!("Prepared and connected to disord");
+                info!("Prepared and connected to disord");
                 Ok(db)
             })
         })
         .build();
-    let client = ClientBuilder::new(TOKEN, GatewayIntents::non_privileged())
-        .framework(framework)
-        .await;
-    client?.start().await?;
+    let client = ClientBuilder::new(
+        token,
+        //  Members leaving the server are only reported with this privileged intent
+        GatewayIntents::non_privileged() | GatewayIntents::GUILD_MEMBERS,
+    )
+    .framework(framework)
+    .await;
+    let mut client = client?;
+    tokio::select! {
+        result = client.start() => match result {
+            //  Discord only tells this by closing the connection, the error alone is cryptic
+            Err(poise::serenity_prelude::Error::Gateway(GatewayError::DisallowedGatewayIntents)) => {
+                anyhow::bail!(
+                    "Discord didn't allow the Server Members Intent, enable it for the bot in the \
+                    Developer Portal under Bot > Privileged Gateway Intents"
+                )
+            }
+            result => result?,
+        },
+        result = shutdown_signal() => {
+            result?;
+            //  Entries that were accepted and messages counted, but not written yet
+            shutdown_db.entries().flush_all(&shutdown_db)?;
+            shutdown_db.activity().flush(&shutdown_db)?;
+            info!("Stopped");
+        }
+    }
+
+    Ok(())
+}
 
+//  Ctrl+C, or SIGTERM from a service manager
+async fn shutdown_signal() -> anyhow::Result<()> {
+    #[cfg(unix)]
+    {
+        let mut terminate =
+            tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())?;
+        tokio::select! {
+            result = tokio::signal::ctrl_c() => result?,
+            _ = terminate.recv() => {}
+        }
+    }
+    #[cfg(not(unix))]
+    tokio::signal::ctrl_c().await?;
     Ok(())
 }
 
+/// Starts the tasks for drafts, running and archived giveaways of the state, after a restart or an import
+fn resume_tasks(
+    guild_id: GuildId,
+    guild: GuildState,
+    db: &Arc<Db>,
+    http: &MyHttpCache,
+) -> anyhow::Result<()> {
+    for (draft_id, draft) in guild.drafts {
+        //  Stored before imports were validated, a broken draft mustn't stop the others
+        let Some(start) = DateTime::from_timestamp(draft.start, 0) else {
+            error!("Skipping draft {draft_id} in {guild_id}, its start time is invalid");
+            continue;
+        };
+        let task = start_task(guild_id, draft_id, start, db.clone(), http.clone());
+        db.spawn_for_guild(guild_id, async move { task.await.unwrap() });
+    }
+    for (giveaway_id, archived) in guild.archive {
+        //  Archived, but the bot stopped before everything after it was done
+        if finishing(db, guild_id, giveaway_id)?.is_some() {
+            let task = complete_finish(guild_id, giveaway_id, db.clone(), http.clone());
+            db.spawn_for_guild(guild_id, async move {
+                if let Err(err) = task.await {
+                    error!("Error completing finished giveaway {giveaway_id}: {}", err);
+                }
+            });
+        }
+        if archived.next_deadline().is_some() {
+            let task = claim_task(guild_id, giveaway_id, db.clone(), http.clone());
+            db.spawn_for_guild(guild_id, async move { task.await.unwrap() });
+        }
+        if archived.next_role_removal().is_some() {
+            spawn_role_task(db, http, guild_id, giveaway_id);
+        }
+    }
+    for giveaway in guild.giveaways {
+        let giveaway_id = giveaway.0;
+        let giveaway: RealGiveaway = giveaway.1.into();
+        if finishing(db, guild_id, giveaway_id)?.is_some() {
+            let task = resume_finish(
+                guild_id,
+                giveaway_id,
+                Duration::ZERO,
+                db.clone(),
+                http.clone(),
+            );
+            db.spawn_for_guild(guild_id, async move { task.await.unwrap() });
+        } else if let Some(time) = giveaway.time {
+            schedule_end(guild_id, giveaway_id, time, db.clone(), http.clone());
+        }
+    }
+    Ok(())
+}
+
+//  Same as poise's default, but failed Discord requests are counted first
+async fn on_error(error: poise::FrameworkError<'_, Arc<Db>, anyhow::Error>) {
+    if let Some(ctx) = error.ctx() {
+        count_command(ctx, false);
+    }
+    match error {
+        //  Errors meant for the user are shown in the language of the guild
+        poise::FrameworkError::Command { error, ctx, .. } => {
+            metrics().discord_error(error.as_ref());
+            error!(
+                "Error in command /{}: {}",
+                ctx.command().qualified_name,
+                error
+            );
+            if let Err(err) = ctx.say(error_text(&error, language(ctx))).await {
+                error!("Error handling error: {}", err);
+            }
+        }
+        error => {
+            if let poise::FrameworkError::EventHandler { error, .. } = &error {
+                metrics().discord_error(error.as_ref());
+            }
+            if let Err(err) = poise::builtins::on_error(error).await {
+                error!("Error handling error: {}", err);
+            }
+        }
+    }
+}
+
 async fn event_handler(
     ctx: &poise::serenity_prelude::Context,
     event: &poise::serenity_prelude::FullEvent,
-    _framework: poise::FrameworkContext<'_, Arc<Database>, anyhow::Error>,
-    db: &Arc<Database>,
+    _framework: poise::FrameworkContext<'_, Arc<Db>, anyhow::Error>,
+    db: &Arc<Db>,
 ) -> anyhow::Result<()> {
     match event {
+        FullEvent::Message { new_message } => {
+            if let Some(guild) = new_message.guild_id
+                && !new_message.author.bot
+            {
+                db.activity().record(guild, new_message.author.id);
+            }
+        }
+        FullEvent::GuildCreate { guild, .. } => {
+            if record_return(db, guild.id)? {
+                let http = MyHttpCache::new(ctx.http.clone(), ctx.cache.clone());
+                resume_tasks(guild.id, db.storage().read(guild.id)?, db, &http)?;
+            }
+            if let Err(err) = refresh_invites(db, guild.id, &ctx).await {
+                metrics().discord_error(err.as_ref());
+                error!("Error loading invites: {}", err);
+            }
+        }
+        //  An unavailable guild is an outage, the bot is still on it
+        FullEvent::GuildDelete { incomplete, .. } if !incomplete.unavailable => {
+            record_departure(db, incomplete.id)?;
+            db.cancel_guild_tasks(incomplete.id);
+        }
+        FullEvent::InviteCreate { data } => {
+            if let Some(guild) = data.guild_id {
+                invite_created(
+                    db,
+                    guild,
+                    data.code.clone(),
+                    data.inviter.as_ref().map(|user| user.id),
+                )?;
+            }
+        }
+        FullEvent::GuildMemberAddition { new_member } => {
+            if !new_member.user.bot
+                && let Err(err) =
+                    member_joined(db, new_member.guild_id, new_member.user.id, &ctx).await
+            {
+                metrics().discord_error(err.as_ref());
+                error!("Error tracking invite: {}", err);
+            }
+        }
+        FullEvent::GuildMemberRemoval {
+            guild_id: guild,
+            user,
+            ..
+        } => {
+            member_left(db, *guild, user.id)?;
+            let _guard = db.lock_guild(*guild).await;
+            let user = user.id.get();
+            let changed: Vec<GiveawayId> = db_write(db, *guild, move |state| {
+                state
+                    .giveaways
+                    .iter_mut()
+                    .filter_map(|(id, giveaway)| giveaway.participants.remove(&user).map(|_| *id))
+                    .collect()
+            })?;
+            for id in changed {
+                update_message(*guild, id, db, &ctx).await?;
+            }
+        }
+        FullEvent::ReactionAdd {
+            add_reaction: reaction,
+        } => {
+            if let Some(guild) = reaction.guild_id
+                && let Some(member) = &reaction.member
+                && !member.user.bot
+                && reaction.emoji.unicode_eq(ENTRY_EMOJI)
+                && let Some(id) = reaction_giveaway(db, guild, reaction)?
+                && let Err(reason) = add_user(guild, id, member, db, &ctx).await?
+            {
+                //  The reaction would look like a successful entry
+                reaction.delete(&ctx).await?;
+                let link = reaction.message_id.link(reaction.channel_id, Some(guild));
+                let message = CreateMessage::new().content(format!("{link}\n{reason}"));
+                //  Members might not accept direct messages
+                if let Err(err) = member.user.id.direct_message(&ctx, message).await {
+                    metrics().discord_error(&err);
+                    error!("Error sending rejection: {}", err);
+                }
+            }
+        }
+        FullEvent::ReactionRemove {
+            removed_reaction: reaction,
+        } => {
+            if let Some(guild) = reaction.guild_id
+                && let Some(user) = reaction.user_id
+                && reaction.emoji.unicode_eq(ENTRY_EMOJI)
+                && let Some(id) = reaction_giveaway(db, guild, reaction)?
+            {
+                db.entries().remove(guild, id, user.get());
+            }
+        }
         FullEvent::MessageDelete {
             channel_id: channel,
             deleted_message_id: message,
             guild_id: Some(guild),
         } => {
-            let data: Option<(GiveawayId, RealGiveaway)> = db_write(db, *guild, move |state| {
-                state
-                    .giveaways
+            let http = MyHttpCache::new(ctx.http.clone(), ctx.cache.clone());
+            giveaway_message_deleted(db, &http, *guild, *channel, *message).await?;
+        }
+        FullEvent::InteractionCreate {
+            interaction: Interaction::Modal(interaction),
+        } => {
+            interaction.defer(&ctx).await?;
+            if let ModalInteraction {
+                guild_id: Some(guild),
+                member: Some(member),
+                data,
+                ..
+            } = &interaction
+            {
+                let action: UserAction = serde_json::from_str(&data.custom_id)?;
+                let language = language_of(db, Some(*guild), &interaction.locale);
+                //  Entries are queued and written later, see [`entries::EntryQueue`]
+                let _guard = match action {
+                    UserAction::Add(_) => None,
+                    _ => Some(db.lock_guild(*guild).await),
+                };
+                let input = data
+                    .components
                     .iter()
-                    .find(|(_, ga)| ga.channel == channel.get() && ga.message == message.get())
-                    .map(|(id, _)| *id)
-                    .and_then(|id| state.giveaways.remove(&id).map(|ga| (id, ga)))
-            })?
-            .map(|(a, b)| (a, b.into()));
-            if let Some((id, giveaway)) = data {
-                if let Err(err) = cancel_giveaway(&giveaway, &ctx).await {
-                    eprintln!("Error cancelling giveaway: {}", err);
-                    let giveaway: Giveaway = giveaway.into();
-                    db_write(db, *guild, move |state| {
-                        state.giveaways.insert(id, giveaway)
-                    })?;
+                    .flat_map(|row| &row.components)
+                    .find_map(|component| match component {
+                        ActionRowComponent::InputText(input) => input.value.clone(),
+                        _ => None,
+                    })
+                    .map(|input| input.trim().to_string())
+                    .filter(|input| !input.is_empty());
+                match action {
+                    UserAction::Cancel(id) if may_end(db, *guild, id, member)? => {
+                        let giveaway: Option<RealGiveaway> =
+                            db_write(db, *guild, |state| state.giveaways.remove(&id))?
+                                .map(|v| v.into());
+                        if let Some(giveaway) = giveaway {
+                            record(
+                                db,
+                                *guild,
+                                Some(interaction.user.id),
+                                t!(language, "audit-giveaway-cancelled"),
+                                format!("{} ({id})", giveaway.title),
+                            );
+                            let result = cancel_giveaway(&giveaway, input, false, &ctx).await;
+                            let http = MyHttpCache::new(ctx.http.clone(), ctx.cache.clone());
+                            archive_or_restore(db, &http, *guild, id, giveaway, result)?;
+                        }
+                    }
+                    UserAction::Codes(id)
+                        if member.permissions.is_some_and(|p| p.create_events()) =>
+                    {
+                        let content = match add_codes(db, *guild, id, input.as_deref())? {
+                            Some(count) => t!(language, "codes-saved", count),
+                            None => t!(language, "giveaway-not-running"),
+                        };
+                        interaction
+                            .create_followup(
+                                &ctx,
+                                CreateInteractionResponseFollowup::new()
+                                    .content(content)
+                                    .ephemeral(true),
+                            )
+                            .await?;
+                    }
+                    UserAction::Add(id) => {
+                        let question = db_read(db, *guild)?
+                            .giveaways
+                            .remove(&id)
+                            .and_then(|giveaway| giveaway.question);
+                        let content = match question {
+                            Some(question)
+                                if !question.accepts(input.as_deref().unwrap_or_default()) =>
+                            {
+                                t!(language, "entry-wrong-answer")
+                            }
+                            _ => enter(*guild, id, member, db, &ctx).await?,
+                        };
+                        interaction
+                            .create_followup(
+                                &ctx,
+                                CreateInteractionResponseFollowup::new()
+                                    .content(content)
+                                    .ephemeral(true),
+                            )
+                            .await?;
+                    }
+                    _ => {}
                 }
             }
         }
         FullEvent::InteractionCreate {
             interaction: Interaction::Component(interaction),
         } => {
+            metrics().button();
+            //  Asking for the reason has to be the first response, so it can't be deferred
+            let modal = match cancel_modal(interaction, db)? {
+                Some(modal) => Some(modal),
+                None => question_modal(interaction, db)?,
+            };
+            if let Some(modal) = modal.or_else(|| codes_modal(interaction, db)) {
+                interaction
+                    .create_response(&ctx, CreateInteractionResponse::Modal(modal))
+                    .await?;
+                return Ok(());
+            }
             interaction.defer(&ctx).await?;
-            match &interaction {
-                ComponentInteraction {
-                    guild_id: Some(guild),
-                    member: Some(member),
-                    user,
-                    data:
-                        ComponentInteractionData {
-                            custom_id,
-                            kind: ComponentInteractionDataKind::Button,
-                            ..
-                        },
-                    ..
-                } => {
-                    let action: UserAction = serde_json::from_str(&custom_id)?;
-                    match action {
-                        UserAction::Add(id) => {
-                            add_user(*guild, id, user.id, db).await?;
-                            interaction
-                                .create_followup(
-                                    &ctx,
-                                    CreateInteractionResponseFollowup::new()
-                                        .content("Du nimmst am Giveaway teil")
-                                        .ephemeral(true),
-                                )
-                                .await?;
+            if let ComponentInteraction {
+                guild_id: Some(guild),
+                member: Some(member),
+                user,
+                data:
+                    ComponentInteractionData {
+                        custom_id,
+                        kind: ComponentInteractionDataKind::Button,
+                        ..
+                    },
+                ..
+            } = &interaction
+            {
+                let action: UserAction = serde_json::from_str(custom_id)?;
+                let language = language_of(db, Some(*guild), &interaction.locale);
+                //  Reading needs no lock, clearing can take minutes without touching giveaways and
+                //  entries are queued
+                let _guard = match action {
+                    UserAction::Add(_)
+                    | UserAction::Remove(_)
+                    | UserAction::Clear(..)
+                    | UserAction::Participants(..)
+                    | UserAction::Audit(_) => None,
+                    _ => Some(db.lock_guild(*guild).await),
+                };
+                match action {
+                    UserAction::Add(id) => {
+                        let content = enter(*guild, id, member, db, &ctx).await?;
+                        interaction
+                            .create_followup(
+                                &ctx,
+                                CreateInteractionResponseFollowup::new()
+                                    .content(content)
+                                    .ephemeral(true),
+                            )
+                            .await?;
+                    }
+                    UserAction::Remove(id) => {
+                        db.entries().remove(*guild, id, user.id.get());
+                        interaction
+                            .create_followup(
+                                &ctx,
+                                CreateInteractionResponseFollowup::new()
+                                    .content(t!(language, "entry-removed"))
+                                    .ephemeral(true),
+                            )
+                            .await?;
+                    }
+                    UserAction::Finish(id) if may_end(db, *guild, id, member)? => {
+                        //  Only archiving takes it out of the running giveaways
+                        let giveaway: Option<RealGiveaway> =
+                            db_read(db, *guild)?.giveaways.remove(&id).map(|v| v.into());
+                        if let Some(mut giveaway) = giveaway {
+                            record(
+                                db,
+                                *guild,
+                                Some(user.id),
+                                t!(language, "audit-giveaway-ended"),
+                                format!("{} ({id})", giveaway.title),
+                            );
+                            let result = finish_giveaway(db, *guild, id, &mut giveaway, &ctx).await;
+                            let http = MyHttpCache::new(ctx.http.clone(), ctx.cache.clone());
+                            archive_or_restore(db, &http, *guild, id, giveaway, result)?;
                         }
-                        UserAction::Remove(id) => {
-                            remove_user(*guild, id, user.id, db).await?;
+                    }
+                    UserAction::Reroll(id)
+                        if member.permissions.is_some_and(|p| p.create_events()) =>
+                    {
+                        let http = MyHttpCache::new(ctx.http.clone(), ctx.cache.clone());
+                        if reroll_giveaway(*guild, id, 1, db, &http).await?.is_none() {
                             interaction
                                 .create_followup(
                                     &ctx,
                                     CreateInteractionResponseFollowup::new()
-                                        .content("Du nimmst nicht mehr am Giveaway teil")
+                                        .content(t!(language, "giveaway-not-archived"))
                                         .ephemeral(true),
                                 )
                                 .await?;
                         }
-                        UserAction::Finish(id)
-                            if member.permissions.is_some_and(|p| p.create_events()) =>
-                        {
-                            let giveaway: Option<RealGiveaway> =
-                                db_write(db, *guild, move |state| state.giveaways.remove(&id))?
-                                    .map(|v| v.into());
-                            if let Some(giveaway) = giveaway {
-                                if let Err(err) = finish_giveaway(&giveaway, &ctx).await {
-                                    eprintln!("Error finishing giveaway: {}", err);
-                                    let giveaway: Giveaway = giveaway.into();
-                                    db_write(db, *guild, move |state| {
-                                        state.giveaways.insert(id, giveaway)
-                                    })?;
-                                }
+                    }
+                    UserAction::Claim(id) => {
+                        let now = Utc::now().timestamp();
+                        let user = user.id.get();
+                        let archived = db_write(db, *guild, move |state| {
+                            let archived = state.archive.get_mut(&id)?;
+                            let slot = archived.slots.iter_mut().find(|slot| {
+                                slot.user == Some(user)
+                                    && slot.pending()
+                                    && slot.deadline.is_some_and(|deadline| deadline > now)
+                            })?;
+                            slot.claimed = true;
+                            Some(archived.clone())
+                        })?;
+                        let content = match archived {
+                            Some(archived) => {
+                                update_announcement(*guild, id, &archived, &ctx).await?;
+                                t!(language, "claim-done")
                             }
-                        }
-                        UserAction::Cancel(id)
-                            if member.permissions.is_some_and(|p| p.create_events()) =>
-                        {
-                            let giveaway: Option<RealGiveaway> =
-                                db_write(db, *guild, |state| state.giveaways.remove(&id))?
-                                    .map(|v| v.into());
-                            if let Some(giveaway) = giveaway {
-                                if let Err(err) = cancel_giveaway(&giveaway, &ctx).await {
-                                    eprintln!("Error cancelling giveaway: {}", err);
-                                    let giveaway: Giveaway = giveaway.into();
-                                    db_write(db, *guild, move |state| {
-                                        state.giveaways.insert(id, giveaway);
-                                    })?;
-                                }
+                            None => t!(language, "claim-none"),
+                        };
+                        interaction
+                            .create_followup(
+                                &ctx,
+                                CreateInteractionResponseFollowup::new()
+                                    .content(content)
+                                    .ephemeral(true),
+                            )
+                            .await?;
+                    }
+                    UserAction::Participants(id, page)
+                        if member.permissions.is_some_and(|p| p.create_events()) =>
+                    {
+                        let (content, components) = match find_giveaway(db, *guild, id)? {
+                            Some(giveaway) if !may_view(&giveaway, member.permissions) => {
+                                (t!(language, "participants-anonymous"), Vec::new())
+                            }
+                            Some(giveaway) => {
+                                participant_page(id, &giveaway, page.unwrap_or(0), language)
+                            }
+                            None => (t!(language, "giveaway-not-found"), Vec::new()),
+                        };
+                        match page {
+                            //  Opened from the giveaway message, which must stay untouched
+                            None => {
+                                interaction
+                                    .create_followup(
+                                        &ctx,
+                                        CreateInteractionResponseFollowup::new()
+                                            .content(content)
+                                            .components(components)
+                                            .ephemeral(true),
+                                    )
+                                    .await?;
+                            }
+                            Some(_) => {
+                                interaction
+                                    .edit_response(
+                                        &ctx,
+                                        EditInteractionResponse::new()
+                                            .content(content)
+                                            .components(components),
+                                    )
+                                    .await?;
                             }
                         }
-                        UserAction::Clear(None) => {
-                            interaction.message.delete(&ctx).await?;
-                        }
-                        UserAction::ClearAll(None) => {
-                            interaction.message.delete(&ctx).await?;
-                        }
-                        UserAction::Clear(Some((guild, user)))
-                            if member.permissions.is_some_and(|p| p.manage_channels()) =>
-                        {
+                    }
+                    UserAction::Participants(..) => {
+                        interaction
+                            .create_followup(
+                                &ctx,
+                                CreateInteractionResponseFollowup::new()
+                                    .content(t!(language, "no-permission"))
+                                    .ephemeral(true),
+                            )
+                            .await?;
+                    }
+                    //  Running jobs notice the removal themselves and report what they deleted
+                    UserAction::Clear(id, false) => match take_clear_job(db, id)? {
+                        Some(job) if job.running => {
                             interaction
                                 .edit_response(
                                     &ctx,
                                     EditInteractionResponse::new()
-                                        .content("Das dauert einen kleinen Moment...")
+                                        .content(t!(language, "clear-stopping"))
                                         .components(Vec::new()),
                                 )
                                 .await?;
-                            let count = clear_user(&ctx, guild, user).await?;
-                            interaction
-                                .create_followup(
-                                    &ctx,
-                                    CreateInteractionResponseFollowup::new()
-                                        .content(format!(
-                                            "Es wurden {count} Nachrichten von <@{user}> gelöscht"
-                                        ))
-                                        .ephemeral(false),
-                                )
-                                .await?;
-                            interaction.delete_response(&ctx).await?;
                         }
-                        UserAction::ClearAll(Some(channel))
-                            if member.permissions.is_some_and(|p| p.manage_channels()) =>
-                        {
+                        _ => interaction.message.delete(&ctx).await?,
+                    },
+                    UserAction::Clear(id, true) if may_clear(db, id, member)? => {
+                        interaction
+                            .edit_response(
+                                &ctx,
+                                EditInteractionResponse::new()
+                                    .content(t!(language, "please-wait"))
+                                    .components(vec![stop_button(id, language)]),
+                            )
+                            .await?;
+                        let Some(job) = start_clear_job(db, id, interaction.user.id)? else {
                             interaction
                                 .edit_response(
                                     &ctx,
                                     EditInteractionResponse::new()
-                                        .content("Das dauert einen kleinen Moment...")
-                                        .components(Vec::new()),
-                                )
-                                .await?;
-                            clear_channel(&ctx, channel).await?;
-                            interaction.delete_response(&ctx).await?;
-                            channel
-                                .send_message(
-                                    &ctx,
-                                    CreateMessage::new().content("_Kanal wurde geleert_"),
-                                )
-                                .await?;
-                        }
-                        _ => {
-                            interaction.delete_response(&ctx).await?;
-                            interaction
-                                .create_response(
-                                    ctx,
-                                    CreateInteractionResponse::Message(
-                                        CreateInteractionResponseMessage::new()
-                                            .content("Keine Berechtigung")
-                                            .ephemeral(true),
-                                    ),
+                                        .content(t!(language, "confirm-expired")),
                                 )
                                 .await?;
+                            return Ok(());
+                        };
+                        let modlog = modlog(db, GuildId::new(job.guild))?;
+                        let cleared = clear_messages(
+                            &ctx,
+                            &job,
+                            false,
+                            Some(Progress::new(Some(interaction), db, id, language)),
+                            modlog.is_some_and(|modlog| modlog.transcript),
+                        )
+                        .await?;
+                        take_clear_job(db, id)?;
+                        let report = cleared.report(&job, language);
+                        record(
+                            db,
+                            GuildId::new(job.guild),
+                            Some(interaction.user.id),
+                            t!(language, "audit-clear-confirmed"),
+                            report.clone(),
+                        );
+                        let complete = !cleared.stopped && cleared.failed.is_empty();
+                        interaction.delete_response(&ctx).await?;
+                        log_clear(&ctx, modlog, interaction.user.id, &job, cleared, language).await;
+                        match job.whole_channel() {
+                            Some(channel) if complete => {
+                                channel
+                                    .send_message(
+                                        &ctx,
+                                        CreateMessage::new()
+                                            .content(t!(language, "clear-channel-done")),
+                                    )
+                                    .await?;
+                            }
+                            _ => {
+                                interaction
+                                    .create_followup(
+                                        &ctx,
+                                        //  The report repeats the pattern, which might contain
+                                        //  mentions
+                                        CreateInteractionResponseFollowup::new()
+                                            .content(report)
+                                            .allowed_mentions(CreateAllowedMentions::new())
+                                            .ephemeral(false),
+                                    )
+                                    .await?;
+                            }
                         }
                     }
+                    UserAction::Audit(page)
+                        if member.permissions.is_some_and(|p| p.administrator()) =>
+                    {
+                        let (content, components) = audit_page(db, *guild, page, language)?;
+                        interaction
+                            .edit_response(
+                                &ctx,
+                                EditInteractionResponse::new()
+                                    .content(content)
+                                    .components(components),
+                            )
+                            .await?;
+                    }
+                    UserAction::EndAll(None)
+                    | UserAction::CancelAll(None)
+                    | UserAction::DeleteData(false) => {
+                        interaction.message.delete(&ctx).await?;
+                    }
+                    UserAction::DeleteData(true) => {
+                        let erased = erase_user(db, *guild, user.id)?;
+                        interaction
+                            .edit_response(
+                                &ctx,
+                                EditInteractionResponse::new()
+                                    .content(t!(language, "mydata-deleted", erased))
+                                    .components(Vec::new()),
+                            )
+                            .await?;
+                    }
+                    UserAction::EndAll(Some(guild)) | UserAction::CancelAll(Some(guild))
+                        if member.permissions.is_some_and(|p| p.administrator()) =>
+                    {
+                        interaction
+                            .edit_response(
+                                &ctx,
+                                EditInteractionResponse::new()
+                                    .content(t!(language, "please-wait"))
+                                    .components(Vec::new()),
+                            )
+                            .await?;
+                        let cancel = matches!(action, UserAction::CancelAll(_));
+                        let report = end_all_giveaways(db, guild, cancel, language, ctx).await?;
+                        let action = match cancel {
+                            true => t!(language, "audit-cancelled-all"),
+                            false => t!(language, "audit-ended-all"),
+                        };
+                        record(db, guild, Some(user.id), action, report.clone());
+                        interaction
+                            .edit_response(&ctx, EditInteractionResponse::new().content(report))
+                            .await?;
+                    }
+                    //  The interaction is already deferred, so the message with the button must stay
+                    _ => {
+                        interaction
+                            .create_followup(
+                                &ctx,
+                                CreateInteractionResponseFollowup::new()
+                                    .content(t!(language, "no-permission"))
+                                    .ephemeral(true),
+                            )
+                            .await?;
+                    }
                 }
-                _ => {}
             }
             //interaction
             //    .create_followup(&ctx, CreateInteractionResponseFollowup::new())
             //    .await?;
         }
-        _ => {}
+        _ => {}
+    }
+    Ok(())
+}
+
+//  Returns the reply for the member, either the confirmation or why they can't enter
+async fn enter(
+    guild: GuildId,
+    id: GiveawayId,
+    member: &Member,
+    db: &Db,
+    http: &impl CacheHttp,
+) -> anyhow::Result<String> {
+    Ok(match add_user(guild, id, member, db, http).await? {
+        Ok(()) => t!(db.settings(guild)?.language, "entry-added"),
+        Err(reason) => reason,
+    })
+}
+
+//  Returns the reason as error, if the member doesn't meet the requirements of the giveaway.
+//  The entry itself is only queued, see [`entries::EntryQueue`]
+async fn add_user(
+    guild: GuildId,
+    id: GiveawayId,
+    member: &Member,
+    db: &Db,
+    http: &impl CacheHttp,
+) -> anyhow::Result<Result<(), String>> {
+    let activity = get_activity(db, guild, member.user.id)?;
+    let last_win = last_win(db, guild, member.user.id)?;
+    let invited = invited_count(db, guild, member.user.id)?;
+    let settings = db.settings(guild)?;
+    let user = member.user.id.get();
+    //  Only the giveaway itself is read, not the whole guild with every participant
+    let Some((giveaway, stored)) = db.storage().giveaway_entry(guild, id, user)? else {
+        return Ok(Ok(()));
+    };
+    if let Err(reason) =
+        giveaway
+            .requirements
+            .check(member, &activity, last_win, invited, settings.language)
+    {
+        return Ok(Err(reason));
+    }
+    //  The winners are drawn already, also after a restart in the middle of finishing it
+    if finishing(db, guild, id)?.is_some() {
+        return Ok(Err(t!(settings.language, "entry-closed")));
+    }
+    if db.entries().participates(guild, id, user, stored) {
+        return Ok(Ok(()));
+    }
+    let flagged = settings.alt_check.filter(|check| check.suspicious(member));
+    let success = match flagged {
+        Some(check) if check.action == AltAction::Deny => {
+            Err(t!(settings.language, "entry-denied"))
+        }
+        _ => match db.entries().add(guild, id, user, stored) {
+            Some(_) => Ok(()),
+            None => Err(t!(settings.language, "entry-closed")),
+        },
+    };
+    if let Some(check) = flagged
+        && let Some(channel) = check.log_channel
+        && let Err(err) = report_alt(
+            guild,
+            ChannelId::new(channel),
+            member,
+            check,
+            giveaway,
+            settings.language,
+            http,
+        )
+        .await
+    {
+        metrics().discord_error(err.as_ref());
+        error!("Error reporting suspicious entry: {}", err);
+    }
+    Ok(success)
+}
+
+//  Moderators decide themselves, whether the account is really an alt
+async fn report_alt(
+    guild: GuildId,
+    channel: ChannelId,
+    member: &Member,
+    check: AltCheck,
+    giveaway: Giveaway,
+    language: Language,
+    http: &impl CacheHttp,
+) -> anyhow::Result<()> {
+    let link = MessageId::new(giveaway.message).link(ChannelId::new(giveaway.channel), Some(guild));
+    let status = match check.action {
+        AltAction::Flag => t!(language, "alt-flagged"),
+        AltAction::Deny => t!(language, "alt-denied"),
+    };
+    channel
+        .send_message(
+            http,
+            CreateMessage::new()
+                .content(t!(
+                    language,
+                    "alt-report",
+                    user = member.user.id.mention(),
+                    name = member.user.name,
+                    status,
+                    created = format!("<t:{}:R>", member.user.id.created_at().unix_timestamp()),
+                    title = giveaway.title,
+                    link,
+                ))
+                .allowed_mentions(CreateAllowedMentions::new()),
+        )
+        .await?;
+    Ok(())
+}
+
+//  The host may end their own giveaway without further permissions
+fn may_end(db: &Db, guild: GuildId, id: GiveawayId, member: &Member) -> anyhow::Result<bool> {
+    if member.permissions.is_some_and(|p| p.create_events()) {
+        return Ok(true);
+    }
+    Ok(db_read(db, guild)?
+        .giveaways
+        .remove(&id)
+        .and_then(|giveaway| giveaway.host)
+        == Some(member.user.id.get()))
+}
+
+//  Returns the modal asking for a reason, if the button cancels a giveaway and the member may do so
+fn cancel_modal(
+    interaction: &ComponentInteraction,
+    db: &Db,
+) -> anyhow::Result<Option<CreateModal>> {
+    let (Some(guild), Some(member)) = (interaction.guild_id, &interaction.member) else {
+        return Ok(None);
+    };
+    let custom_id = &interaction.data.custom_id;
+    let Ok(UserAction::Cancel(id)) = serde_json::from_str(custom_id) else {
+        return Ok(None);
+    };
+    if !may_end(db, guild, id, member)? {
+        return Ok(None);
+    }
+    let language = db.settings(guild)?.language;
+    let reason = CreateInputText::new(
+        InputTextStyle::Paragraph,
+        t!(language, "cancel-modal-reason"),
+        "reason",
+    )
+    .required(false)
+    .max_length(500);
+    Ok(Some(
+        CreateModal::new(custom_id, t!(language, "cancel-modal-title"))
+            .components(vec![CreateActionRow::InputText(reason)]),
+    ))
+}
+
+//  Codes can be added until the giveaway ends, returns None if it isn't running or scheduled
+fn add_codes(
+    db: &Db,
+    guild: GuildId,
+    id: GiveawayId,
+    input: Option<&str>,
+) -> anyhow::Result<Option<usize>> {
+    let codes = input
+        .unwrap_or_default()
+        .lines()
+        .map(str::trim)
+        .filter(|code| !code.is_empty())
+        .map(seal)
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    let count = codes.len();
+    db_write(db, guild, move |state| {
+        let secret_codes = match state.giveaways.get_mut(&id) {
+            Some(giveaway) => &mut giveaway.secret_codes,
+            None => &mut state.drafts.get_mut(&id)?.secret_codes,
+        };
+        secret_codes.extend(codes);
+        Some(count)
+    })
+}
+
+//  Returns the modal asking the entry question, if the giveaway has one and the member didn't enter yet
+fn question_modal(
+    interaction: &ComponentInteraction,
+    db: &Db,
+) -> anyhow::Result<Option<CreateModal>> {
+    let Some(guild) = interaction.guild_id else {
+        return Ok(None);
+    };
+    let custom_id = &interaction.data.custom_id;
+    let Ok(UserAction::Add(id)) = serde_json::from_str(custom_id) else {
+        return Ok(None);
+    };
+    let giveaway = db_read(db, guild)?.giveaways.remove(&id);
+    let Some(giveaway) = giveaway.filter(|giveaway| {
+        !giveaway
+            .participants
+            .contains_key(&interaction.user.id.get())
+    }) else {
+        return Ok(None);
+    };
+    let Some(question) = giveaway.question else {
+        return Ok(None);
+    };
+    let answer =
+        CreateInputText::new(InputTextStyle::Short, question.text, "answer").max_length(100);
+    Ok(Some(
+        CreateModal::new(custom_id, t!(giveaway.style.language, "entry-modal"))
+            .components(vec![CreateActionRow::InputText(answer)]),
+    ))
+}
+
+//  Returns the modal for the secret codes, if the member may create giveaways
+fn codes_modal(interaction: &ComponentInteraction, db: &Db) -> Option<CreateModal> {
+    let custom_id = &interaction.data.custom_id;
+    let Ok(UserAction::Codes(_)) = serde_json::from_str(custom_id) else {
+        return None;
+    };
+    let language = language_of(db, interaction.guild_id, &interaction.locale);
+    interaction
+        .member
+        .as_ref()?
+        .permissions
+        .is_some_and(|p| p.create_events())
+        .then(|| {
+            let codes = CreateInputText::new(
+                InputTextStyle::Paragraph,
+                t!(language, "codes-modal-input"),
+                "codes",
+            )
+            .max_length(4000);
+            CreateModal::new(custom_id, t!(language, "codes-modal-title"))
+                .components(vec![CreateActionRow::InputText(codes)])
+        })
+}
+
+//  Only embeds show the participant count, so plain text messages are left alone
+async fn update_message(
+    guild: GuildId,
+    id: GiveawayId,
+    db: &Db,
+    http: &impl CacheHttp,
+) -> anyhow::Result<()> {
+    let giveaway: Option<RealGiveaway> =
+        db_read(db, guild)?.giveaways.remove(&id).map(|v| v.into());
+    if let Some(giveaway) = giveaway
+        && giveaway.style.embed
+    {
+        giveaway
+            .channel
+            .edit_message(http, giveaway.message, giveaway.view().edit_message(false))
+            .await?;
+        edit_mirrors(&giveaway, giveaway.view().edit_message(false), http).await;
+    }
+    Ok(())
+}
+
+//  Failed posts are tried again, waiting twice as long after every failure
+async fn start_task(
+    guild: GuildId,
+    id: GiveawayId,
+    mut start: DateTime<Utc>,
+    db: Arc<Db>,
+    http: MyHttpCache,
+) -> anyhow::Result<()> {
+    const FIRST_RETRY: i64 = 60;
+    const MAX_RETRY: i64 = 60 * 60;
+    let mut failures = 0;
+    loop {
+        let now = chrono::Utc::now();
+        let diff = start.timestamp() - now.timestamp();
+        if diff > 0 {
+            tokio::time::sleep(Duration::from_secs(diff as u64)).await;
+        }
+        //  Chosen before the message is posted, which shows its hash
+        let commitment = commit()?;
+        let _guard = db.lock_guild(guild).await;
+        let Some((mut draft, ping_role)) = db_write(&db, guild, move |state| {
+            let ping_role = state.settings.ping_role;
+            state.drafts.remove(&id).map(|draft| (draft, ping_role))
+        })?
+        else {
+            return Ok(());
+        };
+        let time = draft
+            .time
+            .map(|ts| DateTime::from_timestamp(ts, 0).unwrap().to_utc());
+        let mut view = draft.view();
+        view.commitment = Some(&commitment.hash);
+        let (mention, allowed_mentions) = ping_mentions(draft.ping, ping_role);
+        let result = ChannelId::new(draft.channel)
+            .send_message(
+                &http,
+                view.create_message(false)
+                    .content(format!("{mention}{}", view.content(false)).trim_end())
+                    .allowed_mentions(allowed_mentions)
+                    .components(vec![giveaway_buttons(id, &draft.style)]),
+            )
+            .await;
+        let message = match result {
+            Ok(message) => message,
+            Err(err) => {
+                metrics().discord_error(&err);
+                error!("Error starting giveaway: {}", err);
+                //  Only told once, e.g. to fix the permissions of the bot in the channel
+                if failures == 0 {
+                    notify_start_failed(&draft, &http).await;
+                }
+                start = Utc::now()
+                    + TimeDelta::seconds((FIRST_RETRY << failures.min(6)).min(MAX_RETRY));
+                failures += 1;
+                draft.start = start.timestamp();
+                db_write(&db, guild, move |state| state.drafts.insert(id, draft))?;
+                continue;
+            }
+        };
+        if draft.style.reactions
+            && let Err(err) = add_entry_reaction(&http, message.channel_id, message.id).await
+        {
+            metrics().discord_error(err.as_ref());
+            error!("Error adding entry reaction: {}", err);
+        }
+        let mirror_channels: Vec<ChannelId> = draft
+            .mirror_channels
+            .iter()
+            .map(|&mirror| ChannelId::new(mirror))
+            .collect();
+        let mirrors = post_mirrors(id, &view, &mirror_channels, &http).await;
+        let mut giveaway = draft.publish(message.id, commitment);
+        giveaway.mirrors = mirrors
+            .into_iter()
+            .map(|(channel, message)| (channel.get(), message.get()))
+            .collect();
+        if let Some(event) = giveaway.event {
+            start_event(&http, guild, event).await;
+        }
+        db_write(&db, guild, move |state| {
+            state.giveaways.insert(id, giveaway)
+        })?;
+        metrics().giveaway_created();
+        if let Some(time) = time {
+            schedule_end(guild, id, time, db, http);
+        }
+        return Ok(());
+    }
+}
+
+async fn notify_start_failed(draft: &Draft, http: &MyHttpCache) {
+    let text = t!(
+        draft.style.language,
+        "start-failed",
+        title = draft.title,
+        channel = ChannelId::new(draft.channel).mention()
+    );
+    if let Err(err) = UserId::new(draft.host)
+        .direct_message(http, CreateMessage::new().content(text))
+        .await
+    {
+        metrics().discord_error(&err);
+        error!("Error notifying host: {}", err);
+    }
+}
+
+//  Archives a giveaway after finishing it, or puts it back, if finishing failed
+fn archive_or_restore(
+    db: &Arc<Db>,
+    http: &MyHttpCache,
+    guild: GuildId,
+    id: GiveawayId,
+    giveaway: RealGiveaway,
+    result: anyhow::Result<ArchivedGiveaway>,
+) -> anyhow::Result<()> {
+    let giveaway: Giveaway = giveaway.into();
+    match result {
+        Ok(archived) => {
+            if !archived.cancelled {
+                metrics().giveaway_finished();
+            }
+            let claims = archived.next_deadline().is_some();
+            let roles = archived.next_role_removal().is_some();
+            if let Some(event) = archived.giveaway.event {
+                let http = http.clone();
+                let cancelled = archived.cancelled;
+                db.spawn_for_guild(guild, async move {
+                    end_event(&http, guild, event, cancelled).await;
+                });
+            }
+            if archived.giveaway.notify_losers && !archived.cancelled {
+                let http = http.clone();
+                let archived = archived.clone();
+                db.spawn_for_guild(guild, async move {
+                    notify_losers(guild, &archived, &http).await;
+                });
+            }
+            db_write(db, guild, move |state| {
+                state.giveaways.remove(&id);
+                state.archive.insert(id, archived);
+            })?;
+            //  Entering is refused anyway now, it isn't running anymore
+            db.entries().reopen(guild, id);
+            let task = complete_finish(guild, id, db.clone(), http.clone());
+            db.spawn_for_guild(guild, async move {
+                if let Err(err) = task.await {
+                    error!("Error completing finished giveaway {id}: {}", err);
+                }
+            });
+            if claims {
+                let task = claim_task(guild, id, db.clone(), http.clone());
+                db.spawn_for_guild(guild, async move { task.await.unwrap() });
+            }
+            if roles {
+                spawn_role_task(db, http, guild, id);
+            }
+        }
+        Err(err) => {
+            metrics().discord_error(err.as_ref());
+            error!("Error ending giveaway: {}", err);
+            //  Unless it was archived or restored some other way meanwhile, e.g. by /import
+            db_write(db, guild, move |state| {
+                if !state.archive.contains_key(&id) {
+                    state.giveaways.entry(id).or_insert(giveaway);
+                }
+            })?;
+            db.entries().reopen(guild, id);
+            //  Once the winners are drawn, it's finished with them later on instead of drawing
+            //  again, which would hand out the prizes twice
+            if finishing(db, guild, id)?.is_some() {
+                let task = resume_finish(guild, id, FIRST_FINISH_RETRY, db.clone(), http.clone());
+                db.spawn_for_guild(guild, async move { task.await.unwrap() });
+            }
+        }
+    }
+    Ok(())
+}
+
+//  Both tasks check, that the end time is still current when they wake up
+fn schedule_end(
+    guild: GuildId,
+    id: GiveawayId,
+    time: DateTime<Utc>,
+    db: Arc<Db>,
+    http: MyHttpCache,
+) {
+    let task = reminder_task(guild, id, time, db.clone(), http.clone());
+    db.spawn_for_guild(guild, async move { task.await.unwrap() });
+    let task = countdown_task(guild, id, time, db.clone(), http.clone());
+    db.spawn_for_guild(guild, async move { task.await.unwrap() });
+    let task = finish_task(guild, id, time, db.clone(), http);
+    db.spawn_for_guild(guild, async move { task.await.unwrap() });
+}
+
+//  Reminders that would have been posted before now are skipped, e.g. after a restart
+async fn reminder_task(
+    guild: GuildId,
+    id: GiveawayId,
+    time: DateTime<Utc>,
+    db: Arc<Db>,
+    http: MyHttpCache,
+) -> anyhow::Result<()> {
+    let lead = db.settings(guild)?.reminder;
+    let Some(minutes) = lead else {
+        return Ok(());
+    };
+    let diff = time.timestamp() - i64::from(minutes) * 60 - Utc::now().timestamp();
+    if diff < 0 {
+        return Ok(());
+    }
+    tokio::time::sleep(Duration::from_secs(diff as u64)).await;
+    let mut state = db_read(&db, guild)?;
+    let Some((giveaway, settings)) = state
+        .giveaways
+        .remove(&id)
+        .filter(|ga| ga.time == Some(time.timestamp()))
+        .map(|ga| (RealGiveaway::from(ga), state.settings))
+    else {
+        return Ok(());
+    };
+    let (mention, allowed_mentions) = ping_mentions(settings.reminder_ping, settings.ping_role);
+    let text = t!(
+        giveaway.style.language,
+        "giveaway-reminder",
+        time = format!("<t:{}:R>", time.timestamp())
+    );
+    giveaway
+        .channel
+        .send_message(
+            &http,
+            CreateMessage::new()
+                .content(format!("{mention}# {}\n\n{text}", giveaway.title))
+                .allowed_mentions(allowed_mentions)
+                .reference_message((giveaway.channel, giveaway.message)),
+        )
+        .await?;
+    Ok(())
+}
+
+//  Refreshes the remaining time of giveaways, that don't use Discord timestamps
+async fn countdown_task(
+    guild: GuildId,
+    id: GiveawayId,
+    time: DateTime<Utc>,
+    db: Arc<Db>,
+    http: MyHttpCache,
+) -> anyhow::Result<()> {
+    const MAX_BACKOFF: u64 = 60 * 60;
+    let mut backoff = 0;
+    loop {
+        let giveaway: Option<RealGiveaway> = db_read(&db, guild)?
+            .giveaways
+            .remove(&id)
+            .filter(|ga| ga.time == Some(time.timestamp()) && ga.style.countdown.is_some())
+            .map(|v| v.into());
+        let Some(giveaway) = giveaway else {
+            return Ok(());
+        };
+        let remaining = time.timestamp() - Utc::now().timestamp();
+        if remaining <= 0 {
+            return Ok(());
+        }
+        //  Every minute in the final minutes, otherwise every ten minutes
+        let interval = match remaining > 15 * 60 {
+            true => 10 * 60,
+            false => 60,
+        };
+        edit_mirrors(&giveaway, giveaway.view().edit_message(false), &http).await;
+        let wait = match giveaway
+            .channel
+            .edit_message(&http, giveaway.message, giveaway.view().edit_message(false))
+            .await
+        {
+            Ok(_) => {
+                backoff = 0;
+                interval
+            }
+            //  Waits longer after every failed edit, e.g. because of rate limits
+            Err(err) => {
+                metrics().discord_error(&err);
+                error!("Error refreshing countdown: {}", err);
+                backoff = (backoff * 2).clamp(interval, MAX_BACKOFF);
+                backoff
+            }
+        };
+        tokio::time::sleep(Duration::from_secs(wait.min(remaining as u64))).await;
+    }
+}
+
+//  Direct messages are sent slowly and in batches, so big giveaways don't hit Discord's limits
+async fn notify_losers(guild: GuildId, archived: &ArchivedGiveaway, http: &MyHttpCache) {
+    const BATCH: usize = 10;
+    let giveaway: RealGiveaway = archived.giveaway.clone().into();
+    let link = giveaway.message.link(giveaway.channel, Some(guild));
+    let text = t!(
+        giveaway.style.language,
+        "giveaway-lost",
+        title = giveaway.title,
+        link
+    );
+    let losers: Vec<UserId> = giveaway
+        .participants
+        .into_keys()
+        .filter(|user| !archived.winners.contains(&user.get()))
+        .collect();
+    for batch in losers.chunks(BATCH) {
+        for user in batch {
+            //  Members might not accept direct messages
+            if let Err(err) = user
+                .direct_message(http, CreateMessage::new().content(&text))
+                .await
+            {
+                metrics().discord_error(&err);
+                error!("Error notifying participant: {}", err);
+            }
+            tokio::time::sleep(Duration::from_secs(2)).await;
+        }
+        tokio::time::sleep(Duration::from_secs(60)).await;
+    }
+}
+
+async fn finish_task(
+    guild: GuildId,
+    id: GiveawayId,
+    time: DateTime<Utc>,
+    db: Arc<Db>,
+    http: MyHttpCache,
+) -> anyhow::Result<()> {
+    let now = chrono::Utc::now();
+    let diff = time.timestamp() - now.timestamp();
+    if diff > 0 {
+        tokio::time::sleep(Duration::from_secs(diff as u64)).await;
+    }
+    let _guard = db.lock_guild(guild).await;
+    //  The end time might have changed in the meantime, then another task is responsible
+    let due = db_write(&db, guild, move |state| {
+        let ga = state
+            .giveaways
+            .get_mut(&id)
+            .filter(|ga| ga.time == Some(time.timestamp()))?;
+        if let Some(auto_extend) = &mut ga.auto_extend
+            && ga.participants.len() < auto_extend.min_participants as usize
+            && auto_extend.extensions < auto_extend.max_extensions
+        {
+            auto_extend.extensions += 1;
+            ga.time = Some(time.timestamp() + auto_extend.extend_by);
+            return Some(Err(ga.clone()));
+        }
+        Some(Ok(ga.clone()))
+    })?;
+    if let Some(Err(extended)) = due {
+        let extended: RealGiveaway = extended.into();
+        //  The new end is stored already, so it has to be scheduled even if the message is stale
+        if let Err(err) = extended
+            .channel
+            .edit_message(&http, extended.message, extended.view().edit_message(false))
+            .await
+        {
+            metrics().discord_error(&err);
+            error!("Error showing extended giveaway: {}", err);
+        }
+        edit_mirrors(&extended, extended.view().edit_message(false), &http).await;
+        if let Some(time) = extended.time {
+            if let Some(event) = extended.event {
+                move_event_end(&http, guild, event, time).await;
+            }
+            schedule_end(guild, id, time, db, http);
+        }
+        return Ok(());
+    }
+    if let Some(Ok(giveaway)) = due {
+        record(
+            &db,
+            guild,
+            None,
+            t!(db.settings(guild)?.language, "audit-giveaway-ended"),
+            format!("{} ({id})", giveaway.title),
+        );
+        let mut giveaway: RealGiveaway = giveaway.into();
+        let result = finish_giveaway(&db, guild, id, &mut giveaway, &http).await;
+        archive_or_restore(&db, &http, guild, id, giveaway, result)?;
+    }
+    Ok(())
+}
+
+const FIRST_FINISH_RETRY: Duration = Duration::from_secs(60);
+const MAX_FINISH_RETRY: Duration = Duration::from_secs(60 * 60);
+
+//  Completes a giveaway with the winners drawn before, after a restart or when finishing it failed.
+//  Waits twice as long after every failure, until it's finished or ended some other way
+async fn resume_finish(
+    guild: GuildId,
+    id: GiveawayId,
+    mut delay: Duration,
+    db: Arc<Db>,
+    http: MyHttpCache,
+) -> anyhow::Result<()> {
+    loop {
+        tokio::time::sleep(delay).await;
+        let _guard = db.lock_guild(guild).await;
+        if finishing(&db, guild, id)?.is_none() {
+            return Ok(());
+        }
+        let Some(giveaway) = db_read(&db, guild)?.giveaways.remove(&id) else {
+            return Ok(());
+        };
+        let mut giveaway: RealGiveaway = giveaway.into();
+        match finish_giveaway(&db, guild, id, &mut giveaway, &http).await {
+            Ok(archived) => {
+                return archive_or_restore(&db, &http, guild, id, giveaway, Ok(archived));
+            }
+            Err(err) => {
+                metrics().discord_error(err.as_ref());
+                error!("Error ending giveaway: {}", err);
+            }
+        }
+        delay = (delay * 2).clamp(FIRST_FINISH_RETRY, MAX_FINISH_RETRY);
+    }
+}
+
+//  Everything that's only done once the winners are archived for good, so a failed finish can't
+//  hand out prizes. Resumed after a restart until every step is done
+async fn complete_finish(
+    guild: GuildId,
+    id: GiveawayId,
+    db: Arc<Db>,
+    http: MyHttpCache,
+) -> anyhow::Result<()> {
+    let _guard = db.lock_guild(guild).await;
+    let Some(finishing) = finishing(&db, guild, id)? else {
+        return Ok(());
+    };
+    if let Some(archived) = db_read(&db, guild)?.archive.remove(&id)
+        && !archived.cancelled
+    {
+        let giveaway = &archived.giveaway;
+        let host = giveaway.host.map(UserId::new);
+        for code in &giveaway.secret_codes {
+            let Some(winner) = code.winner.filter(|winner| {
+                finishing.drawn_winners.contains(winner) && !finishing.code_sent(*winner)
+            }) else {
+                continue;
+            };
+            let (title, language) = (&giveaway.title, giveaway.style.language);
+            send_code(&http, title, language, host, UserId::new(winner), code).await;
+            complete_step(&db, guild, id, Step::CodeSent(winner))?;
+        }
+        //  Granting it again changes nothing, so it isn't recorded per winner
+        if !finishing.done.contains(&Step::RolesGranted) {
+            let winners = finishing.winners();
+            let winner_role = giveaway.winner_role;
+            let language = giveaway.style.language;
+            let grants = grant_winner_role(&http, guild, winner_role, &winners, language).await;
+            add_role_grants(&db, &http, guild, id, grants)?;
+            complete_step(&db, guild, id, Step::RolesGranted)?;
+        }
+    }
+    unmark_finishing(&db, guild, id)
+}
+
+//  Rerolls every prize, that wasn't claimed in time, until all prizes are claimed
+async fn claim_task(
+    guild: GuildId,
+    id: GiveawayId,
+    db: Arc<Db>,
+    http: MyHttpCache,
+) -> anyhow::Result<()> {
+    loop {
+        let archived = db_read(&db, guild)?.archive.remove(&id);
+        let Some((deadline, cooldown)) = archived.and_then(|archived| {
+            let cooldown = archived.giveaway.requirements.win_cooldown;
+            archived
+                .next_deadline()
+                .map(|deadline| (deadline, cooldown))
+        }) else {
+            return Ok(());
+        };
+        let diff = deadline - Utc::now().timestamp();
+        if diff > 0 {
+            tokio::time::sleep(Duration::from_secs(diff as u64)).await;
+        }
+        let _guard = db.lock_guild(guild).await;
+        let now = Utc::now().timestamp();
+        let Some(archived) = db_read(&db, guild)?.archive.remove(&id) else {
+            return Ok(());
+        };
+        let expired: Vec<usize> = archived
+            .slots
+            .iter()
+            .enumerate()
+            .filter(|(_, slot)| {
+                slot.pending() && slot.deadline.is_none_or(|deadline| deadline <= now)
+            })
+            .map(|(i, _)| i)
+            .collect();
+        if expired.is_empty() {
+            continue;
+        }
+        let participants = RealGiveaway::from(archived.giveaway.clone()).participants;
+        let mut previous = recent_winners(&db, guild, cooldown)?;
+        previous.extend(archived.winners.iter().map(|&u| UserId::new(u)));
+        let drawn = loop {
+            let drawn = draw_winners(&participants, expired.len(), &previous);
+            let absent = absent_members(&http, guild, &drawn).await?;
+            if absent.is_empty() {
+                break drawn;
+            }
+            previous.extend(absent);
+        };
+        let result = db_write(&db, guild, move |state| {
+            let archived = state.archive.get_mut(&id)?;
+            let claim_hours = i64::from(archived.giveaway.claim_hours);
+            let mut drawn = drawn.into_iter();
+            for &i in &expired {
+                let winner = drawn.next();
+                let slot = &mut archived.slots[i];
+                slot.user = winner.map(|user| user.get());
+                slot.deadline = winner.map(|_| now + claim_hours * 60 * 60);
+                if let Some(winner) = winner {
+                    archived.winners.push(winner.get());
+                }
+            }
+            let winners: Vec<UserId> = expired
+                .iter()
+                .filter_map(|&i| archived.slots[i].user.map(UserId::new))
+                .collect();
+            let assigned = assign_codes(&mut archived.giveaway.secret_codes, &winners);
+            Some((archived.clone(), expired, assigned))
+        })?;
+        let Some((archived, rerolled, assigned)) = result else {
+            return Ok(());
+        };
+        let winners: Vec<UserId> = rerolled
+            .iter()
+            .filter_map(|&i| archived.slots[i].user.map(UserId::new))
+            .collect();
+        record_wins(&db, guild, &winners)?;
+        let language = archived.giveaway.style.language;
+        let grants = grant_winner_role(
+            &http,
+            guild,
+            archived.giveaway.winner_role,
+            &winners,
+            language,
+        )
+        .await;
+        add_role_grants(&db, &http, guild, id, grants)?;
+        send_codes(
+            &http,
+            &archived.giveaway.title,
+            archived.giveaway.style.language,
+            archived.giveaway.host.map(UserId::new),
+            assigned,
+        )
+        .await;
+        update_announcement(guild, id, &archived, &http).await?;
+        let lines: Vec<String> = rerolled
+            .into_iter()
+            .filter_map(|i| {
+                let slot = &archived.slots[i];
+                slot.user
+                    .map(|user| format!("\n{}. <@{user}>{}", i + 1, prize_str(slot)))
+            })
+            .collect();
+        if let Some((channel, announcement)) = archived.announcement
+            && !lines.is_empty()
+        {
+            let channel = ChannelId::new(channel);
+            channel
+                .send_message(
+                    &http,
+                    CreateMessage::new()
+                        .content(t!(
+                            language,
+                            "claim-rerolled",
+                            title = archived.giveaway.title,
+                            winners = lines.concat()
+                        ))
+                        .reference_message((channel, MessageId::new(announcement))),
+                )
+                .await?;
+        }
+    }
+}
+
+//  A missing role is no reason to fail the draw, e.g. if it was moved above the bot since
+async fn grant_winner_role(
+    http: &impl CacheHttp,
+    guild: GuildId,
+    winner_role: Option<WinnerRole>,
+    winners: &[UserId],
+    language: Language,
+) -> Vec<(u64, i64)> {
+    let Some(winner_role) = winner_role else {
+        return Vec::new();
+    };
+    let until = winner_role
+        .days
+        .map(|days| Utc::now().timestamp() + i64::from(days) * 24 * 60 * 60);
+    let mut grants = Vec::new();
+    for &winner in winners {
+        if let Err(err) = http
+            .http()
+            .add_member_role(
+                guild,
+                winner,
+                RoleId::new(winner_role.role),
+                Some(&t!(language, "winner-role-granted")),
+            )
+            .await
+        {
+            metrics().discord_error(&err);
+            error!("Error assigning winner role: {}", err);
+            continue;
+        }
+        if let Some(until) = until {
+            grants.push((winner.get(), until));
+        }
+    }
+    grants
+}
+
+//  For winners drawn after the giveaway was archived, that's also when the initial winners get it
+fn add_role_grants(
+    db: &Arc<Db>,
+    http: &MyHttpCache,
+    guild: GuildId,
+    id: GiveawayId,
+    grants: Vec<(u64, i64)>,
+) -> anyhow::Result<()> {
+    if grants.is_empty() {
+        return Ok(());
+    }
+    db_write(db, guild, move |state| {
+        if let Some(archived) = state.archive.get_mut(&id) {
+            archived.role_grants.extend(grants);
+        }
+    })?;
+    spawn_role_task(db, http, guild, id);
+    Ok(())
+}
+
+fn spawn_role_task(db: &Arc<Db>, http: &MyHttpCache, guild: GuildId, id: GiveawayId) {
+    let task = role_task(guild, id, db.clone(), http.clone());
+    db.spawn_for_guild(guild, async move {
+        if let Err(err) = task.await {
+            error!("Error removing the winner role of {id}: {}", err);
+        }
+    });
+}
+
+//  Takes the winner role away again, once its time is up
+async fn role_task(
+    guild: GuildId,
+    id: GiveawayId,
+    db: Arc<Db>,
+    http: MyHttpCache,
+) -> anyhow::Result<()> {
+    loop {
+        let archived = db_read(&db, guild)?.archive.remove(&id);
+        let Some((until, role, language)) = archived.and_then(|archived| {
+            let language = archived.giveaway.style.language;
+            archived
+                .next_role_removal()
+                .zip(archived.giveaway.winner_role)
+                .map(|(until, role)| (until, role, language))
+        }) else {
+            return Ok(());
+        };
+        let diff = until - Utc::now().timestamp();
+        if diff > 0 {
+            tokio::time::sleep(Duration::from_secs(diff as u64)).await;
+        }
+        let now = Utc::now().timestamp();
+        //  Another task may have removed them already
+        let due = db_write(&db, guild, move |state| {
+            let archived = state.archive.get_mut(&id)?;
+            let (due, rest) = archived
+                .role_grants
+                .drain(..)
+                .partition(|(_, until)| *until <= now);
+            archived.role_grants = rest;
+            Some(due)
+        })?
+        .unwrap_or_default();
+        for (user, _) in due {
+            if let Err(err) = http
+                .http()
+                .remove_member_role(
+                    guild,
+                    UserId::new(user),
+                    RoleId::new(role.role),
+                    Some(&t!(language, "winner-role-expired")),
+                )
+                .await
+            {
+                metrics().discord_error(&err);
+                error!("Error removing winner role: {}", err);
+            }
+        }
+    }
+}
+
+//  Every ticket is one chance to win, but a user can only win once
+fn draw_winners(
+    participants: &HashMap<UserId, Entry>,
+    count: usize,
+    exclude: &HashSet<UserId>,
+) -> Vec<UserId> {
+    let mut candidates: Vec<(UserId, u32)> = participants
+        .iter()
+        .filter(|(user, _)| !exclude.contains(user))
+        .map(|(user, entry)| (*user, entry.tickets))
+        .collect();
+    let count = count.min(candidates.len());
+    let mut rng = rand::rng();
+    let mut winners = Vec::with_capacity(count);
+    while winners.len() < count {
+        let Ok(index) = WeightedIndex::new(candidates.iter().map(|(_, tickets)| *tickets)) else {
+            break;
+        };
+        let (winner, _) = candidates.swap_remove(index.sample(&mut rng));
+        winners.push(winner);
+    }
+    winners
+}
+
+fn winners_str(heading: String, winners: &[UserId]) -> String {
+    let mut winners_str = heading;
+    for (i, winner) in winners.iter().enumerate() {
+        winners_str.push_str(&format!("\n{}. <@{winner}>", i + 1));
+    }
+    winners_str
+}
+
+fn prize_str(slot: &Slot) -> String {
+    match slot.prize.is_empty() {
+        true => String::new(),
+        false => format!(": {}", slot.prize),
+    }
+}
+
+//  Announcements outside of the giveaway's channel link to the giveaway
+fn announcement_str(guild: GuildId, channel: ChannelId, archived: &ArchivedGiveaway) -> String {
+    let giveaway: RealGiveaway = archived.giveaway.clone().into();
+    let language = giveaway.style.language;
+    let link = match channel == giveaway.channel {
+        true => String::new(),
+        false => format!("\n{}", giveaway.message.link(giveaway.channel, Some(guild))),
+    };
+    if archived.slots.is_empty() {
+        return t!(language, "announcement-empty", title = giveaway.title, link);
+    }
+    let mut content = t!(language, "announcement", title = giveaway.title, link);
+    for (i, slot) in archived.slots.iter().enumerate() {
+        let user = match slot.user {
+            Some(user) => format!("<@{user}>"),
+            None => t!(language, "announcement-nobody"),
+        };
+        let status = match (slot.claimed, slot.deadline) {
+            (true, _) => " ✅".to_string(),
+            (false, Some(deadline)) if slot.user.is_some() => t!(
+                language,
+                "announcement-claim-until",
+                time = format!("<t:{deadline}:f>")
+            ),
+            _ => String::new(),
+        };
+        content.push_str(&format!("\n{}. {user}{}{status}", i + 1, prize_str(slot)));
+    }
+    let ended = DateTime::from_timestamp(archived.ended, 0)
+        .unwrap()
+        .to_utc();
+    if !giveaway.style.anonymous {
+        content.push_str(&stats_str(&giveaway, ended));
+    }
+    if archived.next_deadline().is_some() {
+        content.push_str(&t!(language, "announcement-claim-reroll"));
+    }
+    if let Some(draw) = &archived.draw {
+        content.push_str(&t!(
+            language,
+            "announcement-draw",
+            seed = draw.seed,
+            hash = draw.hash
+        ));
+    }
+    content
+}
+
+fn announcement_buttons(id: GiveawayId, archived: &ArchivedGiveaway) -> Vec<CreateActionRow> {
+    if archived.slots.is_empty() {
+        return Vec::new();
+    }
+    let language = archived.giveaway.style.language;
+    let mut buttons = Vec::new();
+    if archived.next_deadline().is_some() {
+        buttons.push(
+            CreateButton::new(serde_json::to_string(&UserAction::Claim(id)).unwrap())
+                .label(t!(language, "button-claim"))
+                .style(poise::serenity_prelude::ButtonStyle::Success),
+        );
+    }
+    buttons.push(reroll_button(id, language));
+    vec![CreateActionRow::Buttons(buttons)]
+}
+
+//  Announcements are edited without pinging the winners again
+async fn update_announcement(
+    guild: GuildId,
+    id: GiveawayId,
+    archived: &ArchivedGiveaway,
+    http: &impl CacheHttp,
+) -> anyhow::Result<()> {
+    let Some((channel, announcement)) = archived.announcement else {
+        return Ok(());
+    };
+    let channel = ChannelId::new(channel);
+    channel
+        .edit_message(
+            http,
+            MessageId::new(announcement),
+            EditMessage::new()
+                .content(announcement_str(guild, channel, archived))
+                .components(announcement_buttons(id, archived)),
+        )
+        .await?;
+    Ok(())
+}
+
+fn stats_str(giveaway: &RealGiveaway, ended: DateTime<Utc>) -> String {
+    let language = giveaway.style.language;
+    let participants = giveaway.participants.len();
+    let mut stats = t!(language, "announcement-stats", participants);
+    //  Bonus entries add up, so a u32 might not hold the sum
+    let tickets: u64 = giveaway
+        .participants
+        .values()
+        .map(|e| u64::from(e.tickets))
+        .sum();
+    if tickets != participants as u64 {
+        stats.push_str(&t!(language, "announcement-stats-tickets", tickets));
+    }
+    if let Some(created) = giveaway.created {
+        stats.push_str(&t!(
+            language,
+            "announcement-stats-duration",
+            duration = format_duration(ended - created, language)
+        ));
+    }
+    //  Most entries within any hour, entries from older versions have no time
+    let mut entered: Vec<i64> = giveaway
+        .participants
+        .values()
+        .map(|e| e.entered)
+        .filter(|&ts| ts > 0)
+        .collect();
+    entered.sort_unstable();
+    let mut peak = 0;
+    let mut first = 0;
+    for (last, ts) in entered.iter().enumerate() {
+        while ts - entered[first] >= 60 * 60 {
+            first += 1;
+        }
+        peak = peak.max(last - first + 1);
+    }
+    if peak > 1 {
+        stats.push_str(&t!(language, "announcement-stats-peak", peak));
+    }
+    stats
+}
+
+fn reroll_button(id: GiveawayId, language: Language) -> CreateButton {
+    CreateButton::new(serde_json::to_string(&UserAction::Reroll(id)).unwrap())
+        .label(t!(language, "button-reroll"))
+        .style(poise::serenity_prelude::ButtonStyle::Secondary)
+}
+
+async fn draw_fairly(
+    db: &Db,
+    guild: GuildId,
+    giveaway: &RealGiveaway,
+    http: &impl CacheHttp,
+) -> anyhow::Result<(Vec<UserId>, Draw)> {
+    let mut exclude = recent_winners(db, guild, giveaway.requirements.win_cooldown)?;
+    //  Giveaways from older versions didn't choose their seed in advance
+    let (seed, salt) = match &giveaway.commitment {
+        Some(commitment) => reveal(commitment)?,
+        None => (rand::random(), new_salt()),
+    };
+    //  The excluded members are part of the draw, so it can be repeated with the same seed
+    loop {
+        let (winners, draw) = fair_draw(
+            &giveaway.participants,
+            giveaway.prizes.total() as usize,
+            &exclude,
+            seed,
+            salt.clone(),
+        );
+        let absent = absent_members(http, guild, &winners).await?;
+        if absent.is_empty() {
+            return Ok((winners, draw));
+        }
+        exclude.extend(absent);
     }
-    Ok(())
 }
 
-async fn add_user(
+//  Winners drawn before a restart or a failure are kept, so are the steps completed with them.
+//  The giveaway stays running until it's archived
+async fn finish_giveaway(
+    db: &Db,
     guild: GuildId,
     id: GiveawayId,
-    user: UserId,
-    db: &Database,
-) -> anyhow::Result<bool> {
-    let success = db_write(db, guild, move |state| {
-        state
-            .giveaways
-            .get_mut(&id)
-            .map(|giveaway| giveaway.participants.insert(user.get()))
-            .unwrap_or(false)
-    })?;
-    Ok(success)
+    giveaway: &mut RealGiveaway,
+    http: &impl CacheHttp,
+) -> anyhow::Result<ArchivedGiveaway> {
+    //  Entries confirmed until now take part in the draw, later ones are refused
+    db.entries().close(db, guild, id)?;
+    if let Some(stored) = db_read(db, guild)?.giveaways.remove(&id) {
+        giveaway.participants = RealGiveaway::from(stored).participants;
+    }
+    let giveaway = &*giveaway;
+    let finishing = match finishing(db, guild, id)? {
+        Some(finishing) => finishing,
+        None => {
+            let (winners, draw) = draw_fairly(db, guild, giveaway, http).await?;
+            let finishing = Finishing {
+                drawn_winners: winners.iter().map(|winner| winner.get()).collect(),
+                draw,
+                done: Vec::new(),
+            };
+            mark_finishing(db, guild, id, &finishing)?;
+            finishing
+        }
+    };
+    let winners = finishing.winners();
+    let mut archived = ArchivedGiveaway::new(giveaway.clone().into(), &winners);
+    archived.draw = Some(finishing.draw.clone());
+    //  Stored with the archive, they are only sent afterwards by complete_finish
+    assign_codes(&mut archived.giveaway.secret_codes, &winners);
+    giveaway
+        .channel
+        .edit_message(
+            http,
+            giveaway.message,
+            giveaway.view().edit_message(true).components(Vec::new()),
+        )
+        .await?;
+    edit_mirrors(
+        giveaway,
+        giveaway.view().edit_message(true).components(Vec::new()),
+        http,
+    )
+    .await;
+    archived.announcement = match finishing.announcement() {
+        Some(announcement) => Some(announcement),
+        None => {
+            let channel = announcement_channel(db, guild)?.unwrap_or(giveaway.channel);
+            let mut message = CreateMessage::new()
+                .content(announcement_str(guild, channel, &archived))
+                .components(announcement_buttons(id, &archived));
+            //  Replies only work within the same channel
+            if channel == giveaway.channel {
+                message = message.reference_message((giveaway.channel, giveaway.message));
+            }
+            let message = channel.send_message(http, message).await?;
+            let announcement = (message.channel_id.get(), message.id.get());
+            complete_step(
+                db,
+                guild,
+                id,
+                Step::Announced(announcement.0, announcement.1),
+            )?;
+            Some(announcement)
+        }
+    };
+    record_wins_once(db, guild, id)?;
+    Ok(archived)
+}
+
+fn announcement_channel(db: &Db, guild: GuildId) -> anyhow::Result<Option<ChannelId>> {
+    Ok(db.settings(guild)?.announcement_channel.map(ChannelId::new))
+}
+
+//  Ends the giveaways one after another, failed ones are put back like for a single giveaway
+async fn end_all_giveaways(
+    db: &Arc<Db>,
+    guild: GuildId,
+    cancel: bool,
+    language: Language,
+    ctx: &poise::serenity_prelude::Context,
+) -> anyhow::Result<String> {
+    let ids: Vec<GiveawayId> = db_read(db, guild)?.giveaways.into_keys().collect();
+    let http = MyHttpCache::new(ctx.http.clone(), ctx.cache.clone());
+    let mut lines = Vec::new();
+    for id in ids {
+        //  Finished giveaways are only taken out when they're archived
+        let giveaway: Option<RealGiveaway> = match cancel {
+            true => db_write(db, guild, move |state| state.giveaways.remove(&id))?,
+            false => db_read(db, guild)?.giveaways.remove(&id),
+        }
+        .map(|v| v.into());
+        let Some(mut giveaway) = giveaway else {
+            continue;
+        };
+        let title = giveaway.title.clone();
+        let result = match cancel {
+            true => cancel_giveaway(&giveaway, None, false, ctx).await,
+            false => finish_giveaway(db, guild, id, &mut giveaway, ctx).await,
+        };
+        let status = match (&result, cancel) {
+            (Ok(_), true) => t!(language, "end-all-cancelled"),
+            (Ok(_), false) => t!(language, "end-all-ended"),
+            (Err(_), _) => t!(language, "end-all-failed"),
+        };
+        lines.push(format!("\n- `{id}` **{title}**: {status}"));
+        archive_or_restore(db, &http, guild, id, giveaway, result)?;
+    }
+    let mut report = match (lines.len(), cancel) {
+        (0, _) => t!(language, "list-none"),
+        (count, true) => t!(language, "end-all-report-cancelled", count),
+        (count, false) => t!(language, "end-all-report-ended", count),
+    };
+    for line in lines {
+        //  Discord rejects messages longer than 2000 characters
+        if report.chars().count() + line.chars().count() > 1950 {
+            report.push_str("\n...");
+            break;
+        }
+        report.push_str(&line);
+    }
+    Ok(report)
+}
+
+//  Members, that left the server, can't win, in case their leave was missed
+async fn absent_members(
+    http: &impl CacheHttp,
+    guild: GuildId,
+    users: &[UserId],
+) -> anyhow::Result<Vec<UserId>> {
+    let mut absent = Vec::new();
+    for &user in users {
+        match guild.member(http, user).await {
+            Ok(_) => {}
+            Err(poise::serenity_prelude::Error::Http(
+                poise::serenity_prelude::HttpError::UnsuccessfulRequest(ErrorResponse {
+                    error: DiscordJsonError { code: 10007, .. },
+                    ..
+                }),
+            )) => {
+                //  Unknown member
+                absent.push(user);
+            }
+            Err(err) => Err(err)?,
+        }
+    }
+    Ok(absent)
 }
 
-//  Returns true, if the user was removed and false, if the user wasn't a participant
-async fn remove_user(
+//  Returns None, if the giveaway isn't in the archive (anymore) or was cancelled
+async fn reroll_giveaway(
     guild: GuildId,
     id: GiveawayId,
-    user: UserId,
-    db: &Database,
-) -> anyhow::Result<bool> {
-    let success = db_write(db, guild, move |state| {
-        state
-            .giveaways
-            .get_mut(&id)
-            .map(|giveaway| giveaway.participants.remove(&user.get()))
-            .unwrap_or(false)
+    count: usize,
+    db: &Arc<Db>,
+    http: &MyHttpCache,
+) -> anyhow::Result<Option<Vec<UserId>>> {
+    let archived = db_read(db, guild)?.archive.remove(&id);
+    let Some(archived) = archived.filter(|archived| !archived.cancelled) else {
+        return Ok(None);
+    };
+    let mut previous = recent_winners(db, guild, archived.giveaway.requirements.win_cooldown)?;
+    previous.extend(archived.winners.iter().map(|&u| UserId::new(u)));
+    let giveaway: RealGiveaway = archived.giveaway.into();
+    let winners = loop {
+        let winners = draw_winners(&giveaway.participants, count, &previous);
+        let absent = absent_members(http, guild, &winners).await?;
+        if absent.is_empty() {
+            break winners;
+        }
+        previous.extend(absent);
+    };
+    let new_winners: Vec<u64> = winners.iter().map(|u| u.get()).collect();
+    let now = Utc::now().timestamp();
+    let updated = db_write(db, guild, |state| {
+        let archived = state.archive.get_mut(&id)?;
+        //  Rerolled winners have to claim their prize like the ones drawn at the end
+        let claim_hours = archived.giveaway.claim_hours;
+        let deadline = (claim_hours > 0).then(|| now + i64::from(claim_hours) * 60 * 60);
+        archived.slots.extend(new_winners.iter().map(|&user| Slot {
+            prize: String::new(),
+            user: Some(user),
+            deadline,
+            claimed: false,
+        }));
+        archived.winners.extend(new_winners);
+        let assigned = assign_codes(&mut archived.giveaway.secret_codes, &winners);
+        Some((archived.clone(), assigned))
     })?;
-    Ok(success)
+    let Some((archived, assigned)) = updated else {
+        return Ok(None);
+    };
+    let language = giveaway.style.language;
+    let mut message = CreateMessage::new().reference_message((giveaway.channel, giveaway.message));
+    message = match winners.is_empty() {
+        true => message.content(t!(language, "reroll-empty", title = giveaway.title)),
+        false => message
+            .content(format!(
+                "# {}\n\n{}",
+                giveaway.title,
+                winners_str(t!(language, "reroll-heading"), &winners)
+            ))
+            .components(vec![CreateActionRow::Buttons(vec![reroll_button(
+                id, language,
+            )])]),
+    };
+    giveaway.channel.send_message(http, message).await?;
+    record_wins(db, guild, &winners)?;
+    let grants = grant_winner_role(http, guild, giveaway.winner_role, &winners, language).await;
+    add_role_grants(db, http, guild, id, grants)?;
+    send_codes(
+        http,
+        &giveaway.title,
+        giveaway.style.language,
+        giveaway.host,
+        assigned,
+    )
+    .await;
+    update_announcement(guild, id, &archived, http).await?;
+    //  The claim task of the giveaway might have ended already, a second one finds nothing to do
+    if !winners.is_empty() && archived.next_deadline().is_some() {
+        let task = claim_task(guild, id, db.clone(), http.clone());
+        db.spawn_for_guild(guild, async move { task.await.unwrap() });
+    }
+    Ok(Some(winners))
 }
 
-async fn finish_task(
+//  Until the giveaway is reposted or archived, it isn't in the state
+async fn giveaway_message_deleted(
+    db: &Arc<Db>,
+    http: &MyHttpCache,
     guild: GuildId,
-    id: GiveawayId,
-    time: DateTime<Utc>,
-    db: Arc<Database>,
-    http: impl CacheHttp,
+    channel: ChannelId,
+    message: MessageId,
 ) -> anyhow::Result<()> {
-    let now = chrono::Utc::now();
-    let diff = time.timestamp() - now.timestamp();
-    if diff > 0 {
-        tokio::time::sleep(Duration::from_secs(diff as u64)).await;
-    }
-    let giveaway: Option<RealGiveaway> =
-        db_write(&db, guild, move |state| state.giveaways.remove(&id))?.map(|v| v.into());
-    if let Some(giveaway) = giveaway
-        && giveaway.time.as_ref().is_some_and(|dt| dt == &time)
-    {
-        if let Err(err) = finish_giveaway(&giveaway, &http).await {
-            eprintln!("Error finishing giveaway: {}", err);
-            let giveaway: Giveaway = giveaway.into();
-            db_write(&db, guild, move |state| {
-                state.giveaways.insert(id, giveaway)
-            })?;
+    let _guard = db.lock_guild(guild).await;
+    let (data, repost) = db_write(db, guild, move |state| {
+        //  A deleted mirror is just dropped, the giveaway goes on
+        let key = (channel.get(), message.get());
+        for ga in state.giveaways.values_mut() {
+            ga.mirrors.retain(|&mirror| mirror != key);
+        }
+        let data = state
+            .giveaways
+            .iter()
+            .find(|(_, ga)| ga.channel == channel.get() && ga.message == message.get())
+            .map(|(id, _)| *id)
+            .and_then(|id| state.giveaways.remove(&id).map(|ga| (id, ga)));
+        (data, state.settings.repost_deleted)
+    })?;
+    let Some((id, giveaway)) = data else {
+        return Ok(());
+    };
+    let mut giveaway: RealGiveaway = giveaway.into();
+    if repost {
+        match repost_giveaway(id, &mut giveaway, http).await {
+            Ok(()) => {
+                let giveaway: Giveaway = giveaway.into();
+                db_write(db, guild, move |state| {
+                    state.giveaways.entry(id).or_insert(giveaway);
+                })?;
+                return Ok(());
+            }
+            Err(err) => {
+                metrics().discord_error(err.as_ref());
+                error!("Error reposting giveaway: {}", err);
+            }
         }
     }
+    //  Nobody could be asked for a reason here
+    let result = cancel_giveaway(&giveaway, None, false, http).await;
+    archive_or_restore(db, http, guild, id, giveaway, result)?;
     Ok(())
 }
 
-async fn finish_giveaway(giveaway: &RealGiveaway, http: &impl CacheHttp) -> anyhow::Result<()> {
-    let winners_count = min(giveaway.winners as usize, giveaway.participants.len());
-    let mut winners: HashSet<UserId> = HashSet::with_capacity(winners_count);
-    while winners.len() < winners_count {
-        winners.insert(
-            *giveaway
-                .participants
-                .iter()
-                .choose(&mut rand::rng())
-                .unwrap(),
-        );
-    }
-    let mut winners_str = "Gewinner:".to_string();
-    for (i, winner) in winners.into_iter().enumerate() {
-        winners_str.push_str(&format!("\n{}. <@{winner}>", i + 1));
-    }
-    if winners_count == 0 {
-        winners_str = "Keine Teilnehmer".to_string();
-    }
-    giveaway
-        .channel
-        .edit_message(
-            http,
-            giveaway.message,
-            EditMessage::new()
-                .content(giveaway.get_message(true))
-                .components(Vec::new()),
-        )
-        .await?;
-    giveaway
+//  Participants are kept, only the message changes
+async fn repost_giveaway(
+    id: GiveawayId,
+    giveaway: &mut RealGiveaway,
+    http: &MyHttpCache,
+) -> anyhow::Result<()> {
+    let message = giveaway
         .channel
         .send_message(
             http,
-            CreateMessage::new()
-                .content(format!("# {}\n\n{}", giveaway.title, winners_str))
-                .reference_message((giveaway.channel, giveaway.message)),
+            giveaway
+                .view()
+                .create_message(false)
+                .allowed_mentions(CreateAllowedMentions::new())
+                .components(vec![giveaway_buttons(id, &giveaway.style)]),
         )
         .await?;
+    giveaway.message = message.id;
+    if giveaway.style.reactions {
+        add_entry_reaction(http, giveaway.channel, message.id).await?;
+    }
     Ok(())
 }
 
-async fn cancel_giveaway(giveaway: &RealGiveaway, http: &impl CacheHttp) -> anyhow::Result<()> {
-    let reply = match giveaway
-        .channel
-        .edit_message(
-            http,
-            giveaway.message,
-            EditMessage::new()
-                .content(giveaway.get_message(true))
-                .components(Vec::new()),
-        )
-        .await
-    {
+//  Deleting the message leaves no trace in the channel, e.g. if the giveaway was posted by mistake
+async fn cancel_giveaway(
+    giveaway: &RealGiveaway,
+    reason: Option<String>,
+    delete: bool,
+    http: &impl CacheHttp,
+) -> anyhow::Result<ArchivedGiveaway> {
+    let result = match delete {
+        true => {
+            giveaway
+                .channel
+                .delete_message(http.http(), giveaway.message)
+                .await
+        }
+        false => giveaway
+            .channel
+            .edit_message(
+                http,
+                giveaway.message,
+                giveaway.view().edit_message(true).components(Vec::new()),
+            )
+            .await
+            .map(|_| ()),
+    };
+    for &(channel, message) in &giveaway.mirrors {
+        let result = match delete {
+            true => channel.delete_message(http.http(), message).await,
+            false => channel
+                .edit_message(
+                    http,
+                    message,
+                    giveaway.view().edit_message(true).components(Vec::new()),
+                )
+                .await
+                .map(|_| ()),
+        };
+        if let Err(err) = result {
+            metrics().discord_error(&err);
+            error!("Error cancelling mirror: {}", err);
+        }
+    }
+    let reply = match result {
         Ok(_) => true,
         Err(poise::serenity_prelude::Error::Http(
             poise::serenity_prelude::HttpError::UnsuccessfulRequest(ErrorResponse {
-                error: DiscordJsonError { code: 10008, .. },
+                error:
+                    DiscordJsonError {
+                        code: 10003 | 10008,
+                        ..
+                    },
                 ..
             }),
         )) => {
-            //  Message not found, ignore
+            //  Message or channel not found, ignore
             false
         }
         Err(err) => Err(err)?,
     };
-    if reply {
+    if reply && !delete {
         giveaway
             .channel
             .send_message(
                 http,
                 CreateMessage::new()
-                    .content(format!(
-                        "# {}\n\nDieses Giveaway wurde abgebrochen",
-                        giveaway.title
-                    ))
+                    .content(match &reason {
+                        Some(reason) => t!(
+                            giveaway.style.language,
+                            "giveaway-cancelled-reason",
+                            title = giveaway.title,
+                            reason
+                        ),
+                        None => t!(
+                            giveaway.style.language,
+                            "giveaway-cancelled",
+                            title = giveaway.title
+                        ),
+                    })
                     .reference_message((giveaway.channel, giveaway.message)),
             )
             .await?;
     }
+    Ok(ArchivedGiveaway::cancelled(giveaway.clone().into(), reason))
+}
+
+fn parse_time_arg(time: &str, tz: Tz, default_time: NaiveTime) -> anyhow::Result<ParsedTime> {
+    parse_time(time, tz, default_time).map_err(|err| match dst_hint(time, tz, default_time) {
+        Some(hint) => hint.into(),
+        None => parse_error(err),
+    })
+}
+
+//  Kept as `TimeError`, so the reply can explain it in the language of the guild
+fn parse_error(err: TimeError) -> anyhow::Error {
+    err.into()
+}
+
+//  Accepts role mentions or ids, separated by spaces or commas. Ids are never 0, serenity panics
+//  on those.
+fn parse_roles(roles: &str) -> anyhow::Result<Vec<u64>> {
+    roles
+        .split([' ', ','])
+        .filter(|part| !part.is_empty())
+        .map(|part| {
+            parse_role_mention(part)
+                .map(|role| role.get())
+                .or_else(|| part.parse().ok().map(NonZeroU64::get))
+                .ok_or_else(|| localized!("invalid-role", part).into())
+        })
+        .collect()
+}
+
+fn parse_users(users: &str) -> anyhow::Result<Vec<u64>> {
+    users
+        .split([' ', ','])
+        .filter(|part| !part.is_empty())
+        .map(|part| {
+            parse_user_mention(part)
+                .map(|user| user.get())
+                .or_else(|| part.parse().ok().map(NonZeroU64::get))
+                .ok_or_else(|| localized!("invalid-user", part).into())
+        })
+        .collect()
+}
+
+fn parse_channels(channels: &str) -> anyhow::Result<Vec<ChannelId>> {
+    channels
+        .split([' ', ','])
+        .filter(|part| !part.is_empty())
+        .map(|part| {
+            parse_channel_mention(part)
+                .or_else(|| part.parse().ok())
+                .ok_or_else(|| localized!("invalid-channel", part).into())
+        })
+        .collect()
+}
+
+//  Mirrors are copies in further channels, a channel failing doesn't stop the giveaway
+async fn post_mirrors(
+    id: GiveawayId,
+    view: &GiveawayMessage<'_>,
+    channels: &[ChannelId],
+    http: &impl CacheHttp,
+) -> Vec<(ChannelId, MessageId)> {
+    let mut mirrors = Vec::new();
+    for &channel in channels {
+        let result = channel
+            .send_message(
+                http,
+                view.create_message(false)
+                    .allowed_mentions(CreateAllowedMentions::new())
+                    .components(vec![giveaway_buttons(id, view.style)]),
+            )
+            .await;
+        match result {
+            Ok(message) => {
+                if view.style.reactions
+                    && let Err(err) = add_entry_reaction(http, channel, message.id).await
+                {
+                    metrics().discord_error(err.as_ref());
+                    error!("Error adding entry reaction: {}", err);
+                }
+                mirrors.push((channel, message.id));
+            }
+            Err(err) => {
+                metrics().discord_error(&err);
+                error!("Error posting mirror: {}", err);
+            }
+        }
+    }
+    mirrors
+}
+
+//  The original message is handled by the caller
+async fn edit_mirrors(giveaway: &RealGiveaway, edit: EditMessage, http: &impl CacheHttp) {
+    for (channel, message) in &giveaway.mirrors {
+        if let Err(err) = channel.edit_message(http, *message, edit.clone()).await {
+            metrics().discord_error(&err);
+            error!("Error editing mirror: {}", err);
+        }
+    }
+}
+
+//  Giveaways with reaction entry only get the buttons for moderators
+fn giveaway_buttons(id: GiveawayId, style: &Style) -> CreateActionRow {
+    let language = style.language;
+    let mut buttons = Vec::new();
+    if !style.reactions {
+        buttons.push(custom_button(
+            UserAction::Add(id),
+            style.buttons.get(GiveawayButton::Join),
+            &GiveawayButton::Join.label(language),
+            ButtonStyle::Success,
+        ));
+        buttons.push(custom_button(
+            UserAction::Remove(id),
+            style.buttons.get(GiveawayButton::Leave),
+            &GiveawayButton::Leave.label(language),
+            ButtonStyle::Danger,
+        ));
+    }
+    buttons.extend([
+        custom_button(
+            UserAction::Cancel(id),
+            style.buttons.get(GiveawayButton::Cancel),
+            &GiveawayButton::Cancel.label(language),
+            ButtonStyle::Secondary,
+        ),
+        custom_button(
+            UserAction::Finish(id),
+            style.buttons.get(GiveawayButton::Finish),
+            &GiveawayButton::Finish.label(language),
+            ButtonStyle::Secondary,
+        ),
+        CreateButton::new(serde_json::to_string(&UserAction::Participants(id, None)).unwrap())
+            .label(t!(language, "button-participants"))
+            .style(ButtonStyle::Secondary),
+    ]);
+    CreateActionRow::Buttons(buttons)
+}
+
+fn custom_button(
+    action: UserAction,
+    custom: &CustomButton,
+    label: &str,
+    color: ButtonStyle,
+) -> CreateButton {
+    let mut button = CreateButton::new(serde_json::to_string(&action).unwrap())
+        .label(custom.label.as_deref().unwrap_or(label))
+        .style(custom.color.map(ButtonStyle::from).unwrap_or(color));
+    //  Emojis are checked when they are set, so this only fails for deleted custom emojis
+    if let Some(emoji) = custom
+        .emoji
+        .clone()
+        .and_then(|emoji| ReactionType::try_from(emoji).ok())
+    {
+        button = button.emoji(emoji);
+    }
+    button
+}
+
+//  Without the first reaction members would have to pick the emoji themselves
+async fn add_entry_reaction(
+    http: &impl CacheHttp,
+    channel: ChannelId,
+    message: MessageId,
+) -> anyhow::Result<()> {
+    channel
+        .create_reaction(
+            http.http(),
+            message,
+            ReactionType::Unicode(ENTRY_EMOJI.to_string()),
+        )
+        .await?;
     Ok(())
 }
 
+//  Only giveaways with reaction entry are considered
+fn reaction_giveaway(
+    db: &Db,
+    guild: GuildId,
+    reaction: &Reaction,
+) -> anyhow::Result<Option<GiveawayId>> {
+    Ok(db_read(db, guild)?
+        .giveaways
+        .into_iter()
+        .find(|(_, ga)| {
+            let key = (reaction.channel_id.get(), reaction.message_id.get());
+            ga.style.reactions && ((ga.channel, ga.message) == key || ga.mirrors.contains(&key))
+        })
+        .map(|(id, _)| id))
+}
+
 #[poise::command(
     slash_command,
     default_member_permissions = "CREATE_EVENTS",
     guild_only
 )]
+#[allow(clippy::too_many_arguments)]
 async fn create(
-    ctx: Context<'_, Arc<Database>, anyhow::Error>,
+    ctx: Context<'_, Arc<Db>, anyhow::Error>,
     title: String,
     description: String,
-    #[min = 1] winners: Option<u32>,
-    time: Option<String>,
+    #[min = 1]
+    #[max = 100]
+    winners: Option<u32>,
+    prizes: Option<String>,
+    #[autocomplete = "time_autocomplete"] time: Option<String>,
+    required_role: Option<RoleId>,
+    #[min = 1] min_member_since: Option<u32>,
+    start: Option<String>,
+    #[min = 1] min_messages: Option<u32>,
+    #[min = 1]
+    #[max = 90]
+    messages_days: Option<u32>,
+    image: Option<String>,
+    color: Option<String>,
+    claim_hours: Option<u32>,
+    host: Option<UserId>,
+    #[channel_types("Text", "News")] channel: Option<GuildChannel>,
+    #[min = 1] no_repeat_days: Option<u32>,
+    ping: Option<bool>,
+    reactions: Option<bool>,
+    boosters_only: Option<bool>,
+    excluded_roles: Option<String>,
+    notify_losers: Option<bool>,
+    #[min = 1] min_participants: Option<u32>,
+    extend_by: Option<String>,
+    #[min = 1] max_extensions: Option<u32>,
+    winner_role: Option<RoleId>,
+    #[min = 1] winner_role_days: Option<u32>,
+    secret_codes: Option<bool>,
+    #[min = 1] min_invites: Option<u32>,
+    anonymous: Option<bool>,
+    scheduled_event: Option<bool>,
+    channels: Option<String>,
+    #[max_length = 45] question: Option<String>,
+    answers: Option<String>,
 ) -> anyhow::Result<()> {
     ctx.defer().await?;
     let guild = ctx.guild_id().context("Not in a guild")?;
-    let channel = ctx.channel_id();
-    let winners = winners.unwrap_or(1);
     let db = ctx.data();
-    let tz: Tz = {
-        let db_read = db.begin_read()?;
-        let table = db_read.open_table(TABLE)?;
-        table
-            .get(guild.get())?
-            .map(|v| v.value())
-            .unwrap_or_default()
-            .timezone
-            .parse()?
-    };
-    let time: Option<DateTime<Utc>> = if let Some(time) = time {
-        Some(parse_time(&time, tz).map_err(|err| {
-            anyhow::Error::msg(format!(
-                "Fehler beim parsen der Zeit: {} --- {}",
-                &time[..(time.len() - err.len())],
-                err
-            ))
-        })?)
-    } else {
-        None
+    let settings = db.settings(guild)?;
+    if let Some(max) = settings.max_giveaways {
+        let state = db_read(db, guild)?;
+        if state.giveaways.len() + state.drafts.len() >= max as usize {
+            anyhow::bail!(localized!("create-max-giveaways", max));
+        }
+    }
+    if ping == Some(true) && !may_ping(ctx, settings.ping_role).await {
+        anyhow::bail!(localized!("create-ping-forbidden"));
+    }
+    let prizes = match prizes {
+        Some(prizes) => {
+            let prizes: Prizes = prizes.parse()?;
+            //  The winners are given by the prizes, a different count would be dropped silently
+            if let Some(winners) = winners
+                && winners != prizes.total()
+            {
+                anyhow::bail!(localized!(
+                    "create-winners-prizes-mismatch",
+                    winners,
+                    total = prizes.total()
+                ));
+            }
+            prizes
+        }
+        None => Prizes::unnamed(winners.or(settings.winners).unwrap_or(1)),
+    };
+    let tz = settings.timezone;
+    let default_time = settings.default_time();
+    //  A range like "vom 3.1. 18:00 bis 5.1. 20:00" sets start and end at once
+    let range = match (&start, &time) {
+        (None, Some(time)) => parse_time_range(time, tz, default_time).ok(),
+        _ => None,
+    };
+    let (start, time) = match range {
+        Some((start, end)) => (Some(start), Some(end)),
+        None => (
+            start
+                .map(|start| parse_time_arg(&start, tz, default_time))
+                .transpose()?,
+            time.map(|time| parse_time_arg(&time, tz, default_time))
+                .transpose()?,
+        ),
     };
+    //  Dates without a time are mentioned in the confirmation, so the default isn't a surprise
+    let language = settings.language;
+    let default_note = match [start, time]
+        .into_iter()
+        .flatten()
+        .any(|time| time.grammar_used == Grammar::DateOnly)
+    {
+        true => t!(
+            language,
+            "create-default-time",
+            time = default_time.format("%H:%M")
+        ),
+        false => String::new(),
+    };
+    let start = start.map(|start| start.utc);
+    let time = match time {
+        Some(time) => Some(time.utc),
+        //  The default duration counts from the start, if the giveaway is scheduled
+        None => settings.duration.and_then(|secs| {
+            start
+                .unwrap_or(Utc::now())
+                .checked_add_signed(TimeDelta::seconds(secs))
+        }),
+    };
+    if let (Some(start), Some(time)) = (start, time)
+        && start >= time
+    {
+        anyhow::bail!(localized!("create-start-after-end"));
+    }
+    //  Discord only takes http(s) URLs for images and would reject the message otherwise
+    if let Some(image) = &image
+        && !reqwest::Url::parse(image)
+            .is_ok_and(|url| matches!(url.scheme(), "http" | "https") && url.has_host())
+    {
+        anyhow::bail!(localized!("create-invalid-image"));
+    }
+    let extend_by = match extend_by {
+        Some(extend_by) => parse_duration(&extend_by).map_err(parse_error)?,
+        None => TimeDelta::days(1),
+    };
+    if extend_by <= TimeDelta::zero() {
+        anyhow::bail!(localized!("create-extension-negative"));
+    }
+    let auto_extend = min_participants.map(|min_participants| AutoExtend {
+        min_participants,
+        extend_by: extend_by.num_seconds(),
+        max_extensions: max_extensions.unwrap_or(3),
+        extensions: 0,
+    });
+    if let Some(role) = winner_role {
+        check_role(ctx, role).await?;
+    }
+    let winner_role = winner_role.map(|role| WinnerRole {
+        role: role.get(),
+        days: winner_role_days,
+    });
     let id: GiveawayId = GiveawayId(rand::random());
-    let content = RealGiveaway::get_message_early(&title, &description, time.as_ref(), false);
-    let ar = CreateActionRow::Buttons(Vec::from([
-        CreateButton::new(serde_json::to_string(&UserAction::Add(id)).unwrap())
-            .label("Dabei")
-            .style(poise::serenity_prelude::ButtonStyle::Success),
-        CreateButton::new(serde_json::to_string(&UserAction::Remove(id)).unwrap())
-            .label("Raus")
-            .style(poise::serenity_prelude::ButtonStyle::Danger),
-        CreateButton::new(serde_json::to_string(&UserAction::Cancel(id)).unwrap())
-            .label("Abbrechen")
-            .style(poise::serenity_prelude::ButtonStyle::Secondary),
-        CreateButton::new(serde_json::to_string(&UserAction::Finish(id)).unwrap())
-            .label("Abschließen")
-            .style(poise::serenity_prelude::ButtonStyle::Secondary),
-    ]));
-    let message = ctx
-        .send(
-            CreateReply::default()
-                .content(content)
+    let host = host.unwrap_or(ctx.author().id);
+    let requirements = Requirements {
+        role: required_role.map(|role| role.get()).or(settings.role),
+        min_member_days: min_member_since,
+        min_messages: min_messages.map(|messages| (messages, messages_days.unwrap_or(30))),
+        win_cooldown: no_repeat_days,
+        boosters_only: boosters_only.unwrap_or(false),
+        excluded_roles: excluded_roles
+            .as_deref()
+            .map(parse_roles)
+            .transpose()?
+            .unwrap_or_default(),
+        min_invites,
+    };
+    let question = match (question, answers) {
+        (Some(text), Some(answers)) => Some(EntryQuestion {
+            text,
+            answers: answers
+                .split(';')
+                .map(|answer| answer.trim().to_string())
+                .filter(|answer| !answer.is_empty())
+                .collect(),
+        }),
+        (None, None) => None,
+        _ => anyhow::bail!(localized!("create-question-incomplete")),
+    };
+    if question
+        .as_ref()
+        .is_some_and(|question| question.answers.is_empty())
+    {
+        anyhow::bail!(localized!("create-no-answers"));
+    }
+    //  Reactions can't open the modal with the question
+    if question.is_some() && reactions.unwrap_or(false) {
+        anyhow::bail!(localized!("create-question-reactions"));
+    }
+    let color = color.as_deref().map(parse_color).transpose()?;
+    let style = Style {
+        embed: !settings.plain_text,
+        image,
+        color: color.or(settings.color),
+        language: settings.language,
+        reactions: reactions.unwrap_or(false),
+        countdown: settings.countdown.then(|| tz.name().to_string()),
+        buttons: settings.buttons.clone(),
+        anonymous: anonymous.unwrap_or(false),
+    };
+    if let Some(channel) = &channel {
+        check_channel(ctx, channel, style.embed).await?;
+    }
+    let target = channel.map(|channel| channel.id);
+    let channel = target.unwrap_or(ctx.channel_id());
+    let mut mirror_channels = Vec::new();
+    for mirror in channels
+        .as_deref()
+        .map(parse_channels)
+        .transpose()?
+        .unwrap_or_default()
+    {
+        let mirror = mirror
+            .to_channel(ctx)
+            .await?
+            .guild()
+            .filter(|mirror| mirror.guild_id == guild)
+            .with_context(|| localized!("create-foreign-channel", channel = mirror.mention()))?;
+        check_channel(ctx, &mirror, style.embed).await?;
+        if mirror.id != channel && !mirror_channels.contains(&mirror.id) {
+            mirror_channels.push(mirror.id);
+        }
+    }
+    let event = match scheduled_event.unwrap_or(false) {
+        true => {
+            let end = time.context(localized!("create-event-without-end"))?;
+            let event = create_event(&ctx, guild, &title, &description, channel, start, end)
+                .await
+                .context(localized!("create-event-failed"))?;
+            Some(event.get())
+        }
+        false => None,
+    };
+    if let Some(start) = start {
+        let reply = t!(
+            language,
+            "create-scheduled",
+            title,
+            start = format!("<t:{}:R>", start.timestamp()),
+            channel = channel.mention(),
+            note = default_note
+        );
+        let draft = Draft {
+            title,
+            description,
+            prizes,
+            channel: channel.get(),
+            time: time.map(|time| time.timestamp()),
+            requirements,
+            style,
+            claim_hours: claim_hours.unwrap_or(48),
+            host: host.get(),
+            start: start.timestamp(),
+            ping: ping.unwrap_or(false),
+            notify_losers: notify_losers.unwrap_or(false),
+            auto_extend,
+            winner_role,
+            secret_codes: Vec::new(),
+            event,
+            question,
+            mirror_channels: mirror_channels.iter().map(|mirror| mirror.get()).collect(),
+        };
+        db_write(db, guild, move |state| state.drafts.insert(id, draft))?;
+        let http = MyHttpCache::new(
+            ctx.serenity_context().http.clone(),
+            ctx.serenity_context().cache.clone(),
+        );
+        let task = start_task(guild, id, start, db.clone(), http);
+        db.spawn_for_guild(guild, async move { task.await.unwrap() });
+        ctx.send(CreateReply::default().content(reply).reply(true))
+            .await?;
+        if secret_codes.unwrap_or(false) {
+            codes_prompt(ctx, id, language).await?;
+        }
+        return Ok(());
+    }
+    let commitment = commit()?;
+    let view = GiveawayMessage {
+        title: &title,
+        description: &description,
+        prizes: &prizes,
+        time,
+        requirements: &requirements,
+        style: &style,
+        host: Some(host),
+        participants: 0,
+        commitment: Some(&commitment.hash),
+    };
+    let (mention, allowed_mentions) = ping_mentions(ping.unwrap_or(false), settings.ping_role);
+    let content = format!("{mention}{}", view.content(false));
+    let message = match target {
+        Some(target) if target != ctx.channel_id() => {
+            let message = target
+                .send_message(
+                    ctx,
+                    view.create_message(false)
+                        .content(content.trim_end())
+                        .allowed_mentions(allowed_mentions)
+                        .components(vec![giveaway_buttons(id, &style)]),
+                )
+                .await?;
+            ctx.send(
+                CreateReply::default()
+                    .content(t!(
+                        language,
+                        "create-posted",
+                        link = message.link(),
+                        note = default_note
+                    ))
+                    .reply(true),
+            )
+            .await?;
+            message.id
+        }
+        _ => {
+            let mut reply = CreateReply::default()
+                .content(content.trim_end())
                 .reply(true)
-                .components(vec![ar]),
-        )
-        .await?
-        .message()
-        .await?
-        .id;
+                .allowed_mentions(allowed_mentions)
+                .components(vec![giveaway_buttons(id, &style)]);
+            if let Some(embed) = view.embed(false) {
+                reply = reply.embed(embed);
+            }
+            let id = ctx.send(reply).await?.message().await?.id;
+            if !default_note.is_empty() {
+                ctx.send(
+                    CreateReply::default()
+                        .content(default_note.trim_start())
+                        .ephemeral(true),
+                )
+                .await?;
+            }
+            id
+        }
+    };
+    if style.reactions {
+        add_entry_reaction(&ctx, channel, message).await?;
+    }
+    let mirrors = post_mirrors(id, &view, &mirror_channels, &ctx).await;
 
     let giveaway: Giveaway = RealGiveaway {
         title,
         description,
-        participants: HashSet::new(),
-        winners,
+        participants: HashMap::new(),
+        prizes,
         channel,
         message,
         time,
+        requirements,
+        style,
+        created: Some(Utc::now()),
+        claim_hours: claim_hours.unwrap_or(48),
+        host: Some(host),
+        notify_losers: notify_losers.unwrap_or(false),
+        auto_extend,
+        winner_role,
+        secret_codes: Vec::new(),
+        event,
+        question,
+        mirrors,
+        commitment: Some(commitment),
     }
     .into();
     db_write(db, guild, move |state| state.giveaways.insert(id, giveaway))?;
+    metrics().giveaway_created();
+    if secret_codes.unwrap_or(false) {
+        codes_prompt(ctx, id, language).await?;
+    }
 
     if let Some(time) = time {
         let http = MyHttpCache::new(
             ctx.serenity_context().http.clone(),
             ctx.serenity_context().cache.clone(),
         );
-        let db = db.clone();
-        tokio::spawn(async move {
-            finish_task(guild, id, time, db, http).await.unwrap();
-        });
+        schedule_end(guild, id, time, db.clone(), http);
+    }
+    Ok(())
+}
+
+//  A modal can't follow the deferred reply, so the creator opens it with a button
+async fn codes_prompt(
+    ctx: Context<'_, Arc<Db>, anyhow::Error>,
+    id: GiveawayId,
+    language: Language,
+) -> anyhow::Result<()> {
+    ctx.send(
+        CreateReply::default()
+            .content(t!(language, "codes-prompt"))
+            .components(vec![CreateActionRow::Buttons(vec![
+                CreateButton::new(serde_json::to_string(&UserAction::Codes(id)).unwrap())
+                    .label(t!(language, "codes-button"))
+                    .style(ButtonStyle::Primary),
+            ])])
+            .ephemeral(true),
+    )
+    .await?;
+    Ok(())
+}
+
+//  Only the ping itself may notify anyone, mentioning the required role shouldn't
+fn ping_mentions(ping: bool, role: Option<u64>) -> (String, CreateAllowedMentions) {
+    match (ping, role) {
+        (false, _) => (String::new(), CreateAllowedMentions::new()),
+        (true, Some(role)) => (
+            format!("<@&{role}>\n"),
+            CreateAllowedMentions::new().roles([RoleId::new(role)]),
+        ),
+        (true, None) => (
+            "@here\n".to_string(),
+            CreateAllowedMentions::new().everyone(true),
+        ),
+    }
+}
+
+//  The bot can ping anyone, so the member creating the giveaway needs MENTION_EVERYONE, unless
+//  the role can be mentioned by everyone anyway
+async fn may_ping(ctx: Context<'_, Arc<Db>, anyhow::Error>, role: Option<u64>) -> bool {
+    let permissions = ctx
+        .author_member()
+        .await
+        .and_then(|member| member.permissions);
+    if permissions.is_some_and(|p| p.mention_everyone()) {
+        return true;
+    }
+    role.is_some_and(|role| {
+        ctx.guild().is_some_and(|guild| {
+            guild
+                .roles
+                .get(&RoleId::new(role))
+                .is_some_and(|role| role.mentionable)
+        })
+    })
+}
+
+//  Checked before anything is stored, so a giveaway can't end up in a channel the bot can't use
+async fn check_channel(
+    ctx: Context<'_, Arc<Db>, anyhow::Error>,
+    channel: &GuildChannel,
+    embed: bool,
+) -> anyhow::Result<()> {
+    let guild = ctx.guild_id().context("Not in a guild")?;
+    let bot = guild.member(ctx, ctx.framework().bot_id).await?;
+    let permissions = ctx
+        .guild()
+        .context("Guild not in cache")?
+        .user_permissions_in(channel, &bot);
+    let mut required =
+        Permissions::VIEW_CHANNEL | Permissions::SEND_MESSAGES | Permissions::READ_MESSAGE_HISTORY;
+    if embed {
+        required |= Permissions::EMBED_LINKS;
+    }
+    let missing = required - permissions;
+    if !missing.is_empty() {
+        anyhow::bail!(localized!(
+            "missing-channel-permissions",
+            channel = channel.mention(),
+            permissions = missing.get_permission_names().join(", ")
+        ));
+    }
+    Ok(())
+}
+
+//  Roles can only be given by a bot with MANAGE_ROLES, whose highest role is above them
+async fn check_role(ctx: Context<'_, Arc<Db>, anyhow::Error>, role: RoleId) -> anyhow::Result<()> {
+    let guild = ctx.guild_id().context("Not in a guild")?;
+    let bot = guild.member(ctx, ctx.framework().bot_id).await?;
+    let guild = ctx.guild().context("Guild not in cache")?;
+    //  MANAGE_ROLES is a server wide permission, so channel overwrites don't matter
+    let permissions = bot
+        .roles
+        .iter()
+        .chain([&guild.id.everyone_role()])
+        .filter_map(|role| guild.roles.get(role))
+        .fold(Permissions::empty(), |acc, role| acc | role.permissions);
+    if !permissions.manage_roles() && !permissions.administrator() {
+        anyhow::bail!(localized!("missing-manage-roles", role = role.mention()));
+    }
+    let position = guild
+        .roles
+        .get(&role)
+        .context(localized!("role-not-found"))?
+        .position;
+    if guild
+        .member_highest_role(&bot)
+        .is_none_or(|highest| highest.position <= position)
+    {
+        anyhow::bail!(localized!("role-too-high", role = role.mention()));
     }
     Ok(())
 }
 
+//  Previews the end time while typing, so parse errors show up before submitting
+async fn time_autocomplete<'a>(
+    ctx: Context<'a, Arc<Db>, anyhow::Error>,
+    part: &'a str,
+) -> impl Iterator<Item = AutocompleteChoice> + 'a {
+    let settings = ctx
+        .guild_id()
+        .and_then(|guild| ctx.data().settings(guild).ok())
+        .unwrap_or_else(|| Arc::new(CachedSettings::from(&GuildState::default())));
+    let tz = settings.timezone;
+    let default_time = settings.default_time();
+    let language = settings.language;
+    let preview = move |time: &str| {
+        let name = match parse_time(time, tz, default_time) {
+            Ok(end) => t!(
+                language,
+                "time-preview",
+                time,
+                end = end.format_back(language)
+            ),
+            Err(err) => format!(
+                "{time} → {}",
+                err.text(language).lines().next().unwrap_or_default()
+            ),
+        };
+        AutocompleteChoice::new(name.chars().take(100).collect::<String>(), time.to_string())
+    };
+    let typed = (!part.is_empty()).then(|| preview(part));
+    let lower = part.to_lowercase();
+    typed.into_iter().chain(
+        [
+            "in 1 Stunde",
+            "in 1 Tag",
+            "in 3 Tagen",
+            "in 1 Woche",
+            "Heute um 20:00",
+            "Morgen um 18:00",
+        ]
+        .into_iter()
+        .filter(move |time| time != &part && time.to_lowercase().starts_with(&lower))
+        .map(preview),
+    )
+}
+
 async fn timezone_autocomplete<'a>(
-    _ctx: poise::Context<'a, Arc<Database>, anyhow::Error>,
+    _ctx: poise::Context<'a, Arc<Db>, anyhow::Error>,
     part: &'a str,
 ) -> impl Iterator<Item = &'static str> + 'a {
     chrono_tz::TZ_VARIANTS
@@ -522,7 +3212,7 @@ async fn timezone_autocomplete<'a>(
     guild_only
 )]
 async fn timezone(
-    ctx: poise::Context<'_, Arc<Database>, anyhow::Error>,
+    ctx: poise::Context<'_, Arc<Db>, anyhow::Error>,
     #[autocomplete = "timezone_autocomplete"] timezone: Tz,
 ) -> anyhow::Result<()> {
     ctx.defer_ephemeral().await?;
@@ -531,55 +3221,42 @@ async fn timezone(
         state.timezone = timezone.to_string();
         tz
     })?;
-    ctx.reply(format!("Zeitzone von {} zu {} geändert.", old, timezone))
+    ctx.reply(t!(language(ctx), "timezone-changed", old, timezone))
         .await?;
     Ok(())
 }
 
+#[poise::command(
+    slash_command,
+    default_member_permissions = "ADMINISTRATOR",
+    guild_only
+)]
+async fn embeds(
+    ctx: poise::Context<'_, Arc<Db>, anyhow::Error>,
+    enabled: bool,
+) -> anyhow::Result<()> {
+    ctx.defer_ephemeral().await?;
+    db_write(ctx.data(), ctx.guild_id().unwrap(), move |state| {
+        state.plain_text = !enabled;
+    })?;
+    ctx.reply(match enabled {
+        true => t!(language(ctx), "embeds-on"),
+        false => t!(language(ctx), "embeds-off"),
+    })
+    .await?;
+    Ok(())
+}
+
 #[poise::command(slash_command, guild_only)]
-async fn info(ctx: poise::Context<'_, Arc<Database>, anyhow::Error>) -> anyhow::Result<()> {
+async fn info(ctx: poise::Context<'_, Arc<Db>, anyhow::Error>) -> anyhow::Result<()> {
     //ctx.defer_ephemeral().await?;
-    let db_read = ctx.data().begin_read()?;
-    let (giveaway_count, timezone) = {
-        db_read
-            .open_table(TABLE)?
-            .get(ctx.guild_id().unwrap().get())?
-            .map(|v| v.value())
-            .map(|state| (state.giveaways.len(), state.timezone.parse().unwrap()))
-    }
-    .unwrap_or((0, Tz::CET));
-    db_read.close()?;
-
-    let message = format!(
-        r#"
-Dieser Bot erstellt Giveaways und stellt rudimentäre Befehle zur Verfügung.
-
-Befehle:
-/create <Titel> <Beschreibung> [Gewinner: Anzahl Gewinner] [Zeit: Ende des Giveaways]
-    Erstellt ein neues Giveaway in diesem Kanal.
-    Berechtigung: CREATE_EVENTS
-/timezone
-    Ändern der verwendeten Zeitzone für diesen Server.
-    Standart: CET bzw. CEST (Central Europian [Summer-] Time)
-    Berechtigung: ADMINISTRATOR
-/clear <Nutzer>
-    Löscht alle Nachrichten des jeweiligen Nutzers, nützlich um Spam im Nachgang zu entfernen.
-    Wenn es sehr viele Nachrichten gibt, wird das Löschen auf Grund einiger Begrenzungen von Discord lange dauern. Bitte habe etwas Geduld.
-    Berechtigung: BAN_MEMBERS
-/clear_all
-    Leert den gesamten aktuellen Kanal.
-    Wenn es sehr viele Nachrichten gibt, wird das Löschen auf Grund einiger Begrenzungen von Discord lange dauern. Bitte habe etwas Geduld.
-    Berechtigung: MANAGE_CHANNELS
-/info
-    Zeigt diese Info an.
-
-Bei Fragen zur Zeitangabe, wende dich bitte an @doEggi (<@518852275955957761>).
-
-Anzahl der Giveaways auf diesem Server: {giveaway_count}
-Aktuell verwendete Zeitzone: {timezone}
-
-~doEggi was here...
-"#
+    let state = db_read(ctx.data(), ctx.guild_id().unwrap())?;
+    let (giveaway_count, timezone): (usize, Tz) =
+        (state.giveaways.len(), state.timezone.parse().unwrap());
+
+    let message = locale::info(
+        language(ctx),
+        &[("giveaway_count", &giveaway_count), ("timezone", &timezone)],
     )
     .trim()
     .to_string();
@@ -593,35 +3270,19 @@ Aktuell verwendete Zeitzone: {timezone}
     Ok(())
 }
 
-fn dump_db(db: &Database) {
-    println!("BEGIN DB DUMP");
-    let db = db.begin_read().unwrap();
-    let table = db.open_table(TABLE).unwrap();
-    for entry in table.iter().unwrap() {
-        let entry = entry.unwrap();
-        let guild: GuildId = GuildId::new(entry.0.value());
-        let state: GuildState = entry.1.value();
-        println!("  {}: {:?}", guild, state);
-    }
-    println!("END DB DUMP");
+fn db_read(db: &Db, guild: GuildId) -> anyhow::Result<GuildState> {
+    db.storage().read(guild)
 }
 
 fn db_write<T>(
-    db: &Database,
+    db: &Db,
     guild: GuildId,
     r#fn: impl FnOnce(&mut GuildState) -> T,
 ) -> anyhow::Result<T> {
-    let db = db.begin_write()?;
-    let res = {
-        let mut table = db.open_table(TABLE)?;
-        let mut state = table
-            .get(guild.get())?
-            .map(|v| v.value())
-            .unwrap_or_default();
-        let res = r#fn(&mut state);
-        table.insert(guild.get(), state)?;
-        res
-    };
-    db.commit()?;
-    Ok(res)
+    let mut r#fn = Some(r#fn);
+    let mut res = None;
+    db.write(guild, &mut |state| {
+        res = r#fn.take().map(|r#fn| r#fn(state));
+    })?;
+    Ok(res.expect("update runs the closure once"))
 }
@@ -0,0 +1,129 @@
+use chrono::Utc;
+use log::{error, info};
+use poise::{Context, command, serenity_prelude::GuildId};
+use std::{sync::Arc, time::Duration};
+
+use crate::{
+    audit::record,
+    db_write,
+    departed::remove_departed,
+    locale::{language, t},
+    storage::Db,
+    structs::{ArchivedGiveaway, GuildState},
+};
+
+const CHECK_INTERVAL: u64 = 60 * 60;
+const PRUNE_INTERVAL: u64 = 24 * 60 * 60;
+//  Compacting locks the database, so it waits until nothing was written for a while
+const IDLE_FOR: i64 = 15 * 60;
+
+/// Compacts the database once it is idle, if anything was written since the last time
+pub async fn maintenance_task(db: Arc<Db>) {
+    loop {
+        tokio::time::sleep(Duration::from_secs(CHECK_INTERVAL)).await;
+        let last_write = db.last_write();
+        if last_write <= db.last_compaction() || Utc::now().timestamp() - last_write < IDLE_FOR {
+            continue;
+        }
+        let db = db.clone();
+        //  A failed compaction is tried again once the interval passed
+        match tokio::task::spawn_blocking(move || db.compact()).await {
+            Ok(Ok(Some(freed))) => info!("Compacted the database, freed {}", format_bytes(freed)),
+            Ok(Ok(None)) => {}
+            Ok(Err(err)) => error!("Error compacting the database: {}", err),
+            Err(err) => error!("Error compacting the database: {}", err),
+        }
+    }
+}
+
+/// Removes giveaways from the archive once they are older than the retention of their guild, and
+/// guilds the bot left a while ago. Guilds without a retention keep their archive.
+pub async fn prune_task(db: Arc<Db>) {
+    loop {
+        //  A failing guild is tried again the next day, the others are pruned anyway
+        match db.storage().guilds() {
+            Ok(guilds) => {
+                for (guild, state) in guilds {
+                    if let Err(err) = prune_archive(&db, guild, &state) {
+                        error!("Error pruning the archive of {}: {}", guild, err);
+                    }
+                }
+            }
+            Err(err) => error!("Error reading the guilds to prune: {}", err),
+        }
+        match remove_departed(&db).await {
+            Ok(0) => {}
+            Ok(removed) => info!("Removed {removed} guilds the bot left"),
+            Err(err) => error!("Error removing departed guilds: {}", err),
+        }
+        tokio::time::sleep(Duration::from_secs(PRUNE_INTERVAL)).await;
+    }
+}
+
+fn prune_archive(db: &Db, guild: GuildId, state: &GuildState) -> anyhow::Result<()> {
+    let Some(days) = state.settings.archive_days() else {
+        return Ok(());
+    };
+    let before = Utc::now().timestamp() - i64::from(days) * 24 * 60 * 60;
+    if !state
+        .archive
+        .values()
+        .any(|archived| expired(archived, before))
+    {
+        return Ok(());
+    }
+    let pruned = db_write(db, guild, move |state| {
+        let count = state.archive.len();
+        state
+            .archive
+            .retain(|_, archived| !expired(archived, before));
+        count - state.archive.len()
+    })?;
+    let language = state.settings.language;
+    record(
+        db,
+        guild,
+        None,
+        t!(language, "audit-archive-pruned"),
+        t!(language, "audit-archive-pruned-details", pruned, days),
+    );
+    Ok(())
+}
+
+//  Giveaways with unclaimed prizes or winner roles still to remove are kept until those are done
+fn expired(archived: &ArchivedGiveaway, before: i64) -> bool {
+    archived.ended < before
+        && archived.next_deadline().is_none()
+        && archived.next_role_removal().is_none()
+}
+
+/// Compacts the database now instead of waiting for it to be idle
+#[command(
+    slash_command,
+    default_member_permissions = "ADMINISTRATOR",
+    owners_only
+)]
+pub async fn compact(ctx: Context<'_, Arc<Db>, anyhow::Error>) -> anyhow::Result<()> {
+    ctx.defer_ephemeral().await?;
+    let freed = {
+        let db = ctx.data().clone();
+        tokio::task::spawn_blocking(move || db.compact()).await??
+    };
+    let language = language(ctx);
+    match freed {
+        Some(freed) => {
+            ctx.reply(t!(language, "compact-done", freed = format_bytes(freed)))
+                .await?
+        }
+        None => ctx.reply(t!(language, "compact-busy")).await?,
+    };
+    Ok(())
+}
+
+pub fn format_bytes(bytes: u64) -> String {
+    match bytes {
+        0..1024 => format!("{bytes} B"),
+        1024..1_048_576 => format!("{:.1} KiB", bytes as f64 / 1024.0),
+        _ => format!("{:.1} MiB", bytes as f64 / 1_048_576.0),
+    }
+}
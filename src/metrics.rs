@@ -0,0 +1,237 @@
+use anyhow::Context as _;
+use log::error;
+use poise::serenity_prelude::{Error, HttpError};
+use std::{
+    collections::BTreeMap,
+    fmt::Write as _,
+    net::SocketAddr,
+    sync::{
+        Arc, LazyLock, Mutex,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::Duration,
+};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+};
+
+use crate::storage::Db;
+
+/// Upper bounds in seconds, writes usually take a few milliseconds
+const LATENCY_BUCKETS: [f64; 12] = [
+    0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0,
+];
+/// Requests are a single line and a few headers, anything bigger isn't a scraper
+const MAX_REQUEST: usize = 8 * 1024;
+
+static METRICS: LazyLock<Metrics> = LazyLock::new(Metrics::default);
+
+/// Counters for Prometheus, served on `metrics_addr` of the config file
+#[derive(Default)]
+pub struct Metrics {
+    /// By command and outcome
+    commands: Mutex<BTreeMap<(String, &'static str), u64>>,
+    buttons: AtomicU64,
+    giveaways_created: AtomicU64,
+    giveaways_finished: AtomicU64,
+    cleared_messages: AtomicU64,
+    discord_errors: AtomicU64,
+    db_writes: Histogram,
+}
+
+#[derive(Default)]
+struct Histogram {
+    buckets: [AtomicU64; LATENCY_BUCKETS.len()],
+    count: AtomicU64,
+    /// In microseconds, so it can be added atomically
+    sum: AtomicU64,
+}
+
+impl Histogram {
+    fn observe(&self, duration: Duration) {
+        let seconds = duration.as_secs_f64();
+        for (bucket, bound) in self.buckets.iter().zip(LATENCY_BUCKETS) {
+            if seconds <= bound {
+                bucket.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+        self.count.fetch_add(1, Ordering::Relaxed);
+        self.sum
+            .fetch_add(duration.as_micros() as u64, Ordering::Relaxed);
+    }
+
+    fn render(&self, out: &mut String, name: &str) {
+        for (bucket, bound) in self.buckets.iter().zip(LATENCY_BUCKETS) {
+            let count = bucket.load(Ordering::Relaxed);
+            let _ = writeln!(out, "{name}_bucket{{le=\"{bound}\"}} {count}");
+        }
+        let count = self.count.load(Ordering::Relaxed);
+        let sum = self.sum.load(Ordering::Relaxed) as f64 / 1_000_000.0;
+        let _ = writeln!(out, "{name}_bucket{{le=\"+Inf\"}} {count}");
+        let _ = writeln!(out, "{name}_sum {sum}");
+        let _ = writeln!(out, "{name}_count {count}");
+    }
+}
+
+pub fn metrics() -> &'static Metrics {
+    &METRICS
+}
+
+fn inc(counter: &AtomicU64) {
+    counter.fetch_add(1, Ordering::Relaxed);
+}
+
+impl Metrics {
+    pub fn button(&self) {
+        inc(&self.buttons);
+    }
+
+    pub fn giveaway_created(&self) {
+        inc(&self.giveaways_created);
+    }
+
+    pub fn giveaway_finished(&self) {
+        inc(&self.giveaways_finished);
+    }
+
+    pub fn message_cleared(&self) {
+        inc(&self.cleared_messages);
+    }
+
+    pub fn db_write(&self, duration: Duration) {
+        self.db_writes.observe(duration);
+    }
+
+    /// Only counts errors returned by Discord or failed requests, the cause might be wrapped
+    pub fn discord_error(&self, err: &(dyn std::error::Error + 'static)) {
+        let mut source = Some(err);
+        while let Some(err) = source {
+            if matches!(err.downcast_ref::<Error>(), Some(Error::Http(_)))
+                || err.downcast_ref::<HttpError>().is_some()
+            {
+                inc(&self.discord_errors);
+                return;
+            }
+            source = err.source();
+        }
+    }
+
+    fn render(&self) -> String {
+        let mut out = String::new();
+        let counters = [
+            ("buttons", "Button presses", &self.buttons),
+            (
+                "giveaways_created",
+                "Giveaways posted, including scheduled ones",
+                &self.giveaways_created,
+            ),
+            (
+                "giveaways_finished",
+                "Giveaways ended with winners drawn, without cancelled ones",
+                &self.giveaways_finished,
+            ),
+            (
+                "cleared_messages",
+                "Messages deleted by /clear",
+                &self.cleared_messages,
+            ),
+            (
+                "discord_errors",
+                "Failed requests to Discord",
+                &self.discord_errors,
+            ),
+        ];
+        for (name, help, counter) in counters {
+            let _ = writeln!(out, "# HELP dobot_{name}_total {help}");
+            let _ = writeln!(out, "# TYPE dobot_{name}_total counter");
+            let value = counter.load(Ordering::Relaxed);
+            let _ = writeln!(out, "dobot_{name}_total {value}");
+        }
+        let _ = writeln!(out, "# HELP dobot_commands_total Commands executed");
+        let _ = writeln!(out, "# TYPE dobot_commands_total counter");
+        for ((command, outcome), count) in self.commands.lock().unwrap().iter() {
+            let command = command.replace('\\', "\\\\").replace('"', "\\\"");
+            let _ = writeln!(
+                out,
+                "dobot_commands_total{{command=\"{command}\",outcome=\"{outcome}\"}} {count}"
+            );
+        }
+        let _ = writeln!(
+            out,
+            "# HELP dobot_db_write_seconds Duration of database write transactions"
+        );
+        let _ = writeln!(out, "# TYPE dobot_db_write_seconds histogram");
+        self.db_writes.render(&mut out, "dobot_db_write_seconds");
+        out
+    }
+}
+
+/// Counts every command once it's done, failed ones separately
+pub fn count_command(ctx: poise::Context<'_, Arc<Db>, anyhow::Error>, succeeded: bool) {
+    let command = ctx.command().qualified_name.clone();
+    let outcome = match succeeded {
+        true => "ok",
+        false => "error",
+    };
+    *metrics()
+        .commands
+        .lock()
+        .unwrap()
+        .entry((command, outcome))
+        .or_default() += 1;
+}
+
+/// Bound before the bot connects, so a wrong `metrics_addr` stops it right away
+pub async fn bind_metrics(addr: SocketAddr) -> anyhow::Result<TcpListener> {
+    TcpListener::bind(addr)
+        .await
+        .with_context(|| format!("Couldn't listen for metrics on {addr}"))
+}
+
+/// Answers `GET /metrics`, everything else gets a 404
+pub async fn serve_metrics(listener: TcpListener) {
+    loop {
+        //  Failed connections, e.g. with too many open files, don't stop the next ones
+        let stream = match listener.accept().await {
+            Ok((stream, _)) => stream,
+            Err(err) => {
+                error!("Error accepting metrics connection: {}", err);
+                tokio::time::sleep(Duration::from_secs(1)).await;
+                continue;
+            }
+        };
+        tokio::spawn(async move {
+            if let Err(err) = respond(stream).await {
+                error!("Error serving metrics: {}", err);
+            }
+        });
+    }
+}
+
+async fn respond(mut stream: TcpStream) -> anyhow::Result<()> {
+    let mut request = Vec::new();
+    let mut buf = [0; 1024];
+    //  Only the request line matters, but the headers are read so the client gets the response
+    while !request.windows(4).any(|w| w == b"\r\n\r\n") {
+        let read = tokio::time::timeout(Duration::from_secs(10), stream.read(&mut buf)).await??;
+        if read == 0 || request.len() + read > MAX_REQUEST {
+            return Ok(());
+        }
+        request.extend_from_slice(&buf[..read]);
+    }
+    let line = request.split(|b| *b == b'\r').next().unwrap_or_default();
+    let mut parts = line.split(|b| *b == b' ');
+    let (status, body) = match (parts.next(), parts.next()) {
+        (Some(b"GET"), Some(b"/metrics")) => ("200 OK", metrics().render()),
+        _ => ("404 Not Found", String::new()),
+    };
+    let response = format!(
+        "HTTP/1.1 {status}\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\n\
+        Connection: close\r\n\r\n{body}",
+        body.len()
+    );
+    stream.write_all(response.as_bytes()).await?;
+    stream.shutdown().await?;
+    Ok(())
+}
@@ -0,0 +1,87 @@
+use bincode::Decode;
+use log::warn;
+use redb::{Database, ReadableTable, TableDefinition, WriteTransaction};
+
+use crate::{
+    bc::{Bincode, Raw, decode_exact},
+    legacy,
+    structs::GuildState,
+};
+
+/// Bumped whenever the layout of a stored struct changes, together with a new entry in [`MIGRATIONS`]
+pub const SCHEMA_VERSION: u32 = 5;
+
+const META_TABLE: TableDefinition<&str, u32> = TableDefinition::new("meta");
+const VERSION_KEY: &str = "schema_version";
+
+/// Every guild as one value, before version 3 split them up into the tables of [`crate::storage`]
+pub(crate) const GUILDS_TABLE: TableDefinition<u64, Bincode<GuildState>> =
+    TableDefinition::new("guilds");
+/// [`GUILDS_TABLE`] without decoding, for guilds in an older layout
+pub(crate) const RAW_GUILDS_TABLE: TableDefinition<u64, Raw<GuildState>> =
+    TableDefinition::new("guilds");
+
+type Migration = fn(&WriteTransaction) -> anyhow::Result<()>;
+
+/// `MIGRATIONS[n]` upgrades a database from version `n` to `n + 1`
+const MIGRATIONS: [Migration; SCHEMA_VERSION as usize] = [
+    legacy::migrate_v0,
+    legacy::migrate_v1,
+    legacy::migrate_v2,
+    legacy::migrate_v3,
+    legacy::migrate_v4,
+];
+
+/// Brings the database to [`SCHEMA_VERSION`], returns the version it was at before
+pub fn migrate(db: &Database) -> anyhow::Result<u32> {
+    let w = db.begin_write()?;
+    let version = {
+        let table = w.open_table(META_TABLE)?;
+        //  Databases from before the version was stored count as version 0
+        table.get(VERSION_KEY)?.map(|v| v.value()).unwrap_or(0)
+    };
+    if version > SCHEMA_VERSION {
+        anyhow::bail!(
+            "The database has schema version {version}, but this build only knows up to {SCHEMA_VERSION}"
+        );
+    }
+    for migration in &MIGRATIONS[version as usize..] {
+        migration(&w)?;
+    }
+    w.open_table(META_TABLE)?
+        .insert(VERSION_KEY, SCHEMA_VERSION)?;
+    //  Everything or nothing, a failed migration leaves the old data untouched
+    w.commit()?;
+    Ok(version)
+}
+
+/// Rewrites every guild stored in the layout `Old`, guilds already in the current layout are kept
+pub fn convert_guilds<Old>(
+    w: &WriteTransaction,
+    convert: impl Fn(Old) -> GuildState,
+) -> anyhow::Result<usize>
+where
+    Old: Decode<()>,
+{
+    let mut converted = Vec::new();
+    {
+        let table = w.open_table(RAW_GUILDS_TABLE)?;
+        for entry in table.iter()? {
+            let (guild, data) = entry?;
+            let data = data.value();
+            if decode_exact::<GuildState>(data).is_some() {
+                continue;
+            }
+            match decode_exact::<Old>(data) {
+                Some(state) => converted.push((guild.value(), convert(state))),
+                None => warn!("Unknown layout for guild {}, skipping", guild.value()),
+            }
+        }
+    }
+    let count = converted.len();
+    let mut table = w.open_table(GUILDS_TABLE)?;
+    for (guild, state) in converted {
+        table.insert(guild, state)?;
+    }
+    Ok(count)
+}
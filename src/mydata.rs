@@ -0,0 +1,276 @@
+use anyhow::Context as _;
+use chrono::DateTime;
+use poise::{
+    Context, CreateReply, command,
+    serenity_prelude::{CreateAttachment, GuildId, UserId},
+};
+use serde::Serialize;
+use std::{collections::BTreeMap, sync::Arc};
+
+use crate::{
+    activity::{ACTIVITY_TABLE, get_activity},
+    audit::{self, Actor, entries_of},
+    db_read, db_write, draw,
+    giveaway::confirm,
+    invites::{INVITES_TABLE, recount_invites},
+    leaderboard::WINS_TABLE,
+    locale::{language, t},
+    storage::Db,
+    structs::{Entry, GiveawayId, UserAction},
+};
+
+/// Everything stored about a member of a guild
+#[derive(Debug, Serialize)]
+struct UserData {
+    user: u64,
+    guild: u64,
+    /// Running giveaways the member takes part in
+    entries: Vec<EntryData>,
+    /// Ended giveaways the member took part in
+    past_entries: Vec<EntryData>,
+    /// Ended giveaways the member was drawn in, including rerolls
+    won: Vec<u64>,
+    /// Ended giveaways the member couldn't win, e.g. because they won recently
+    excluded: Vec<u64>,
+    /// Timestamp of every win, for the leaderboard and win cooldowns
+    wins: Vec<i64>,
+    /// Messages per day, for activity requirements
+    messages: BTreeMap<String, u32>,
+    invited_by: Option<u64>,
+    /// Members invited by this one, who are still on the guild
+    invited: Vec<u64>,
+    /// Administrative actions of the member or naming them
+    audit: Vec<AuditData>,
+}
+
+#[derive(Debug, Serialize)]
+struct EntryData {
+    giveaway: u64,
+    title: String,
+    tickets: u32,
+    entered: i64,
+}
+
+#[derive(Debug, Serialize)]
+struct AuditData {
+    time: i64,
+    by_member: bool,
+    action: String,
+    details: String,
+}
+
+impl EntryData {
+    fn new(id: GiveawayId, title: &str, entry: &Entry) -> Self {
+        Self {
+            giveaway: id.0,
+            title: title.to_string(),
+            tickets: entry.tickets,
+            entered: entry.entered,
+        }
+    }
+}
+
+fn collect(db: &Db, guild: GuildId, user: UserId) -> anyhow::Result<UserData> {
+    let state = db_read(db, guild)?;
+    let mut data = UserData {
+        user: user.get(),
+        guild: guild.get(),
+        entries: state
+            .giveaways
+            .iter()
+            .filter_map(|(id, giveaway)| {
+                let entry = giveaway.participants.get(&user.get())?;
+                Some(EntryData::new(*id, &giveaway.title, entry))
+            })
+            .collect(),
+        past_entries: state
+            .archive
+            .iter()
+            .filter_map(|(id, archived)| {
+                let entry = archived.giveaway.participants.get(&user.get())?;
+                Some(EntryData::new(*id, &archived.giveaway.title, entry))
+            })
+            .collect(),
+        won: state
+            .archive
+            .iter()
+            .filter(|(_, archived)| archived.winners.contains(&user.get()))
+            .map(|(id, _)| id.0)
+            .collect(),
+        excluded: state
+            .archive
+            .iter()
+            .filter(|(_, archived)| {
+                archived
+                    .draw
+                    .as_ref()
+                    .is_some_and(|draw| draw.excluded.contains(&user.get()))
+            })
+            .map(|(id, _)| id.0)
+            .collect(),
+        wins: Vec::new(),
+        messages: BTreeMap::new(),
+        invited_by: None,
+        invited: Vec::new(),
+        audit: entries_of(db, guild, user.get())?
+            .into_iter()
+            .map(|entry| AuditData {
+                time: entry.time,
+                by_member: entry.actor == Actor::Member(user.get()),
+                action: entry.action,
+                details: entry.details,
+            })
+            .collect(),
+    };
+    let key = (guild.get(), user.get());
+    let db_read = db.begin_read()?;
+    if let Some(wins) = db_read.open_table(WINS_TABLE)?.get(key)? {
+        data.wins = wins.value().times;
+    }
+    data.messages = get_activity(db, guild, user)?
+        .days
+        .into_iter()
+        .filter_map(|(day, count)| {
+            let date = DateTime::from_timestamp(day * 24 * 60 * 60, 0)?.date_naive();
+            Some((date.to_string(), count))
+        })
+        .collect();
+    let invites = db_read.open_table(INVITES_TABLE)?;
+    data.invited_by = invites.get(key)?.map(|v| v.value());
+    for entry in invites.range((guild.get(), 0)..=(guild.get(), u64::MAX))? {
+        let (key, inviter) = entry?;
+        if inviter.value() == user.get() {
+            data.invited.push(key.value().1);
+        }
+    }
+    data.entries.sort_by_key(|entry| entry.entered);
+    data.past_entries.sort_by_key(|entry| entry.entered);
+    data.won.sort_unstable();
+    data.excluded.sort_unstable();
+    Ok(data)
+}
+
+//  Running entries are removed through the queue, which also updates the participant count of the
+//  messages. Winners stay in the archive, their prizes might still have to be claimed. Ended
+//  entries are replaced by a tombstone in the draw, so it can still be verified
+fn erase_entries(db: &Db, guild: GuildId, user: UserId) -> anyhow::Result<usize> {
+    let mut erased = 0;
+    for (id, giveaway) in db_read(db, guild)?.giveaways {
+        if giveaway.participants.contains_key(&user.get()) {
+            db.entries().remove(guild, id, user.get());
+            erased += 1;
+        }
+    }
+    db.entries().flush(db, guild)?;
+    erased += db_write(db, guild, move |state| {
+        let mut erased = 0;
+        for archived in state.archive.values_mut() {
+            let entry = archived.giveaway.participants.remove(&user.get());
+            if let Some(draw) = &mut archived.draw {
+                draw::erase(draw, user.get(), entry.map(|entry| entry.tickets));
+            }
+            if entry.is_some() {
+                erased += 1;
+            }
+        }
+        erased
+    })?;
+    Ok(erased)
+}
+
+/// Removes wins, message counts and invites of the user, on every guild without one
+fn erase_records(db: &Db, guild: Option<GuildId>, user: UserId) -> anyhow::Result<()> {
+    db.activity().forget(guild, Some(user));
+    let user = user.get();
+    let matches = |(key_guild, key_user): (u64, u64)| {
+        key_user == user && guild.is_none_or(|guild| guild.get() == key_guild)
+    };
+    let db = db.begin_write()?;
+    db.open_table(WINS_TABLE)?.retain(|key, _| !matches(key))?;
+    db.open_table(ACTIVITY_TABLE)?
+        .retain(|key, _| !matches(key))?;
+    //  Also forgets whom the user invited, so they don't count for invite requirements anymore
+    db.open_table(INVITES_TABLE)?
+        .retain(|key, inviter| !matches(key) && !matches((key.0, inviter)))?;
+    recount_invites(&db, guild.map(|guild| guild.get()))?;
+    db.commit()?;
+    Ok(())
+}
+
+//  Audit entries stay, only without the user
+fn erase_audit(db: &Db, guild: GuildId, user: UserId) -> anyhow::Result<()> {
+    let erased = t!(db.settings(guild)?.language, "audit-erased");
+    audit::erase_user(db, guild, user.get(), &erased)
+}
+
+/// Erases the data of the user on this guild, once they confirmed it
+pub fn erase_user(db: &Db, guild: GuildId, user: UserId) -> anyhow::Result<usize> {
+    let erased = erase_entries(db, guild, user)?;
+    erase_audit(db, guild, user)?;
+    erase_records(db, Some(guild), user)?;
+    Ok(erased)
+}
+
+/// Erases a user on every guild, returns on how many guilds they took part and in how many giveaways
+pub async fn purge_user(db: &Db, user: UserId) -> anyhow::Result<(usize, usize)> {
+    let mut guilds = 0;
+    let mut erased = 0;
+    for (guild, _) in db.storage().guilds()? {
+        let _guard = db.lock_guild(guild).await;
+        let count = erase_entries(db, guild, user)?;
+        erase_audit(db, guild, user)?;
+        if count > 0 {
+            guilds += 1;
+            erased += count;
+        }
+    }
+    erase_records(db, None, user)?;
+    Ok((guilds, erased))
+}
+
+/// Your data stored by the bot on this server
+#[command(
+    slash_command,
+    guild_only,
+    subcommands("mydata_export", "mydata_delete"),
+    subcommand_required
+)]
+pub async fn mydata(_ctx: Context<'_, Arc<Db>, anyhow::Error>) -> anyhow::Result<()> {
+    Ok(())
+}
+
+/// Sends you everything the bot stored about you on this server
+#[command(slash_command, guild_only, rename = "export")]
+pub async fn mydata_export(ctx: Context<'_, Arc<Db>, anyhow::Error>) -> anyhow::Result<()> {
+    ctx.defer_ephemeral().await?;
+    let guild = ctx.guild_id().context("Not in a guild")?;
+    let user = ctx.author().id;
+    //  Entries that are still queued are written first
+    let data = {
+        let _guard = ctx.data().lock_guild(guild).await;
+        collect(ctx.data(), guild, user)?
+    };
+    ctx.send(
+        CreateReply::default()
+            .content(t!(language(ctx), "mydata-export"))
+            .attachment(CreateAttachment::bytes(
+                serde_json::to_vec_pretty(&data)?,
+                format!("mydata-{guild}.json"),
+            ))
+            .ephemeral(true),
+    )
+    .await?;
+    Ok(())
+}
+
+/// Deletes your entries, wins, message counts and invites on this server
+#[command(slash_command, guild_only, rename = "delete")]
+pub async fn mydata_delete(ctx: Context<'_, Arc<Db>, anyhow::Error>) -> anyhow::Result<()> {
+    confirm(
+        ctx,
+        &t!(language(ctx), "mydata-delete-confirm"),
+        UserAction::DeleteData(true),
+        UserAction::DeleteData(false),
+    )
+    .await
+}
@@ -0,0 +1,219 @@
+use anyhow::Context as _;
+use chrono::DateTime;
+use poise::{
+    ChoiceParameter, Context, CreateReply, command,
+    serenity_prelude::{CreateActionRow, CreateAttachment, CreateButton, Permissions, UserId},
+};
+use serde::Serialize;
+use std::sync::Arc;
+
+use crate::{
+    giveaway::{any_autocomplete, find_giveaway},
+    locale::{language, t},
+    storage::Db,
+    structs::{Giveaway, GiveawayId, Language, UserAction},
+};
+
+#[derive(Debug, Clone, Copy, ChoiceParameter)]
+pub enum ExportFormat {
+    #[name = "CSV"]
+    Csv,
+    #[name = "JSON"]
+    Json,
+}
+
+#[derive(Debug, Serialize)]
+struct ExportEntry {
+    user_id: u64,
+    username: String,
+    tickets: u32,
+    entered: Option<String>,
+}
+
+#[command(
+    slash_command,
+    default_member_permissions = "CREATE_EVENTS",
+    guild_only,
+    subcommands("list", "export"),
+    subcommand_required
+)]
+pub async fn participants(_ctx: Context<'_, Arc<Db>, anyhow::Error>) -> anyhow::Result<()> {
+    Ok(())
+}
+
+const PAGE_SIZE: usize = 25;
+
+/// Participants of anonymous giveaways are only visible to admins
+pub fn may_view(giveaway: &Giveaway, permissions: Option<Permissions>) -> bool {
+    !giveaway.style.anonymous || permissions.is_some_and(|p| p.administrator())
+}
+
+/// Renders one page of the participant list with buttons to switch pages
+pub fn participant_page(
+    id: GiveawayId,
+    giveaway: &Giveaway,
+    page: u32,
+    language: Language,
+) -> (String, Vec<CreateActionRow>) {
+    let mut participants: Vec<_> = giveaway.participants.iter().collect();
+    participants.sort_by_key(|(user, entry)| (entry.entered, **user));
+    let pages = participants.len().div_ceil(PAGE_SIZE).max(1) as u32;
+    let page = page.min(pages - 1);
+    let mut content = t!(
+        language,
+        "participants-header",
+        title = giveaway.title,
+        total = participants.len(),
+        page = page + 1,
+        pages
+    );
+    let first = page as usize * PAGE_SIZE;
+    for (i, (user, entry)) in participants
+        .into_iter()
+        .enumerate()
+        .skip(first)
+        .take(PAGE_SIZE)
+    {
+        content.push_str(&format!("\n{}. <@{user}>", i + 1));
+        if entry.tickets > 1 {
+            content.push_str(&t!(
+                language,
+                "participants-tickets",
+                tickets = entry.tickets
+            ));
+        }
+    }
+    let ar = CreateActionRow::Buttons(Vec::from([
+        CreateButton::new(
+            serde_json::to_string(&UserAction::Participants(id, Some(page.saturating_sub(1))))
+                .unwrap(),
+        )
+        .label(t!(language, "button-previous"))
+        .style(poise::serenity_prelude::ButtonStyle::Secondary)
+        .disabled(page == 0),
+        CreateButton::new(
+            serde_json::to_string(&UserAction::Participants(id, Some(page + 1))).unwrap(),
+        )
+        .label(t!(language, "button-next"))
+        .style(poise::serenity_prelude::ButtonStyle::Secondary)
+        .disabled(page + 1 >= pages),
+    ]));
+    (content, vec![ar])
+}
+
+#[command(slash_command, guild_only)]
+pub async fn list(
+    ctx: Context<'_, Arc<Db>, anyhow::Error>,
+    #[autocomplete = "any_autocomplete"] giveaway: GiveawayId,
+) -> anyhow::Result<()> {
+    let guild = ctx.guild_id().context("Not in a guild")?;
+    let language = language(ctx);
+    let Some(data) = find_giveaway(ctx.data(), guild, giveaway)? else {
+        ctx.send(
+            CreateReply::default()
+                .content(t!(language, "giveaway-not-found"))
+                .ephemeral(true),
+        )
+        .await?;
+        return Ok(());
+    };
+    let permissions = ctx
+        .author_member()
+        .await
+        .and_then(|member| member.permissions);
+    if !may_view(&data, permissions) {
+        ctx.send(
+            CreateReply::default()
+                .content(t!(language, "participants-anonymous"))
+                .ephemeral(true),
+        )
+        .await?;
+        return Ok(());
+    }
+    let (content, components) = participant_page(giveaway, &data, 0, language);
+    ctx.send(
+        CreateReply::default()
+            .content(content)
+            .components(components)
+            .ephemeral(true),
+    )
+    .await?;
+    Ok(())
+}
+
+#[command(slash_command, guild_only)]
+pub async fn export(
+    ctx: Context<'_, Arc<Db>, anyhow::Error>,
+    #[autocomplete = "any_autocomplete"] giveaway: GiveawayId,
+    format: Option<ExportFormat>,
+) -> anyhow::Result<()> {
+    ctx.defer_ephemeral().await?;
+    let guild = ctx.guild_id().context("Not in a guild")?;
+    let language = language(ctx);
+    let Some(giveaway) = find_giveaway(ctx.data(), guild, giveaway)? else {
+        ctx.reply(t!(language, "giveaway-not-found")).await?;
+        return Ok(());
+    };
+    let permissions = ctx
+        .author_member()
+        .await
+        .and_then(|member| member.permissions);
+    if !may_view(&giveaway, permissions) {
+        ctx.reply(t!(language, "participants-anonymous")).await?;
+        return Ok(());
+    }
+
+    let mut participants: Vec<_> = giveaway.participants.into_iter().collect();
+    participants.sort_by_key(|(_, entry)| entry.entered);
+    let mut entries = Vec::with_capacity(participants.len());
+    for (user, entry) in participants {
+        let username = UserId::new(user)
+            .to_user(ctx)
+            .await
+            .map(|user| user.name)
+            .unwrap_or_default();
+        entries.push(ExportEntry {
+            user_id: user,
+            username,
+            tickets: entry.tickets,
+            //  Entries from before entry times were recorded
+            entered: (entry.entered > 0)
+                .then(|| DateTime::from_timestamp(entry.entered, 0))
+                .flatten()
+                .map(|time| time.to_rfc3339()),
+        });
+    }
+
+    let (data, extension) = match format.unwrap_or(ExportFormat::Csv) {
+        ExportFormat::Csv => {
+            let mut csv = "user_id,username,tickets,entered\n".to_string();
+            for entry in &entries {
+                csv.push_str(&format!(
+                    "{},\"{}\",{},{}\n",
+                    entry.user_id,
+                    entry.username.replace('"', "\"\""),
+                    entry.tickets,
+                    entry.entered.as_deref().unwrap_or_default()
+                ));
+            }
+            (csv.into_bytes(), "csv")
+        }
+        ExportFormat::Json => (serde_json::to_vec_pretty(&entries)?, "json"),
+    };
+    ctx.send(
+        CreateReply::default()
+            .content(t!(
+                language,
+                "participants-export",
+                count = entries.len(),
+                title = giveaway.title
+            ))
+            .attachment(CreateAttachment::bytes(
+                data,
+                format!("{}.{extension}", t!(language, "participants-file")),
+            ))
+            .ephemeral(true),
+    )
+    .await?;
+    Ok(())
+}
@@ -0,0 +1,561 @@
+use anyhow::Context as _;
+use chrono::{TimeDelta, Timelike};
+use poise::{
+    ChoiceParameter, Context, CreateReply, command,
+    serenity_prelude::{ChannelId, GuildId, Mentionable, ReactionType, RoleId},
+};
+use std::sync::Arc;
+
+use crate::{
+    datetime::{format_duration, parse_clock, parse_duration},
+    db_write,
+    locale::{self, localized, t},
+    parse_error,
+    storage::Db,
+    structs::{AltAction, AltCheck, ButtonColor, CustomButton, GiveawayButton, Language, Modlog},
+};
+
+#[command(
+    slash_command,
+    default_member_permissions = "ADMINISTRATOR",
+    guild_only,
+    subcommands(
+        "show",
+        "winners",
+        "duration",
+        "role",
+        "announcements",
+        "language",
+        "color",
+        "ping_role",
+        "reminder",
+        "countdown",
+        "max_giveaways",
+        "repost_deleted",
+        "buttons",
+        "alt_check",
+        "modlog",
+        "default_time",
+        "archive_days"
+    ),
+    subcommand_required
+)]
+pub async fn settings(_ctx: Context<'_, Arc<Db>, anyhow::Error>) -> anyhow::Result<()> {
+    Ok(())
+}
+
+#[command(slash_command, guild_only)]
+pub async fn show(ctx: Context<'_, Arc<Db>, anyhow::Error>) -> anyhow::Result<()> {
+    let guild = ctx.guild_id().context("Not in a guild")?;
+    let settings = ctx.data().settings(guild)?;
+    let language = settings.language;
+    let none = || t!(language, "settings-none");
+    let off = || t!(language, "settings-off");
+    let duration = settings
+        .duration
+        .map(|secs| format_duration(TimeDelta::seconds(secs), language))
+        .unwrap_or_else(|| t!(language, "settings-no-end"));
+    let role = settings
+        .role
+        .map(|role| format!("<@&{role}>"))
+        .unwrap_or_else(none);
+    let channel = settings
+        .announcement_channel
+        .map(|channel| format!("<#{channel}>"))
+        .unwrap_or_else(|| t!(language, "settings-giveaway-channel"));
+    let color = settings
+        .color
+        .map(|color| format!("#{color:06x}"))
+        .unwrap_or_else(none);
+    let ping_role = settings
+        .ping_role
+        .map(|role| format!("<@&{role}>"))
+        .unwrap_or_else(|| "@here".to_string());
+    let reminder = match settings.reminder {
+        Some(minutes) if settings.reminder_ping => {
+            t!(language, "settings-reminder-ping", minutes)
+        }
+        Some(minutes) => t!(language, "settings-reminder", minutes),
+        None => none(),
+    };
+    let max_giveaways = settings
+        .max_giveaways
+        .map(|max| t!(language, "settings-max-giveaways", max))
+        .unwrap_or_else(|| t!(language, "settings-unlimited"));
+    let buttons: Vec<String> = [
+        GiveawayButton::Join,
+        GiveawayButton::Leave,
+        GiveawayButton::Cancel,
+        GiveawayButton::Finish,
+    ]
+    .into_iter()
+    .filter(|&button| {
+        let custom = settings.buttons.get(button);
+        custom.label.is_some() || custom.emoji.is_some() || custom.color.is_some()
+    })
+    .map(|button| button.label(language))
+    .collect();
+    let buttons = match buttons.is_empty() {
+        true => none(),
+        false => buttons.join(", "),
+    };
+    let alt_check = match settings.alt_check {
+        Some(check) => {
+            let action = match check.action {
+                AltAction::Flag => t!(language, "settings-alt-flag"),
+                AltAction::Deny => t!(language, "settings-alt-deny"),
+            };
+            match check.log_channel {
+                Some(channel) => t!(
+                    language,
+                    "settings-alt-check-log",
+                    action,
+                    days = check.min_account_days,
+                    channel = format!("<#{channel}>")
+                ),
+                None => t!(
+                    language,
+                    "settings-alt-check",
+                    action,
+                    days = check.min_account_days
+                ),
+            }
+        }
+        None => off(),
+    };
+    let modlog = match settings.modlog {
+        Some(modlog) if modlog.transcript => t!(
+            language,
+            "settings-modlog-transcript",
+            channel = format!("<#{}>", modlog.channel)
+        ),
+        Some(modlog) => format!("<#{}>", modlog.channel),
+        None => off(),
+    };
+    let archive = match settings.archive_days() {
+        Some(days) => t!(language, "settings-archive", days),
+        None => t!(language, "settings-archive-forever"),
+    };
+    let content = t!(
+        language,
+        "settings-show",
+        winners = settings.winners.unwrap_or(1),
+        duration,
+        role,
+        channel,
+        language = language.name(),
+        color,
+        ping_role,
+        reminder,
+        countdown = match settings.countdown {
+            true => t!(language, "settings-on"),
+            false => off(),
+        },
+        max_giveaways,
+        deleted = match settings.repost_deleted {
+            true => t!(language, "settings-repost"),
+            false => t!(language, "settings-cancel"),
+        },
+        buttons,
+        alt_check,
+        modlog,
+        default_time = settings.default_time().format("%H:%M"),
+        archive,
+    );
+    ctx.send(
+        CreateReply::default()
+            .content(content)
+            .reply(true)
+            .ephemeral(true),
+    )
+    .await?;
+    Ok(())
+}
+
+/// Leaving out the count resets it to one winner
+#[command(slash_command, guild_only)]
+pub async fn winners(
+    ctx: Context<'_, Arc<Db>, anyhow::Error>,
+    #[min = 1]
+    #[max = 100]
+    count: Option<u32>,
+) -> anyhow::Result<()> {
+    ctx.defer_ephemeral().await?;
+    let guild = ctx.guild_id().context("Not in a guild")?;
+    db_write(ctx.data(), guild, move |state| {
+        state.settings.winners = count;
+    })?;
+    ctx.reply(t!(
+        locale::language(ctx),
+        "settings-winners",
+        count = count.unwrap_or(1)
+    ))
+    .await?;
+    Ok(())
+}
+
+#[command(slash_command, guild_only)]
+pub async fn duration(
+    ctx: Context<'_, Arc<Db>, anyhow::Error>,
+    duration: Option<String>,
+) -> anyhow::Result<()> {
+    ctx.defer_ephemeral().await?;
+    let guild = ctx.guild_id().context("Not in a guild")?;
+    let delta = duration
+        .as_deref()
+        .map(|duration| parse_duration(duration).map_err(parse_error))
+        .transpose()?;
+    if delta.is_some_and(|delta| delta <= TimeDelta::zero()) {
+        anyhow::bail!(localized!("settings-duration-positive"));
+    }
+    db_write(ctx.data(), guild, move |state| {
+        state.settings.duration = delta.map(|delta| delta.num_seconds());
+    })?;
+    let language = locale::language(ctx);
+    ctx.reply(match delta {
+        Some(delta) => t!(
+            language,
+            "settings-duration",
+            duration = format_duration(delta, language)
+        ),
+        None => t!(language, "settings-duration-none"),
+    })
+    .await?;
+    Ok(())
+}
+
+#[command(slash_command, guild_only)]
+pub async fn role(
+    ctx: Context<'_, Arc<Db>, anyhow::Error>,
+    role: Option<RoleId>,
+) -> anyhow::Result<()> {
+    ctx.defer_ephemeral().await?;
+    let guild = ctx.guild_id().context("Not in a guild")?;
+    db_write(ctx.data(), guild, move |state| {
+        state.settings.role = role.map(|role| role.get());
+    })?;
+    let language = locale::language(ctx);
+    ctx.reply(match role {
+        Some(role) => t!(language, "settings-role", role = role.mention()),
+        None => t!(language, "settings-role-none"),
+    })
+    .await?;
+    Ok(())
+}
+
+#[command(slash_command, guild_only)]
+pub async fn announcements(
+    ctx: Context<'_, Arc<Db>, anyhow::Error>,
+    #[channel_types("Text", "News")] channel: Option<ChannelId>,
+) -> anyhow::Result<()> {
+    ctx.defer_ephemeral().await?;
+    let guild = ctx.guild_id().context("Not in a guild")?;
+    db_write(ctx.data(), guild, move |state| {
+        state.settings.announcement_channel = channel.map(|channel| channel.get());
+    })?;
+    let language = locale::language(ctx);
+    ctx.reply(match channel {
+        Some(channel) => t!(
+            language,
+            "settings-announcements",
+            channel = channel.mention()
+        ),
+        None => t!(language, "settings-announcements-none"),
+    })
+    .await?;
+    Ok(())
+}
+
+/// Language of new giveaways and of the bot's replies on this server
+#[command(slash_command, guild_only)]
+pub async fn language(
+    ctx: Context<'_, Arc<Db>, anyhow::Error>,
+    language: Language,
+) -> anyhow::Result<()> {
+    ctx.defer_ephemeral().await?;
+    let guild = ctx.guild_id().context("Not in a guild")?;
+    db_write(ctx.data(), guild, move |state| {
+        state.settings.language = language;
+    })?;
+    //  Already in the new language
+    ctx.reply(t!(language, "settings-language", name = language.name()))
+        .await?;
+    Ok(())
+}
+
+#[command(slash_command, guild_only)]
+pub async fn color(
+    ctx: Context<'_, Arc<Db>, anyhow::Error>,
+    color: Option<String>,
+) -> anyhow::Result<()> {
+    ctx.defer_ephemeral().await?;
+    let guild = ctx.guild_id().context("Not in a guild")?;
+    let color = color.as_deref().map(parse_color).transpose()?;
+    db_write(ctx.data(), guild, move |state| {
+        state.settings.color = color;
+    })?;
+    let language = locale::language(ctx);
+    ctx.reply(match color {
+        Some(color) => t!(language, "settings-color", color = format!("#{color:06x}")),
+        None => t!(language, "settings-color-none"),
+    })
+    .await?;
+    Ok(())
+}
+
+/// Leaving out the role mentions @here instead
+#[command(slash_command, guild_only)]
+pub async fn ping_role(
+    ctx: Context<'_, Arc<Db>, anyhow::Error>,
+    role: Option<RoleId>,
+) -> anyhow::Result<()> {
+    ctx.defer_ephemeral().await?;
+    let guild = ctx.guild_id().context("Not in a guild")?;
+    db_write(ctx.data(), guild, move |state| {
+        state.settings.ping_role = role.map(|role| role.get());
+    })?;
+    let language = locale::language(ctx);
+    ctx.reply(match role {
+        Some(role) => t!(language, "settings-ping-role", role = role.mention()),
+        None => t!(language, "settings-ping-role-none"),
+    })
+    .await?;
+    Ok(())
+}
+
+/// Leaving out the minutes turns the reminder off
+#[command(slash_command, guild_only)]
+pub async fn reminder(
+    ctx: Context<'_, Arc<Db>, anyhow::Error>,
+    #[min = 1] minutes: Option<u32>,
+    ping: Option<bool>,
+) -> anyhow::Result<()> {
+    ctx.defer_ephemeral().await?;
+    let guild = ctx.guild_id().context("Not in a guild")?;
+    let ping = ping.unwrap_or(false);
+    db_write(ctx.data(), guild, move |state| {
+        state.settings.reminder = minutes;
+        state.settings.reminder_ping = ping;
+    })?;
+    let language = locale::language(ctx);
+    ctx.reply(match (minutes, ping) {
+        (Some(minutes), true) => t!(language, "settings-reminder-on-ping", minutes),
+        (Some(minutes), false) => t!(language, "settings-reminder-on", minutes),
+        (None, _) => t!(language, "settings-reminder-off"),
+    })
+    .await?;
+    Ok(())
+}
+
+/// The end is shown in the server's timezone, the remaining time is refreshed by the bot
+#[command(slash_command, guild_only)]
+pub async fn countdown(
+    ctx: Context<'_, Arc<Db>, anyhow::Error>,
+    enabled: bool,
+) -> anyhow::Result<()> {
+    ctx.defer_ephemeral().await?;
+    let guild = ctx.guild_id().context("Not in a guild")?;
+    db_write(ctx.data(), guild, move |state| {
+        state.settings.countdown = enabled;
+    })?;
+    let language = locale::language(ctx);
+    ctx.reply(match enabled {
+        true => t!(language, "settings-countdown-on"),
+        false => t!(language, "settings-countdown-off"),
+    })
+    .await?;
+    Ok(())
+}
+
+/// Running and scheduled giveaways both count, leaving out the count removes the limit
+#[command(slash_command, guild_only)]
+pub async fn max_giveaways(
+    ctx: Context<'_, Arc<Db>, anyhow::Error>,
+    #[min = 1] count: Option<u32>,
+) -> anyhow::Result<()> {
+    ctx.defer_ephemeral().await?;
+    let guild = ctx.guild_id().context("Not in a guild")?;
+    db_write(ctx.data(), guild, move |state| {
+        state.settings.max_giveaways = count;
+    })?;
+    let language = locale::language(ctx);
+    ctx.reply(match count {
+        Some(count) => t!(language, "settings-max-giveaways-set", count),
+        None => t!(language, "settings-max-giveaways-none"),
+    })
+    .await?;
+    Ok(())
+}
+
+#[command(slash_command, guild_only)]
+pub async fn repost_deleted(
+    ctx: Context<'_, Arc<Db>, anyhow::Error>,
+    enabled: bool,
+) -> anyhow::Result<()> {
+    ctx.defer_ephemeral().await?;
+    let guild = ctx.guild_id().context("Not in a guild")?;
+    db_write(ctx.data(), guild, move |state| {
+        state.settings.repost_deleted = enabled;
+    })?;
+    let language = locale::language(ctx);
+    ctx.reply(match enabled {
+        true => t!(language, "settings-repost-on"),
+        false => t!(language, "settings-repost-off"),
+    })
+    .await?;
+    Ok(())
+}
+
+/// Leaving out everything resets the button
+#[command(slash_command, guild_only)]
+pub async fn buttons(
+    ctx: Context<'_, Arc<Db>, anyhow::Error>,
+    button: GiveawayButton,
+    #[max_length = 80] label: Option<String>,
+    emoji: Option<String>,
+    color: Option<ButtonColor>,
+) -> anyhow::Result<()> {
+    ctx.defer_ephemeral().await?;
+    let guild = ctx.guild_id().context("Not in a guild")?;
+    if let Some(emoji) = &emoji
+        && !valid_emoji(ctx, guild, emoji).await
+    {
+        anyhow::bail!(localized!("settings-invalid-emoji", emoji));
+    }
+    let custom = CustomButton {
+        label,
+        emoji,
+        color,
+    };
+    let reset = custom.label.is_none() && custom.emoji.is_none() && custom.color.is_none();
+    db_write(ctx.data(), guild, move |state| {
+        *state.settings.buttons.get_mut(button) = custom;
+    })?;
+    let language = locale::language(ctx);
+    let name = button.label(language);
+    ctx.reply(match reset {
+        true => t!(language, "settings-button-reset", name),
+        false => t!(language, "settings-button", name),
+    })
+    .await?;
+    Ok(())
+}
+
+//  Discord takes only unicode emojis and custom emojis of the server, anything else would fail
+//  every giveaway message with the button
+async fn valid_emoji(
+    ctx: Context<'_, Arc<Db>, anyhow::Error>,
+    guild: GuildId,
+    emoji: &str,
+) -> bool {
+    match ReactionType::try_from(emoji) {
+        Ok(ReactionType::Custom { id, .. }) => guild.emoji(ctx.http(), id).await.is_ok(),
+        Ok(ReactionType::Unicode(emoji)) => emojis::get(&emoji).is_some(),
+        _ => false,
+    }
+}
+
+/// Flags new accounts without avatar and roles, leaving out the action turns it off
+#[command(slash_command, guild_only)]
+pub async fn alt_check(
+    ctx: Context<'_, Arc<Db>, anyhow::Error>,
+    action: Option<AltAction>,
+    #[min = 1] account_days: Option<u32>,
+    #[channel_types("Text")] log_channel: Option<ChannelId>,
+) -> anyhow::Result<()> {
+    ctx.defer_ephemeral().await?;
+    let guild = ctx.guild_id().context("Not in a guild")?;
+    let check = action.map(|action| AltCheck {
+        action,
+        min_account_days: account_days.unwrap_or(7),
+        log_channel: log_channel.map(|channel| channel.get()),
+    });
+    db_write(ctx.data(), guild, move |state| {
+        state.settings.alt_check = check;
+    })?;
+    let language = locale::language(ctx);
+    ctx.reply(match check.map(|check| check.action) {
+        Some(AltAction::Flag) => t!(language, "settings-alt-check-flag"),
+        Some(AltAction::Deny) => t!(language, "settings-alt-check-deny"),
+        None => t!(language, "settings-alt-check-off"),
+    })
+    .await?;
+    Ok(())
+}
+
+/// Logs every clear in the channel, leaving out the channel turns it off
+#[command(slash_command, guild_only)]
+pub async fn modlog(
+    ctx: Context<'_, Arc<Db>, anyhow::Error>,
+    #[channel_types("Text")] channel: Option<ChannelId>,
+    transcript: Option<bool>,
+) -> anyhow::Result<()> {
+    ctx.defer_ephemeral().await?;
+    let guild = ctx.guild_id().context("Not in a guild")?;
+    let modlog = channel.map(|channel| Modlog {
+        channel: channel.get(),
+        transcript: transcript.unwrap_or_default(),
+    });
+    db_write(ctx.data(), guild, move |state| {
+        state.settings.modlog = modlog;
+    })?;
+    let language = locale::language(ctx);
+    ctx.reply(match channel {
+        Some(channel) => t!(language, "settings-modlog", channel = channel.mention()),
+        None => t!(language, "settings-modlog-off"),
+    })
+    .await?;
+    Ok(())
+}
+
+/// Used for end and start times given as just a date, leaving it out resets it to 20:00
+#[command(slash_command, guild_only)]
+pub async fn default_time(
+    ctx: Context<'_, Arc<Db>, anyhow::Error>,
+    time: Option<String>,
+) -> anyhow::Result<()> {
+    ctx.defer_ephemeral().await?;
+    let guild = ctx.guild_id().context("Not in a guild")?;
+    let time = time
+        .as_deref()
+        .map(|time| parse_clock(time).map_err(parse_error))
+        .transpose()?;
+    let settings = db_write(ctx.data(), guild, move |state| {
+        state.settings.default_time = time.map(|time| time.num_seconds_from_midnight());
+        state.settings.clone()
+    })?;
+    ctx.reply(t!(
+        settings.language,
+        "settings-default-time",
+        time = settings.default_time().format("%H:%M")
+    ))
+    .await?;
+    Ok(())
+}
+
+/// Days until ended giveaways leave the archive, leaving it out or 0 keeps them forever
+#[command(slash_command, guild_only)]
+pub async fn archive_days(
+    ctx: Context<'_, Arc<Db>, anyhow::Error>,
+    days: Option<u32>,
+) -> anyhow::Result<()> {
+    ctx.defer_ephemeral().await?;
+    let guild = ctx.guild_id().context("Not in a guild")?;
+    let settings = db_write(ctx.data(), guild, move |state| {
+        state.settings.archive_days = days;
+        state.settings.clone()
+    })?;
+    ctx.reply(match settings.archive_days() {
+        Some(days) => t!(settings.language, "settings-archive-days", days),
+        None => t!(settings.language, "settings-archive-days-forever"),
+    })
+    .await?;
+    Ok(())
+}
+
+pub fn parse_color(color: &str) -> anyhow::Result<u32> {
+    u32::from_str_radix(color.trim_start_matches('#'), 16)
+        .ok()
+        .filter(|color| *color <= 0xFFFFFF)
+        .ok_or_else(|| localized!("settings-invalid-color").into())
+}
@@ -0,0 +1,102 @@
+use anyhow::Context as _;
+use chrono::Utc;
+use poise::{Context, CreateReply, command};
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+};
+
+use crate::{
+    db_read,
+    locale::{language, t},
+    storage::Db,
+};
+
+const UPCOMING_COUNT: usize = 5;
+
+/// Summary of the archive, optionally only giveaways ended within the last days
+#[command(
+    slash_command,
+    default_member_permissions = "CREATE_EVENTS",
+    guild_only
+)]
+pub async fn stats(
+    ctx: Context<'_, Arc<Db>, anyhow::Error>,
+    #[min = 1] days: Option<u32>,
+) -> anyhow::Result<()> {
+    ctx.defer_ephemeral().await?;
+    let guild = ctx.guild_id().context("Not in a guild")?;
+    let state = db_read(ctx.data(), guild)?;
+    let since = days.map(|days| Utc::now().timestamp() - i64::from(days) * 24 * 60 * 60);
+    let archived: Vec<_> = state
+        .archive
+        .values()
+        .filter(|archived| since.is_none_or(|since| archived.ended >= since))
+        .collect();
+    let cancelled = archived
+        .iter()
+        .filter(|archived| archived.cancelled)
+        .count();
+    let finished: Vec<_> = archived
+        .iter()
+        .filter(|archived| !archived.cancelled)
+        .collect();
+    let participants: usize = finished
+        .iter()
+        .map(|archived| archived.giveaway.participants.len())
+        .sum();
+    let unique: HashSet<u64> = finished
+        .iter()
+        .flat_map(|archived| archived.giveaway.participants.keys().copied())
+        .collect();
+    let mut channels: HashMap<u64, usize> = HashMap::new();
+    for archived in &finished {
+        *channels.entry(archived.giveaway.channel).or_default() += 1;
+    }
+    let channel = channels
+        .into_iter()
+        .max_by_key(|&(channel, count)| (count, std::cmp::Reverse(channel)));
+
+    let language = language(ctx);
+    let mut content = match days {
+        Some(days) => t!(language, "stats-header-days", days),
+        None => t!(language, "stats-header"),
+    };
+    content.push_str(&t!(language, "stats-finished", count = finished.len()));
+    if cancelled > 0 {
+        content.push_str(&t!(language, "stats-cancelled", count = cancelled));
+    }
+    content.push_str(&t!(language, "stats-unique", count = unique.len()));
+    if !finished.is_empty() {
+        let average = participants as f64 / finished.len() as f64;
+        content.push_str(&t!(
+            language,
+            "stats-average",
+            average = format!("{average:.1}")
+        ));
+    }
+    if let Some((channel, count)) = channel {
+        content.push_str(&t!(
+            language,
+            "stats-channel",
+            channel = format!("<#{channel}>"),
+            count
+        ));
+    }
+
+    let mut upcoming: Vec<_> = state
+        .giveaways
+        .values()
+        .filter_map(|giveaway| giveaway.time.map(|time| (time, &giveaway.title)))
+        .collect();
+    upcoming.sort_unstable();
+    if !upcoming.is_empty() {
+        content.push_str(&t!(language, "stats-upcoming"));
+        for (time, title) in upcoming.into_iter().take(UPCOMING_COUNT) {
+            content.push_str(&format!("\n- **{title}** <t:{time}:R>"));
+        }
+    }
+    ctx.send(CreateReply::default().content(content).ephemeral(true))
+        .await?;
+    Ok(())
+}
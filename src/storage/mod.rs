@@ -0,0 +1,392 @@
+use chrono::Utc;
+use chrono_tz::Tz;
+use log::error;
+use poise::serenity_prelude::GuildId;
+use redb::{CommitError, CompactionError, Database, ReadTransaction, WriteTransaction};
+use std::{
+    collections::HashMap,
+    future::Future,
+    ops::Deref,
+    sync::{
+        Arc, Mutex, RwLock,
+        atomic::{AtomicI64, Ordering},
+    },
+    time::Instant,
+};
+use tokio::sync::OwnedMutexGuard;
+use tokio_util::sync::CancellationToken;
+
+use crate::{
+    activity::ActivityBuffer,
+    entries::{Changes, EntryQueue},
+    metrics::metrics,
+    structs::{Giveaway, GiveawayId, GuildState, Settings},
+};
+
+#[cfg(feature = "sql")]
+mod sql;
+mod tables;
+
+pub(crate) use tables::{
+    ARCHIVE_TABLE, CONFIG_TABLE, DRAFTS_TABLE, GIVEAWAYS_TABLE, PARTICIPANTS_TABLE, update_guild,
+};
+
+/// Keeps the state of every guild, by default in the guild tables of [`Redb`]
+pub trait Storage: Send + Sync {
+    /// The default state for guilds that have nothing stored yet
+    fn read(&self, guild: GuildId) -> anyhow::Result<GuildState>;
+    /// Reads, changes and stores the state of the guild in one transaction
+    fn update(&self, guild: GuildId, update: &mut dyn FnMut(&mut GuildState))
+    -> anyhow::Result<()>;
+    fn guilds(&self) -> anyhow::Result<Vec<(GuildId, GuildState)>>;
+    /// Forgets the guild, it's read as a new one afterwards
+    fn delete(&self, guild: GuildId) -> anyhow::Result<()>;
+    /// A running giveaway without its participants, and whether the user is one of them
+    fn giveaway_entry(
+        &self,
+        guild: GuildId,
+        id: GiveawayId,
+        user: u64,
+    ) -> anyhow::Result<Option<(Giveaway, bool)>> {
+        Ok(self.read(guild)?.giveaways.remove(&id).map(|mut giveaway| {
+            let participates = giveaway.participants.contains_key(&user);
+            giveaway.participants.clear();
+            (giveaway, participates)
+        }))
+    }
+    /// Entries and exits of running giveaways, giveaways that ended are skipped
+    fn update_participants(
+        &self,
+        guild: GuildId,
+        changes: &HashMap<GiveawayId, Changes>,
+    ) -> anyhow::Result<()> {
+        self.update(guild, &mut |state| {
+            for (id, changes) in changes {
+                if let Some(giveaway) = state.giveaways.get_mut(id) {
+                    for (user, change) in changes {
+                        change.apply(&mut giveaway.participants, *user);
+                    }
+                }
+            }
+        })
+    }
+}
+
+/// The redb database, only locked exclusively while it is compacted
+pub struct Redb {
+    db: RwLock<Database>,
+    path: String,
+    /// Unix timestamp of the last write transaction
+    last_write: AtomicI64,
+    /// Unix timestamp of the last compaction, `main` compacts before opening the database
+    last_compaction: AtomicI64,
+}
+
+impl Redb {
+    pub fn begin_read(&self) -> anyhow::Result<ReadTransaction> {
+        Ok(self.db.read().unwrap().begin_read()?)
+    }
+
+    pub fn begin_write(&self) -> anyhow::Result<WriteTx> {
+        self.last_write
+            .store(Utc::now().timestamp(), Ordering::Relaxed);
+        Ok(WriteTx {
+            tx: self.db.read().unwrap().begin_write()?,
+            started: Instant::now(),
+        })
+    }
+
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    pub fn last_write(&self) -> i64 {
+        self.last_write.load(Ordering::Relaxed)
+    }
+
+    pub fn last_compaction(&self) -> i64 {
+        self.last_compaction.load(Ordering::Relaxed)
+    }
+
+    /// Returns the bytes freed in the file, or None if a transaction is still open
+    pub fn compact(&self) -> anyhow::Result<Option<u64>> {
+        let before = std::fs::metadata(&self.path)?.len();
+        match self.db.write().unwrap().compact() {
+            Ok(_) => {}
+            Err(CompactionError::TransactionInProgress) => return Ok(None),
+            Err(err) => return Err(err.into()),
+        }
+        let after = std::fs::metadata(&self.path)?.len();
+        self.last_compaction
+            .store(Utc::now().timestamp(), Ordering::Relaxed);
+        Ok(Some(before.saturating_sub(after)))
+    }
+}
+
+/// A write transaction of [`Redb`], how long it took until the commit goes into the metrics
+pub struct WriteTx {
+    tx: WriteTransaction,
+    started: Instant,
+}
+
+impl WriteTx {
+    pub fn commit(self) -> Result<(), CommitError> {
+        let result = self.tx.commit();
+        metrics().db_write(self.started.elapsed());
+        result
+    }
+}
+
+impl Deref for WriteTx {
+    type Target = WriteTransaction;
+
+    fn deref(&self) -> &WriteTransaction {
+        &self.tx
+    }
+}
+
+impl Storage for Redb {
+    fn read(&self, guild: GuildId) -> anyhow::Result<GuildState> {
+        tables::read_guild(&self.begin_read()?, guild.get())
+    }
+
+    fn update(
+        &self,
+        guild: GuildId,
+        update: &mut dyn FnMut(&mut GuildState),
+    ) -> anyhow::Result<()> {
+        let db = self.begin_write()?;
+        update_guild(&db, guild.get(), update)?;
+        db.commit()?;
+        Ok(())
+    }
+
+    fn guilds(&self) -> anyhow::Result<Vec<(GuildId, GuildState)>> {
+        let db_read = self.begin_read()?;
+        tables::guild_ids(&db_read)?
+            .into_iter()
+            .map(|guild| Ok((GuildId::new(guild), tables::read_guild(&db_read, guild)?)))
+            .collect()
+    }
+
+    fn delete(&self, guild: GuildId) -> anyhow::Result<()> {
+        let db = self.begin_write()?;
+        tables::delete_guild(&db, guild.get())?;
+        db.commit()?;
+        Ok(())
+    }
+
+    //  Looked up by their keys, every button press comes here
+    fn giveaway_entry(
+        &self,
+        guild: GuildId,
+        id: GiveawayId,
+        user: u64,
+    ) -> anyhow::Result<Option<(Giveaway, bool)>> {
+        tables::giveaway_entry(&self.begin_read()?, guild.get(), id, user)
+    }
+
+    //  Participants have their own table, so this doesn't read the guild
+    fn update_participants(
+        &self,
+        guild: GuildId,
+        changes: &HashMap<GiveawayId, Changes>,
+    ) -> anyhow::Result<()> {
+        let db = self.begin_write()?;
+        tables::update_participants(&db, guild.get(), changes)?;
+        db.commit()?;
+        Ok(())
+    }
+}
+
+/// The part of a guild state most handlers need, kept in memory by [`Db::settings`]
+#[derive(Debug)]
+pub struct CachedSettings {
+    pub timezone: Tz,
+    pub plain_text: bool,
+    pub settings: Settings,
+}
+
+impl From<&GuildState> for CachedSettings {
+    fn from(state: &GuildState) -> Self {
+        Self {
+            timezone: state.timezone.parse().unwrap_or(chrono_tz::CET),
+            plain_text: state.plain_text,
+            settings: state.settings.clone(),
+        }
+    }
+}
+
+impl Deref for CachedSettings {
+    type Target = Settings;
+
+    fn deref(&self) -> &Settings {
+        &self.settings
+    }
+}
+
+/// The redb database, which also keeps the guild states unless the bot is built with the `sql`
+/// feature and `database_url` is set
+pub struct Db {
+    redb: Redb,
+    #[cfg(feature = "sql")]
+    sql: Option<sql::SqlStorage>,
+    guild_locks: Mutex<HashMap<GuildId, Arc<tokio::sync::Mutex<()>>>>,
+    settings_cache: RwLock<HashMap<GuildId, Arc<CachedSettings>>>,
+    entries: EntryQueue,
+    activity: ActivityBuffer,
+    /// Cancelled when the bot leaves the guild, see [`Db::spawn_for_guild`]
+    guild_tasks: Mutex<HashMap<GuildId, CancellationToken>>,
+}
+
+impl Db {
+    /// Keeps the guild states in SQL with a `url`, which needs the `sql` feature
+    pub async fn open(db: Database, path: String, url: Option<String>) -> anyhow::Result<Self> {
+        let redb = Redb {
+            db: RwLock::new(db),
+            path,
+            last_write: AtomicI64::new(Utc::now().timestamp()),
+            last_compaction: AtomicI64::new(Utc::now().timestamp()),
+        };
+        #[cfg(not(feature = "sql"))]
+        if url.is_some() {
+            log::warn!("Ignoring the database URL, the bot was built without the sql feature");
+        }
+        #[cfg(feature = "sql")]
+        if let Some(url) = url {
+            let sql = sql::SqlStorage::connect(&url).await?;
+            //  The first start with SQL takes over everything stored in redb so far. The URL isn't
+            //  logged, it may contain the password
+            if let Some(count) = sql.copy_from(|| redb.guilds())? {
+                log::info!("Copied {count} guilds from redb to SQL");
+            }
+            return Ok(Self {
+                redb,
+                sql: Some(sql),
+                guild_locks: Mutex::default(),
+                settings_cache: RwLock::default(),
+                entries: EntryQueue::default(),
+                activity: ActivityBuffer::default(),
+                guild_tasks: Mutex::default(),
+            });
+        }
+        Ok(Self {
+            redb,
+            #[cfg(feature = "sql")]
+            sql: None,
+            guild_locks: Mutex::default(),
+            settings_cache: RwLock::default(),
+            entries: EntryQueue::default(),
+            activity: ActivityBuffer::default(),
+            guild_tasks: Mutex::default(),
+        })
+    }
+
+    /// The settings of the guild, only read from the storage again after they were written
+    pub fn settings(&self, guild: GuildId) -> anyhow::Result<Arc<CachedSettings>> {
+        if let Some(cached) = self.settings_cache.read().unwrap().get(&guild) {
+            return Ok(cached.clone());
+        }
+        let cached = Arc::new(CachedSettings::from(&self.storage().read(guild)?));
+        //  A write in the meantime already cached newer settings
+        Ok(self
+            .settings_cache
+            .write()
+            .unwrap()
+            .entry(guild)
+            .or_insert(cached)
+            .clone())
+    }
+
+    /// Like [`Storage::update`], but keeps the cached settings up to date
+    pub fn write(
+        &self,
+        guild: GuildId,
+        update: &mut dyn FnMut(&mut GuildState),
+    ) -> anyhow::Result<()> {
+        let result = self.storage().update(guild, &mut |state| {
+            update(state);
+            //  Still inside the transaction, so the cache is updated in the order of the writes
+            self.settings_cache
+                .write()
+                .unwrap()
+                .insert(guild, Arc::new(CachedSettings::from(&*state)));
+        });
+        if result.is_err() {
+            self.forget_settings(guild);
+        }
+        result
+    }
+
+    /// For writes that bypass [`Db::write`]
+    pub fn forget_settings(&self, guild: GuildId) {
+        self.settings_cache.write().unwrap().remove(&guild);
+    }
+
+    /// Held while a giveaway is changed in several steps with Discord requests in between, e.g.
+    /// taken out of the state, finished and archived, so other changes to the guild wait for it.
+    /// It isn't reentrant, so only events, commands and tasks take it, never the helpers they call.
+    /// Queued entries of the guild are written before it is handed out
+    pub async fn lock_guild(&self, guild: GuildId) -> OwnedMutexGuard<()> {
+        let lock = self
+            .guild_locks
+            .lock()
+            .unwrap()
+            .entry(guild)
+            .or_default()
+            .clone();
+        let guard = lock.lock_owned().await;
+        if let Err(err) = self.entries.flush(self, guild) {
+            error!("Error writing queued entries: {}", err);
+        }
+        guard
+    }
+
+    /// Runs a scheduled task of the guild, unless the bot leaves the guild before it's done
+    pub fn spawn_for_guild(&self, guild: GuildId, task: impl Future<Output = ()> + Send + 'static) {
+        let token = self
+            .guild_tasks
+            .lock()
+            .unwrap()
+            .entry(guild)
+            .or_default()
+            .clone();
+        tokio::spawn(async move {
+            tokio::select! {
+                _ = token.cancelled() => {}
+                _ = task => {}
+            }
+        });
+    }
+
+    /// Tasks spawned afterwards run again, e.g. after the bot was invited back
+    pub fn cancel_guild_tasks(&self, guild: GuildId) {
+        if let Some(token) = self.guild_tasks.lock().unwrap().remove(&guild) {
+            token.cancel();
+        }
+    }
+
+    pub fn entries(&self) -> &EntryQueue {
+        &self.entries
+    }
+
+    pub fn activity(&self) -> &ActivityBuffer {
+        &self.activity
+    }
+
+    pub fn storage(&self) -> &dyn Storage {
+        #[cfg(feature = "sql")]
+        if let Some(sql) = &self.sql {
+            return sql;
+        }
+        &self.redb
+    }
+}
+
+impl Deref for Db {
+    type Target = Redb;
+
+    fn deref(&self) -> &Redb {
+        &self.redb
+    }
+}
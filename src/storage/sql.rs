@@ -0,0 +1,151 @@
+use anyhow::Context as _;
+use bincode::{config, encode_to_vec};
+use poise::serenity_prelude::GuildId;
+use sqlx::{Any, AnyPool, Transaction, any::install_default_drivers};
+use std::{future::Future, time::Instant};
+
+use super::Storage;
+use crate::{legacy::decode_guild, metrics::metrics, structs::GuildState};
+
+/// Guild states in SQLite or Postgres, encoded like in redb.
+/// Older layouts are converted when read and stored in the current one with the next update
+pub struct SqlStorage {
+    pool: AnyPool,
+    postgres: bool,
+}
+
+impl SqlStorage {
+    pub async fn connect(url: &str) -> anyhow::Result<Self> {
+        install_default_drivers();
+        let pool = AnyPool::connect(url).await?;
+        let postgres = url.starts_with("postgres");
+        let blob = if postgres { "BYTEA" } else { "BLOB" };
+        sqlx::query(&format!(
+            "CREATE TABLE IF NOT EXISTS guilds (id BIGINT PRIMARY KEY, state {blob} NOT NULL)"
+        ))
+        .execute(&pool)
+        .await?;
+        Ok(Self { pool, postgres })
+    }
+
+    //  SQLite only takes the write lock with the first write otherwise, so two updates could read
+    //  the same state and one of them would be lost
+    async fn begin(&self) -> sqlx::Result<Transaction<'static, Any>> {
+        if self.postgres {
+            self.pool.begin().await
+        } else {
+            self.pool.begin_with("BEGIN IMMEDIATE").await
+        }
+    }
+
+    /// Takes over the guilds stored in redb so far, unless there are guilds in SQL already.
+    /// Everything is copied in one transaction, so an interrupted copy is simply repeated on the
+    /// next start. Returns how many guilds were copied
+    pub fn copy_from(
+        &self,
+        guilds: impl FnOnce() -> anyhow::Result<Vec<(GuildId, GuildState)>>,
+    ) -> anyhow::Result<Option<usize>> {
+        block_on(async {
+            let mut tx = self.begin().await?;
+            let (stored,): (i64,) = sqlx::query_as("SELECT COUNT(*) FROM guilds")
+                .fetch_one(&mut *tx)
+                .await?;
+            if stored > 0 {
+                return Ok(None);
+            }
+            let guilds = guilds()?;
+            let count = guilds.len();
+            for (guild, state) in guilds {
+                sqlx::query("INSERT INTO guilds (id, state) VALUES ($1, $2)")
+                    .bind(guild.get() as i64)
+                    .bind(encode_to_vec(&state, config::standard())?)
+                    .execute(&mut *tx)
+                    .await?;
+            }
+            tx.commit().await?;
+            Ok(Some(count))
+        })
+    }
+}
+
+//  The storage is used from synchronous code, which always runs inside the multi-threaded runtime
+fn block_on<T>(future: impl Future<Output = T>) -> T {
+    tokio::task::block_in_place(|| tokio::runtime::Handle::current().block_on(future))
+}
+
+fn decode(data: &[u8]) -> anyhow::Result<GuildState> {
+    decode_guild(data).context("Unknown layout of a stored guild")
+}
+
+impl Storage for SqlStorage {
+    fn read(&self, guild: GuildId) -> anyhow::Result<GuildState> {
+        let row: Option<(Vec<u8>,)> = block_on(
+            sqlx::query_as("SELECT state FROM guilds WHERE id = $1")
+                .bind(guild.get() as i64)
+                .fetch_optional(&self.pool),
+        )?;
+        Ok(row
+            .map(|(state,)| decode(&state))
+            .transpose()?
+            .unwrap_or_default())
+    }
+
+    fn update(
+        &self,
+        guild: GuildId,
+        update: &mut dyn FnMut(&mut GuildState),
+    ) -> anyhow::Result<()> {
+        block_on(async {
+            let started = Instant::now();
+            let mut tx = self.begin().await?;
+            //  SQLite locks the whole database on write, Postgres needs the row locked. A row
+            //  that doesn't exist yet can't be locked, so it's created first
+            sqlx::query(
+                "INSERT INTO guilds (id, state) VALUES ($1, $2) ON CONFLICT (id) DO NOTHING",
+            )
+            .bind(guild.get() as i64)
+            .bind(encode_to_vec(GuildState::default(), config::standard())?)
+            .execute(&mut *tx)
+            .await?;
+            let lock = if self.postgres { " FOR UPDATE" } else { "" };
+            let row: Option<(Vec<u8>,)> =
+                sqlx::query_as(&format!("SELECT state FROM guilds WHERE id = $1{lock}"))
+                    .bind(guild.get() as i64)
+                    .fetch_optional(&mut *tx)
+                    .await?;
+            let mut state = row
+                .map(|(state,)| decode(&state))
+                .transpose()?
+                .unwrap_or_default();
+            update(&mut state);
+            sqlx::query(
+                "INSERT INTO guilds (id, state) VALUES ($1, $2) \
+                ON CONFLICT (id) DO UPDATE SET state = excluded.state",
+            )
+            .bind(guild.get() as i64)
+            .bind(encode_to_vec(&state, config::standard())?)
+            .execute(&mut *tx)
+            .await?;
+            tx.commit().await?;
+            metrics().db_write(started.elapsed());
+            Ok(())
+        })
+    }
+
+    fn guilds(&self) -> anyhow::Result<Vec<(GuildId, GuildState)>> {
+        let rows: Vec<(i64, Vec<u8>)> =
+            block_on(sqlx::query_as("SELECT id, state FROM guilds").fetch_all(&self.pool))?;
+        rows.into_iter()
+            .map(|(guild, state)| Ok((GuildId::new(guild as u64), decode(&state)?)))
+            .collect()
+    }
+
+    fn delete(&self, guild: GuildId) -> anyhow::Result<()> {
+        block_on(
+            sqlx::query("DELETE FROM guilds WHERE id = $1")
+                .bind(guild.get() as i64)
+                .execute(&self.pool),
+        )?;
+        Ok(())
+    }
+}
@@ -0,0 +1,367 @@
+use bincode::{Decode, Encode, config, encode_to_vec};
+use log::warn;
+use redb::{ReadTransaction, ReadableTable, Table, TableDefinition, WriteTransaction};
+use std::{collections::HashMap, fmt::Debug};
+
+use crate::{
+    bc::{Raw, decode_exact},
+    entries::{Change, Changes},
+    structs::{ArchivedGiveaway, Draft, Entry, Giveaway, GiveawayId, GuildState, Settings},
+};
+
+//  Values are read undecoded, so an entry in an unknown layout is skipped instead of panicking
+pub(crate) const CONFIG_TABLE: TableDefinition<u64, Raw<GuildConfig>> =
+    TableDefinition::new("guild_config");
+pub(crate) const GIVEAWAYS_TABLE: TableDefinition<(u64, u64), Raw<Giveaway>> =
+    TableDefinition::new("giveaways");
+pub(crate) const ARCHIVE_TABLE: TableDefinition<(u64, u64), Raw<ArchivedGiveaway>> =
+    TableDefinition::new("archive");
+pub(crate) const DRAFTS_TABLE: TableDefinition<(u64, u64), Raw<Draft>> =
+    TableDefinition::new("drafts");
+//  Running giveaways are stored without their participants, which get a row each keyed by guild,
+//  giveaway and user, so entering is a single insert
+pub(crate) const PARTICIPANTS_TABLE: TableDefinition<(u64, u64, u64), Raw<Entry>> =
+    TableDefinition::new("participants");
+
+type Participants = HashMap<GiveawayId, HashMap<u64, Entry>>;
+
+/// Everything of a [`GuildState`] that isn't kept per giveaway
+#[derive(Debug, Encode, Decode)]
+pub struct GuildConfig {
+    timezone: String,
+    plain_text: bool,
+    settings: Settings,
+}
+
+/// The stored bytes of every entry of a guild, to only write back what changed
+#[derive(Default)]
+struct Stored {
+    config: Option<StoredConfig>,
+    giveaways: HashMap<GiveawayId, Vec<u8>>,
+    archive: HashMap<GiveawayId, Vec<u8>>,
+    drafts: HashMap<GiveawayId, Vec<u8>>,
+    participants: Participants,
+}
+
+enum StoredConfig {
+    Decoded(Vec<u8>),
+    /// E.g. written by a newer version, it's never overwritten
+    Unknown,
+}
+
+impl GuildConfig {
+    fn encode(state: &GuildState) -> anyhow::Result<Vec<u8>> {
+        Ok(encode_to_vec(
+            GuildConfig {
+                timezone: state.timezone.clone(),
+                plain_text: state.plain_text,
+                settings: state.settings.clone(),
+            },
+            config::standard(),
+        )?)
+    }
+}
+
+fn read_config(
+    table: &impl ReadableTable<u64, Raw<GuildConfig>>,
+    guild: u64,
+    state: &mut GuildState,
+) -> anyhow::Result<Option<StoredConfig>> {
+    let Some(data) = table.get(guild)? else {
+        return Ok(None);
+    };
+    let data = data.value().to_vec();
+    match decode_exact::<GuildConfig>(&data) {
+        Some(config) => {
+            state.timezone = config.timezone;
+            state.plain_text = config.plain_text;
+            state.settings = config.settings;
+            Ok(Some(StoredConfig::Decoded(data)))
+        }
+        None => {
+            warn!("Unknown layout for the settings of guild {guild}, using the defaults");
+            Ok(Some(StoredConfig::Unknown))
+        }
+    }
+}
+
+fn read_entries<T>(
+    table: &impl ReadableTable<(u64, u64), Raw<T>>,
+    guild: u64,
+    entries: &mut HashMap<GiveawayId, T>,
+    stored: &mut HashMap<GiveawayId, Vec<u8>>,
+) -> anyhow::Result<()>
+where
+    T: Debug + Decode<()> + 'static,
+{
+    for entry in table.range((guild, 0)..=(guild, u64::MAX))? {
+        let (key, data) = entry?;
+        let id = GiveawayId(key.value().1);
+        match decode_exact::<T>(data.value()) {
+            Some(value) => {
+                entries.insert(id, value);
+                stored.insert(id, data.value().to_vec());
+            }
+            None => warn!("Unknown layout for {id} in {guild}, skipping"),
+        }
+    }
+    Ok(())
+}
+
+//  Entries that weren't decoded aren't in `stored` and stay untouched
+fn write_entries<T>(
+    table: &mut Table<(u64, u64), Raw<T>>,
+    guild: u64,
+    entries: &HashMap<GiveawayId, T>,
+    stored: &HashMap<GiveawayId, Vec<u8>>,
+) -> anyhow::Result<()>
+where
+    T: Debug + Encode + 'static,
+{
+    for (id, value) in entries {
+        let data = encode_to_vec(value, config::standard())?;
+        if stored.get(id) != Some(&data) {
+            table.insert((guild, id.0), data.as_slice())?;
+        }
+    }
+    for id in stored.keys().filter(|id| !entries.contains_key(id)) {
+        table.remove((guild, id.0))?;
+    }
+    Ok(())
+}
+
+//  Only read for giveaways that were decoded, so the rows of the others stay untouched
+fn read_participants(
+    table: &impl ReadableTable<(u64, u64, u64), Raw<Entry>>,
+    guild: u64,
+    giveaways: &mut HashMap<GiveawayId, Giveaway>,
+    stored: &mut Participants,
+) -> anyhow::Result<()> {
+    for (id, giveaway) in giveaways {
+        let mut participants = HashMap::new();
+        for entry in table.range((guild, id.0, 0)..=(guild, id.0, u64::MAX))? {
+            let (key, data) = entry?;
+            let user = key.value().2;
+            match decode_exact::<Entry>(data.value()) {
+                Some(entry) => {
+                    participants.insert(user, entry);
+                }
+                None => warn!("Unknown layout for the entry of {user} in {id}, skipping"),
+            }
+        }
+        stored.insert(*id, participants.clone());
+        giveaway.participants = participants;
+    }
+    Ok(())
+}
+
+fn write_participants(
+    table: &mut Table<(u64, u64, u64), Raw<Entry>>,
+    guild: u64,
+    participants: &Participants,
+    stored: &Participants,
+) -> anyhow::Result<()> {
+    let empty = HashMap::new();
+    for (id, entries) in participants {
+        let before = stored.get(id).unwrap_or(&empty);
+        for (user, entry) in entries {
+            if before.get(user) != Some(entry) {
+                let data = encode_to_vec(entry, config::standard())?;
+                table.insert((guild, id.0, *user), data.as_slice())?;
+            }
+        }
+    }
+    //  Also removes the participants of giveaways that ended
+    for (id, before) in stored {
+        let entries = participants.get(id).unwrap_or(&empty);
+        for user in before.keys().filter(|user| !entries.contains_key(user)) {
+            table.remove((guild, id.0, *user))?;
+        }
+    }
+    Ok(())
+}
+
+pub fn read_guild(r: &ReadTransaction, guild: u64) -> anyhow::Result<GuildState> {
+    let mut state = GuildState::default();
+    let mut stored = Stored::default();
+    read_config(&r.open_table(CONFIG_TABLE)?, guild, &mut state)?;
+    read_entries(
+        &r.open_table(GIVEAWAYS_TABLE)?,
+        guild,
+        &mut state.giveaways,
+        &mut stored.giveaways,
+    )?;
+    read_participants(
+        &r.open_table(PARTICIPANTS_TABLE)?,
+        guild,
+        &mut state.giveaways,
+        &mut stored.participants,
+    )?;
+    read_entries(
+        &r.open_table(ARCHIVE_TABLE)?,
+        guild,
+        &mut state.archive,
+        &mut stored.archive,
+    )?;
+    read_entries(
+        &r.open_table(DRAFTS_TABLE)?,
+        guild,
+        &mut state.drafts,
+        &mut stored.drafts,
+    )?;
+    Ok(state)
+}
+
+/// The giveaway without reading its participants, only whether the user is one of them
+pub fn giveaway_entry(
+    r: &ReadTransaction,
+    guild: u64,
+    id: GiveawayId,
+    user: u64,
+) -> anyhow::Result<Option<(Giveaway, bool)>> {
+    let Some(data) = r.open_table(GIVEAWAYS_TABLE)?.get((guild, id.0))? else {
+        return Ok(None);
+    };
+    let Some(giveaway) = decode_exact::<Giveaway>(data.value()) else {
+        warn!("Unknown layout for {id} in {guild}, skipping");
+        return Ok(None);
+    };
+    let participates = r
+        .open_table(PARTICIPANTS_TABLE)?
+        .get((guild, id.0, user))?
+        .is_some();
+    Ok(Some((giveaway, participates)))
+}
+
+/// Reads, changes and writes back a guild, only the entries that changed are written
+pub fn update_guild(
+    w: &WriteTransaction,
+    guild: u64,
+    update: &mut dyn FnMut(&mut GuildState),
+) -> anyhow::Result<()> {
+    let mut config_table = w.open_table(CONFIG_TABLE)?;
+    let mut giveaways_table = w.open_table(GIVEAWAYS_TABLE)?;
+    let mut archive_table = w.open_table(ARCHIVE_TABLE)?;
+    let mut drafts_table = w.open_table(DRAFTS_TABLE)?;
+    let mut participants_table = w.open_table(PARTICIPANTS_TABLE)?;
+
+    let mut state = GuildState::default();
+    let mut stored = Stored {
+        config: read_config(&config_table, guild, &mut state)?,
+        ..Default::default()
+    };
+    read_entries(
+        &giveaways_table,
+        guild,
+        &mut state.giveaways,
+        &mut stored.giveaways,
+    )?;
+    read_participants(
+        &participants_table,
+        guild,
+        &mut state.giveaways,
+        &mut stored.participants,
+    )?;
+    read_entries(
+        &archive_table,
+        guild,
+        &mut state.archive,
+        &mut stored.archive,
+    )?;
+    read_entries(&drafts_table, guild, &mut state.drafts, &mut stored.drafts)?;
+
+    update(&mut state);
+
+    let config = GuildConfig::encode(&state)?;
+    match &stored.config {
+        //  The defaults stand in for settings that couldn't be read, writing them would lose the
+        //  real ones for good. Only changing them is refused, the giveaways are still written.
+        Some(StoredConfig::Unknown) => {
+            if config != GuildConfig::encode(&GuildState::default())? {
+                anyhow::bail!(
+                    "The settings of guild {guild} have an unknown layout and can't be changed"
+                );
+            }
+        }
+        Some(StoredConfig::Decoded(data)) if *data == config => {}
+        //  Always stored, so every guild that was ever written has a row in the config table
+        _ => {
+            config_table.insert(guild, config.as_slice())?;
+        }
+    }
+    let participants: Participants = state
+        .giveaways
+        .iter_mut()
+        .map(|(id, giveaway)| (*id, std::mem::take(&mut giveaway.participants)))
+        .collect();
+    write_entries(
+        &mut giveaways_table,
+        guild,
+        &state.giveaways,
+        &stored.giveaways,
+    )?;
+    write_participants(
+        &mut participants_table,
+        guild,
+        &participants,
+        &stored.participants,
+    )?;
+    write_entries(&mut archive_table, guild, &state.archive, &stored.archive)?;
+    write_entries(&mut drafts_table, guild, &state.drafts, &stored.drafts)?;
+    Ok(())
+}
+
+/// Writes entries and exits without reading the guild, giveaways that ended are skipped
+pub fn update_participants(
+    w: &WriteTransaction,
+    guild: u64,
+    changes: &HashMap<GiveawayId, Changes>,
+) -> anyhow::Result<()> {
+    let giveaways = w.open_table(GIVEAWAYS_TABLE)?;
+    let mut participants = w.open_table(PARTICIPANTS_TABLE)?;
+    for (id, changes) in changes {
+        if giveaways.get((guild, id.0))?.is_none() {
+            continue;
+        }
+        for (user, change) in changes {
+            let key = (guild, id.0, *user);
+            match change {
+                Change::Enter(entry) => {
+                    if participants.get(key)?.is_none() {
+                        let data = encode_to_vec(entry, config::standard())?;
+                        participants.insert(key, data.as_slice())?;
+                    }
+                }
+                Change::Reenter(entry) => {
+                    let data = encode_to_vec(entry, config::standard())?;
+                    participants.insert(key, data.as_slice())?;
+                }
+                Change::Leave => {
+                    participants.remove(key)?;
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Removes the settings and every giveaway, draft and participant of the guild
+pub fn delete_guild(w: &WriteTransaction, guild: u64) -> anyhow::Result<()> {
+    w.open_table(CONFIG_TABLE)?.remove(guild)?;
+    let all = (guild, 0)..=(guild, u64::MAX);
+    w.open_table(GIVEAWAYS_TABLE)?
+        .retain_in(all.clone(), |_, _| false)?;
+    w.open_table(ARCHIVE_TABLE)?
+        .retain_in(all.clone(), |_, _| false)?;
+    w.open_table(DRAFTS_TABLE)?.retain_in(all, |_, _| false)?;
+    w.open_table(PARTICIPANTS_TABLE)?
+        .retain_in((guild, 0, 0)..=(guild, u64::MAX, u64::MAX), |_, _| false)?;
+    Ok(())
+}
+
+pub fn guild_ids(r: &ReadTransaction) -> anyhow::Result<Vec<u64>> {
+    r.open_table(CONFIG_TABLE)?
+        .iter()?
+        .map(|entry| Ok(entry?.0.value()))
+        .collect()
+}
@@ -1,9 +1,23 @@
 use bincode::{Decode, Encode};
-use chrono::{DateTime, Utc};
-use poise::serenity_prelude::{Cache, CacheHttp, ChannelId, GuildId, Http, MessageId, UserId};
+use chrono::{DateTime, NaiveTime, Utc};
+use chrono_tz::Tz;
+use poise::ChoiceParameter;
+use poise::serenity_prelude::{
+    ButtonStyle, Cache, CacheHttp, ChannelId, CreateEmbed, CreateEmbedFooter, CreateMessage,
+    EditMessage, GuildId, Http, Member, Mentionable, MessageId, UserId,
+};
 use serde::{Deserialize, Serialize};
+
+use crate::{
+    activity::Activity,
+    datetime::format_duration,
+    locale::{Localized, localized, t},
+};
 use std::{
-    collections::{HashMap, HashSet},
+    collections::HashMap,
+    fmt::{Display, Formatter},
+    num::ParseIntError,
+    str::FromStr,
     sync::Arc,
 };
 
@@ -18,7 +32,7 @@ impl MyHttpCache {
 
 impl CacheHttp for MyHttpCache {
     fn http(&self) -> &Http {
-        &*self.0
+        &self.0
     }
 
     fn cache(&self) -> Option<&Arc<Cache>> {
@@ -26,73 +40,925 @@ impl CacheHttp for MyHttpCache {
     }
 }
 
-#[derive(Debug, Encode, Decode)]
+/// Everything stored for a guild, layout changes need a migration in [`crate::migrations`]
+#[derive(Debug, Encode, Decode, Serialize, Deserialize)]
 pub struct GuildState {
     pub timezone: String,
+    /// New giveaways are posted as plain text instead of an embed
+    pub plain_text: bool,
     pub giveaways: HashMap<GiveawayId, Giveaway>,
+    pub archive: HashMap<GiveawayId, ArchivedGiveaway>,
+    pub drafts: HashMap<GiveawayId, Draft>,
+    pub settings: Settings,
 }
 
 impl Default for GuildState {
     fn default() -> Self {
         Self {
             timezone: chrono_tz::CET.name().to_string(),
+            plain_text: false,
             giveaways: HashMap::new(),
+            archive: HashMap::new(),
+            drafts: HashMap::new(),
+            settings: Settings::default(),
         }
     }
 }
 
+/// Defaults for new giveaways, set with /settings
+#[derive(Debug, Clone, Default, Encode, Decode, Serialize, Deserialize)]
+pub struct Settings {
+    pub winners: Option<u32>,
+    /// In seconds
+    pub duration: Option<i64>,
+    pub role: Option<u64>,
+    /// Winners are announced here instead of below the giveaway
+    pub announcement_channel: Option<u64>,
+    pub language: Language,
+    pub color: Option<u32>,
+    /// Mentioned when a giveaway is posted with ping, @here if unset
+    pub ping_role: Option<u64>,
+    /// Minutes before the end of a giveaway to post a reminder
+    pub reminder: Option<u32>,
+    /// The reminder mentions the ping role as well
+    pub reminder_ping: bool,
+    /// New giveaways show the end as absolute time instead of Discord timestamps
+    pub countdown: bool,
+    /// Maximum number of running and scheduled giveaways
+    pub max_giveaways: Option<u32>,
+    /// Giveaways, whose message was deleted, are posted again instead of cancelled
+    pub repost_deleted: bool,
+    pub buttons: Buttons,
+    pub alt_check: Option<AltCheck>,
+    pub modlog: Option<Modlog>,
+    /// Time of day for end times given as just a date, in seconds after midnight
+    pub default_time: Option<u32>,
+    /// Days ended giveaways are archived for, unset or 0 keeps them forever
+    pub archive_days: Option<u32>,
+}
+
+impl Settings {
+    pub fn default_time(&self) -> NaiveTime {
+        self.default_time
+            .and_then(|secs| NaiveTime::from_num_seconds_from_midnight_opt(secs, 0))
+            .unwrap_or(NaiveTime::from_hms_opt(20, 0, 0).unwrap())
+    }
+
+    /// None if the archive is never pruned, pruning is up to the guild
+    pub fn archive_days(&self) -> Option<u32> {
+        self.archive_days.filter(|&days| days > 0)
+    }
+}
+
+/// Where clears are logged for accountability
+#[derive(Debug, Clone, Copy, Encode, Decode, Serialize, Deserialize)]
+pub struct Modlog {
+    pub channel: u64,
+    /// Attaches the deleted messages as text file
+    pub transcript: bool,
+}
+
+/// Flags entries from accounts, that look like they were made just for giveaways
+#[derive(Debug, Clone, Copy, Encode, Decode, Serialize, Deserialize)]
+pub struct AltCheck {
+    pub action: AltAction,
+    /// Accounts younger than this are suspicious
+    pub min_account_days: u32,
+    /// Flagged entries are reported here for review
+    pub log_channel: Option<u64>,
+}
+
+impl AltCheck {
+    /// Only all signs together count, each one alone is common for real members
+    pub fn suspicious(&self, member: &Member) -> bool {
+        let age = Utc::now().timestamp() - member.user.id.created_at().unix_timestamp();
+        member.user.avatar.is_none()
+            && member.roles.is_empty()
+            && age < i64::from(self.min_account_days) * 24 * 60 * 60
+    }
+}
+
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, Encode, Decode, Serialize, Deserialize, ChoiceParameter,
+)]
+pub enum AltAction {
+    /// The entry counts, but is reported
+    #[name = "Melden"]
+    Flag,
+    #[name = "Ablehnen"]
+    Deny,
+}
+
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    Default,
+    PartialEq,
+    Eq,
+    Encode,
+    Decode,
+    Serialize,
+    Deserialize,
+    ChoiceParameter,
+)]
+pub enum Language {
+    #[default]
+    #[name = "Deutsch"]
+    German,
+    #[name = "English"]
+    English,
+}
+
 /// This is just a data collection, no functionality behind it
-#[derive(Debug, Clone, Encode, Decode)]
+#[derive(Debug, Clone, Encode, Decode, Serialize, Deserialize)]
 pub struct Giveaway {
     pub title: String,
     pub description: String,
-    pub participants: HashSet<u64>,
-    pub winners: u32,
+    pub participants: HashMap<u64, Entry>,
+    pub prizes: Prizes,
     pub channel: u64,
     pub message: u64,
     pub time: Option<i64>,
+    pub requirements: Requirements,
+    pub style: Style,
+    /// Unknown for giveaways from older versions
+    pub created: Option<i64>,
+    /// Winners have to claim their prize within this many hours, 0 if they don't have to
+    pub claim_hours: u32,
+    /// Unknown for giveaways from older versions
+    pub host: Option<u64>,
+    /// Participants, who didn't win, get a direct message
+    pub notify_losers: bool,
+    pub auto_extend: Option<AutoExtend>,
+    pub winner_role: Option<WinnerRole>,
+    pub secret_codes: Vec<SecretCode>,
+    /// Discord scheduled event kept in sync with the giveaway
+    pub event: Option<u64>,
+    pub question: Option<EntryQuestion>,
+    /// Copies of the message in other channels, as channel and message
+    pub mirrors: Vec<(u64, u64)>,
+    /// None for giveaways from older versions, their seed was only chosen at the draw
+    pub commitment: Option<Commitment>,
+}
+
+/// The seed and salt of the draw, chosen when the giveaway is posted. Only the hash is shown until
+/// the winners are drawn, so nobody can pick a seed that suits them
+#[derive(Debug, Clone, Encode, Decode, Serialize, Deserialize)]
+pub struct Commitment {
+    /// Encrypted like the secret codes, so not even an export shows the seed early
+    pub sealed: Vec<u8>,
+    pub hash: String,
+}
+
+/// The end is postponed, while there are too few participants
+#[derive(Debug, Clone, Copy, Encode, Decode, Serialize, Deserialize)]
+pub struct AutoExtend {
+    pub min_participants: u32,
+    /// In seconds
+    pub extend_by: i64,
+    pub max_extensions: u32,
+    /// Number of times the giveaway was extended so far
+    pub extensions: u32,
+}
+
+/// Has to be answered before entering, keeps out members blindly clicking every button
+#[derive(Debug, Clone, Encode, Decode, Serialize, Deserialize)]
+pub struct EntryQuestion {
+    pub text: String,
+    /// Any of these is accepted, ignoring case
+    pub answers: Vec<String>,
+}
+
+impl EntryQuestion {
+    pub fn accepts(&self, answer: &str) -> bool {
+        let answer = answer.trim().to_lowercase();
+        self.answers
+            .iter()
+            .any(|accepted| accepted.to_lowercase() == answer)
+    }
+}
+
+/// A secret prize, e.g. a Steam key, that is only ever sent to its winner
+#[derive(Debug, Clone, Encode, Decode, Serialize, Deserialize)]
+pub struct SecretCode {
+    /// Nonce followed by the encrypted code
+    pub sealed: Vec<u8>,
+    /// Set once the code was given to a winner
+    pub winner: Option<u64>,
+}
+
+/// A role the winners get, optionally only for some days
+#[derive(Debug, Clone, Copy, Encode, Decode, Serialize, Deserialize)]
+pub struct WinnerRole {
+    pub role: u64,
+    pub days: Option<u32>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Encode, Decode, Serialize, Deserialize)]
+pub struct Entry {
+    pub tickets: u32,
+    /// Timestamp of the first entry
+    pub entered: i64,
+}
+
+impl Entry {
+    pub fn new() -> Self {
+        Self {
+            tickets: 1,
+            entered: Utc::now().timestamp(),
+        }
+    }
+}
+
+/// More winners than this would only flood the announcement and the direct messages
+pub const MAX_WINNERS: u32 = 100;
+
+#[derive(Debug, Clone, Encode, Decode, Serialize, Deserialize)]
+pub struct Prize {
+    /// Empty, if the prizes weren't named when creating the giveaway
+    pub name: String,
+    pub count: u32,
+}
+
+#[derive(Debug, Clone, Encode, Decode, Serialize, Deserialize)]
+pub struct Prizes(pub Vec<Prize>);
+
+impl Prizes {
+    pub fn unnamed(count: u32) -> Self {
+        Self(vec![Prize {
+            name: String::new(),
+            count,
+        }])
+    }
+
+    //  Imported or older giveaways weren't capped, so the sum might not fit
+    pub fn total(&self) -> u32 {
+        self.0
+            .iter()
+            .try_fold(0u32, |total, prize| total.checked_add(prize.count))
+            .unwrap_or(u32::MAX)
+    }
+
+    /// The prize of each of the first winners in order of the draw
+    pub fn slots(&self, winners: usize) -> Vec<&str> {
+        self.0
+            .iter()
+            .flat_map(|prize| (0..prize.count).map(|_| prize.name.as_str()))
+            .take(winners)
+            .collect()
+    }
+
+    pub fn lines(&self) -> Vec<String> {
+        self.0
+            .iter()
+            .filter(|prize| !prize.name.is_empty())
+            .map(|prize| format!("- {}x {}", prize.count, prize.name))
+            .collect()
+    }
+}
+
+/// Parses a list like "1x Nitro; 2x Steam Key", the count defaults to 1
+impl FromStr for Prizes {
+    type Err = Localized;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut prizes = Vec::new();
+        for part in s.split(';').map(str::trim).filter(|part| !part.is_empty()) {
+            let (count, name) = match part.split_once(['x', 'X']) {
+                Some((count, name)) if count.trim().parse::<u32>().is_ok() => {
+                    (count.trim().parse().unwrap(), name.trim())
+                }
+                _ => (1, part),
+            };
+            if count == 0 || count > MAX_WINNERS || name.is_empty() {
+                return Err(localized!("invalid-prize", part));
+            }
+            prizes.push(Prize {
+                name: name.to_string(),
+                count,
+            });
+        }
+        if prizes.iter().map(|prize| prize.count).sum::<u32>() > MAX_WINNERS {
+            return Err(localized!("prizes-too-many", max = MAX_WINNERS));
+        }
+        match prizes.is_empty() {
+            true => Err(localized!("prizes-empty")),
+            false => Ok(Self(prizes)),
+        }
+    }
+}
+
+/// Conditions a member has to meet to enter a giveaway
+#[derive(Debug, Clone, Default, Encode, Decode, Serialize, Deserialize)]
+pub struct Requirements {
+    pub role: Option<u64>,
+    /// Minimum number of days since the member joined the guild
+    pub min_member_days: Option<u32>,
+    /// Minimum number of messages written in the last number of days
+    pub min_messages: Option<(u32, u32)>,
+    /// Members who won a giveaway within this many days can't win again
+    pub win_cooldown: Option<u32>,
+    pub boosters_only: bool,
+    /// Members with any of these roles can't enter, e.g. staff
+    pub excluded_roles: Vec<u64>,
+    /// Minimum number of invited members, that are still on the guild
+    pub min_invites: Option<u32>,
+}
+
+impl Requirements {
+    pub fn describe(&self, language: Language) -> String {
+        let lines = self.lines(language);
+        match lines.is_empty() {
+            true => String::new(),
+            false => t!(language, "view-requirements-text", lines = lines.join("\n")),
+        }
+    }
+
+    pub fn lines(&self, language: Language) -> Vec<String> {
+        let mut lines = Vec::new();
+        if let Some(role) = self.role {
+            lines.push(t!(language, "view-role", role = format!("<@&{role}>")));
+        }
+        if let Some(days) = self.min_member_days {
+            lines.push(t!(language, "view-member-days", days));
+        }
+        if let Some((messages, days)) = self.min_messages {
+            lines.push(t!(language, "view-messages", messages, days));
+        }
+        if let Some(days) = self.win_cooldown {
+            lines.push(t!(language, "view-cooldown", days));
+        }
+        if self.boosters_only {
+            lines.push(t!(language, "view-boosters"));
+        }
+        if !self.excluded_roles.is_empty() {
+            let roles: Vec<String> = self
+                .excluded_roles
+                .iter()
+                .map(|role| format!("<@&{role}>"))
+                .collect();
+            lines.push(t!(language, "view-excluded", roles = roles.join(", ")));
+        }
+        if let Some(invites) = self.min_invites {
+            lines.push(t!(language, "view-invites", invites));
+        }
+        lines
+    }
+
+    /// Returns the reason for the rejection, if the member may not enter
+    pub fn check(
+        &self,
+        member: &Member,
+        activity: &Activity,
+        last_win: Option<i64>,
+        invited: u32,
+        language: Language,
+    ) -> Result<(), String> {
+        if let Some(role) = self.role
+            && !member.roles.iter().any(|r| r.get() == role)
+        {
+            return Err(t!(
+                language,
+                "requirement-role",
+                role = format!("<@&{role}>")
+            ));
+        }
+        if let Some(role) = self
+            .excluded_roles
+            .iter()
+            .find(|&&role| member.roles.iter().any(|r| r.get() == role))
+        {
+            return Err(t!(
+                language,
+                "requirement-excluded",
+                role = format!("<@&{role}>")
+            ));
+        }
+        if self.boosters_only && member.premium_since.is_none() {
+            return Err(t!(language, "requirement-boosters"));
+        }
+        if let Some(days) = self.min_member_days {
+            let since = member
+                .joined_at
+                .map(|joined| Utc::now().timestamp() - joined.unix_timestamp())
+                .unwrap_or(0);
+            if since < i64::from(days) * 24 * 60 * 60 {
+                return Err(t!(language, "requirement-member-days", days));
+            }
+        }
+        if let Some((messages, days)) = self.min_messages {
+            let written = activity.messages_since(days);
+            if written < messages {
+                return Err(t!(
+                    language,
+                    "requirement-messages",
+                    messages,
+                    days,
+                    written
+                ));
+            }
+        }
+        if let Some(invites) = self.min_invites
+            && invited < invites
+        {
+            return Err(t!(language, "requirement-invites", invites, invited));
+        }
+        if let Some(days) = self.win_cooldown
+            && let Some(last_win) = last_win
+        {
+            let again = last_win + i64::from(days) * 24 * 60 * 60;
+            if again > Utc::now().timestamp() {
+                return Err(t!(
+                    language,
+                    "requirement-cooldown",
+                    time = format!("<t:{again}:f>")
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A giveaway, that is only posted once `start` is reached
+#[derive(Debug, Clone, Encode, Decode, Serialize, Deserialize)]
+pub struct Draft {
+    pub title: String,
+    pub description: String,
+    pub prizes: Prizes,
+    pub channel: u64,
+    pub time: Option<i64>,
+    pub requirements: Requirements,
+    pub style: Style,
+    pub claim_hours: u32,
+    pub host: u64,
+    pub start: i64,
+    pub ping: bool,
+    pub notify_losers: bool,
+    pub auto_extend: Option<AutoExtend>,
+    pub winner_role: Option<WinnerRole>,
+    pub secret_codes: Vec<SecretCode>,
+    /// Discord scheduled event kept in sync with the giveaway
+    pub event: Option<u64>,
+    pub question: Option<EntryQuestion>,
+    /// Channels, that get a copy of the message once the giveaway is posted
+    pub mirror_channels: Vec<u64>,
+}
+
+impl Draft {
+    pub fn publish(self, message: MessageId, commitment: Commitment) -> Giveaway {
+        Giveaway {
+            title: self.title,
+            description: self.description,
+            participants: HashMap::new(),
+            prizes: self.prizes,
+            channel: self.channel,
+            message: message.get(),
+            time: self.time,
+            requirements: self.requirements,
+            style: self.style,
+            created: Some(Utc::now().timestamp()),
+            claim_hours: self.claim_hours,
+            host: Some(self.host),
+            notify_losers: self.notify_losers,
+            auto_extend: self.auto_extend,
+            winner_role: self.winner_role,
+            secret_codes: self.secret_codes,
+            event: self.event,
+            question: self.question,
+            mirrors: Vec::new(),
+            commitment: Some(commitment),
+        }
+    }
+
+    pub fn view(&self) -> GiveawayMessage<'_> {
+        GiveawayMessage {
+            title: &self.title,
+            description: &self.description,
+            prizes: &self.prizes,
+            time: self
+                .time
+                .map(|ts| DateTime::from_timestamp(ts, 0).unwrap().to_utc()),
+            requirements: &self.requirements,
+            style: &self.style,
+            host: Some(UserId::new(self.host)),
+            participants: 0,
+            commitment: None,
+        }
+    }
+}
+
+/// A finished giveaway, kept so winners can be rerolled later
+#[derive(Debug, Clone, Encode, Decode, Serialize, Deserialize)]
+pub struct ArchivedGiveaway {
+    pub giveaway: Giveaway,
+    /// Every user drawn so far, including rerolls
+    pub winners: Vec<u64>,
+    pub ended: i64,
+    /// Channel and message announcing the winners
+    pub announcement: Option<(u64, u64)>,
+    pub slots: Vec<Slot>,
+    /// Cancelled giveaways have no winners and can't be rerolled
+    pub cancelled: bool,
+    pub cancel_reason: Option<String>,
+    /// Unknown for giveaways from older versions and cancelled ones
+    pub draw: Option<Draw>,
+    /// Winners and when their winner role is taken away again
+    pub role_grants: Vec<(u64, i64)>,
+}
+
+/// Everything needed to repeat the initial draw of a giveaway
+#[derive(Debug, Clone, Encode, Decode, Serialize, Deserialize)]
+pub struct Draw {
+    pub seed: u64,
+    pub hash: String,
+    /// Members, that couldn't win, e.g. because they won recently
+    pub excluded: Vec<u64>,
+    /// Hides the participants in the hash behind a salted leaf, so erased ones can be replaced by
+    /// a tombstone. None for draws from before, they can't be repeated once someone was erased
+    pub salt: Option<String>,
+    /// Participants that had their data erased
+    #[serde(default)]
+    pub erased: Vec<Tombstone>,
+}
+
+/// What remains of an erased participant, enough to repeat the draw without knowing who it was
+#[derive(Debug, Clone, Encode, Decode, Serialize, Deserialize)]
+pub struct Tombstone {
+    /// Empty for draws without a salt
+    pub leaf: String,
+    pub tickets: u32,
+    pub excluded: bool,
+}
+
+impl ArchivedGiveaway {
+    pub fn new(giveaway: Giveaway, winners: &[UserId]) -> Self {
+        let ended = Utc::now().timestamp();
+        let deadline =
+            (giveaway.claim_hours > 0).then(|| ended + i64::from(giveaway.claim_hours) * 60 * 60);
+        let slots = giveaway
+            .prizes
+            .slots(winners.len())
+            .into_iter()
+            .zip(winners)
+            .map(|(prize, user)| Slot {
+                prize: prize.to_string(),
+                user: Some(user.get()),
+                deadline,
+                claimed: false,
+            })
+            .collect();
+        Self {
+            giveaway,
+            winners: winners.iter().map(|user| user.get()).collect(),
+            ended,
+            announcement: None,
+            slots,
+            cancelled: false,
+            cancel_reason: None,
+            draw: None,
+            role_grants: Vec::new(),
+        }
+    }
+
+    pub fn cancelled(giveaway: Giveaway, reason: Option<String>) -> Self {
+        Self {
+            cancelled: true,
+            cancel_reason: reason,
+            ..Self::new(giveaway, &[])
+        }
+    }
+
+    /// The earliest deadline of a prize, that wasn't claimed yet
+    pub fn next_deadline(&self) -> Option<i64> {
+        self.slots
+            .iter()
+            .filter(|slot| slot.pending())
+            .filter_map(|slot| slot.deadline)
+            .min()
+    }
+
+    /// The earliest time a winner role has to be removed
+    pub fn next_role_removal(&self) -> Option<i64> {
+        self.role_grants.iter().map(|(_, until)| *until).min()
+    }
+}
+
+/// One prize of a finished giveaway and who won it
+#[derive(Debug, Clone, Encode, Decode, Serialize, Deserialize)]
+pub struct Slot {
+    pub prize: String,
+    /// None, if nobody was left to win it
+    pub user: Option<u64>,
+    /// The prize has to be claimed before this, if set
+    pub deadline: Option<i64>,
+    pub claimed: bool,
+}
+
+impl Slot {
+    pub fn pending(&self) -> bool {
+        self.user.is_some() && self.deadline.is_some() && !self.claimed
+    }
 }
 
 #[derive(Debug, Clone)]
 pub struct RealGiveaway {
     pub title: String,
     pub description: String,
-    pub participants: HashSet<UserId>,
-    pub winners: u32,
+    pub participants: HashMap<UserId, Entry>,
+    pub prizes: Prizes,
     pub channel: ChannelId,
     pub message: MessageId,
     pub time: Option<DateTime<Utc>>,
+    pub requirements: Requirements,
+    pub style: Style,
+    pub created: Option<DateTime<Utc>>,
+    pub claim_hours: u32,
+    pub host: Option<UserId>,
+    pub notify_losers: bool,
+    pub auto_extend: Option<AutoExtend>,
+    pub winner_role: Option<WinnerRole>,
+    pub secret_codes: Vec<SecretCode>,
+    /// Discord scheduled event kept in sync with the giveaway
+    pub event: Option<u64>,
+    pub question: Option<EntryQuestion>,
+    pub mirrors: Vec<(ChannelId, MessageId)>,
+    pub commitment: Option<Commitment>,
 }
 
 impl RealGiveaway {
-    pub fn get_message(&self, past: bool) -> String {
-        Self::get_message_early(&self.title, &self.description, self.time.as_ref(), past)
-    }
-
-    pub fn get_message_early(
-        title: &str,
-        description: &str,
-        time: Option<&DateTime<Utc>>,
-        past: bool,
-    ) -> String {
-        let time_str = time
-            .map(|t| {
-                format!(
-                    "\n\n{}: <t:{}:R>",
-                    match past {
-                        true => "Endete",
-                        false => "Endet",
-                    },
-                    //  Event is finished before time ran out, so we show current time as ending
-                    if past && time.is_some_and(|t| t > &Utc::now()) {
-                        Utc::now().timestamp()
-                    } else {
-                        t.timestamp()
-                    }
-                )
-            })
+    pub fn view(&self) -> GiveawayMessage<'_> {
+        GiveawayMessage {
+            title: &self.title,
+            description: &self.description,
+            prizes: &self.prizes,
+            time: self.time,
+            requirements: &self.requirements,
+            style: &self.style,
+            host: self.host,
+            participants: self.participants.len(),
+            commitment: self
+                .commitment
+                .as_ref()
+                .map(|commitment| commitment.hash.as_str()),
+        }
+    }
+}
+
+/// How the giveaway message looks
+#[derive(Debug, Clone, Default, Encode, Decode, Serialize, Deserialize)]
+pub struct Style {
+    /// Plain text is used, if this is false
+    pub embed: bool,
+    pub image: Option<String>,
+    pub color: Option<u32>,
+    pub language: Language,
+    /// Members enter by reacting with [`ENTRY_EMOJI`] instead of using the buttons
+    pub reactions: bool,
+    /// Timezone for showing the end as absolute time with a countdown, that the bot refreshes,
+    /// Discord timestamps are used if unset
+    pub countdown: Option<String>,
+    pub buttons: Buttons,
+    /// Only admins can see who entered, the participant count isn't shown either
+    pub anonymous: bool,
+}
+
+/// Overrides for the buttons below a giveaway, set with /settings buttons
+#[derive(Debug, Clone, Default, Encode, Decode, Serialize, Deserialize)]
+pub struct Buttons {
+    pub join: CustomButton,
+    pub leave: CustomButton,
+    pub cancel: CustomButton,
+    pub finish: CustomButton,
+}
+
+impl Buttons {
+    pub fn get(&self, button: GiveawayButton) -> &CustomButton {
+        match button {
+            GiveawayButton::Join => &self.join,
+            GiveawayButton::Leave => &self.leave,
+            GiveawayButton::Cancel => &self.cancel,
+            GiveawayButton::Finish => &self.finish,
+        }
+    }
+
+    pub fn get_mut(&mut self, button: GiveawayButton) -> &mut CustomButton {
+        match button {
+            GiveawayButton::Join => &mut self.join,
+            GiveawayButton::Leave => &mut self.leave,
+            GiveawayButton::Cancel => &mut self.cancel,
+            GiveawayButton::Finish => &mut self.finish,
+        }
+    }
+}
+
+/// Unset parts fall back to the defaults
+#[derive(Debug, Clone, Default, Encode, Decode, Serialize, Deserialize)]
+pub struct CustomButton {
+    pub label: Option<String>,
+    pub emoji: Option<String>,
+    pub color: Option<ButtonColor>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ChoiceParameter)]
+pub enum GiveawayButton {
+    #[name = "Dabei"]
+    Join,
+    #[name = "Raus"]
+    Leave,
+    #[name = "Abbrechen"]
+    Cancel,
+    #[name = "Abschließen"]
+    Finish,
+}
+
+impl GiveawayButton {
+    /// The default label, also used to name the button in replies
+    pub fn label(self, language: Language) -> String {
+        match self {
+            GiveawayButton::Join => t!(language, "button-join"),
+            GiveawayButton::Leave => t!(language, "button-leave"),
+            GiveawayButton::Cancel => t!(language, "button-cancel"),
+            GiveawayButton::Finish => t!(language, "button-finish"),
+        }
+    }
+}
+
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, Encode, Decode, Serialize, Deserialize, ChoiceParameter,
+)]
+pub enum ButtonColor {
+    #[name = "Blau"]
+    Blue,
+    #[name = "Grau"]
+    Grey,
+    #[name = "Grün"]
+    Green,
+    #[name = "Rot"]
+    Red,
+}
+
+impl From<ButtonColor> for ButtonStyle {
+    fn from(value: ButtonColor) -> Self {
+        match value {
+            ButtonColor::Blue => ButtonStyle::Primary,
+            ButtonColor::Grey => ButtonStyle::Secondary,
+            ButtonColor::Green => ButtonStyle::Success,
+            ButtonColor::Red => ButtonStyle::Danger,
+        }
+    }
+}
+
+pub const ENTRY_EMOJI: &str = "🎉";
+
+/// Everything needed to render a giveaway message, also for giveaways that aren't posted yet
+pub struct GiveawayMessage<'a> {
+    pub title: &'a str,
+    pub description: &'a str,
+    pub prizes: &'a Prizes,
+    pub time: Option<DateTime<Utc>>,
+    pub requirements: &'a Requirements,
+    pub style: &'a Style,
+    pub host: Option<UserId>,
+    pub participants: usize,
+    /// Hash of the seed the winners will be drawn with
+    pub commitment: Option<&'a str>,
+}
+
+impl GiveawayMessage<'_> {
+    fn end(&self, past: bool) -> Option<(String, String)> {
+        let language = self.style.language;
+        self.time.map(|t| {
+            let label = match past {
+                true => t!(language, "view-ended"),
+                false => t!(language, "view-ends"),
+            };
+            //  Event is finished before time ran out, so we show current time as ending
+            let t = if past && t > Utc::now() {
+                Utc::now()
+            } else {
+                t
+            };
+            let Some(tz) = self
+                .style
+                .countdown
+                .as_deref()
+                .and_then(|tz| tz.parse::<Tz>().ok())
+            else {
+                return (label, format!("<t:{}:R>", t.timestamp()));
+            };
+            let absolute = t.with_timezone(&tz).format("%d.%m.%Y %H:%M");
+            match past {
+                true => (label, absolute.to_string()),
+                false => (
+                    label,
+                    t!(
+                        language,
+                        "view-countdown",
+                        absolute,
+                        remaining = format_duration(t - Utc::now(), language)
+                    ),
+                ),
+            }
+        })
+    }
+
+    /// The plain text content, empty if an embed is used
+    pub fn content(&self, past: bool) -> String {
+        if self.style.embed {
+            return String::new();
+        }
+        let time_str = self
+            .end(past)
+            .map(|(label, time)| format!("\n\n{label}: {time}"))
             .unwrap_or_default();
-        format!("# {title}\n\n{description}{time_str}")
+        let language = self.style.language;
+        let prizes = self.prizes.lines();
+        let prizes_str = match prizes.is_empty() {
+            true => String::new(),
+            false => t!(language, "view-prizes-text", prizes = prizes.join("\n")),
+        };
+        let requirements_str = self.requirements.describe(language);
+        let host_str = self
+            .host
+            .map(|host| t!(language, "view-host-text", host = host.mention()))
+            .unwrap_or_default();
+        let reactions_str = self
+            .entry_hint(past)
+            .map(|hint| format!("\n\n{hint}"))
+            .unwrap_or_default();
+        let mut content = format!(
+            "# {}\n\n{}{prizes_str}{requirements_str}{host_str}{time_str}{reactions_str}",
+            self.title, self.description
+        );
+        if let Some(hash) = self.commitment {
+            content.push_str(&format!("\n-# {}", t!(language, "view-commitment", hash)));
+        }
+        content
+    }
+
+    fn entry_hint(&self, past: bool) -> Option<String> {
+        (self.style.reactions && !past)
+            .then(|| t!(self.style.language, "view-react", emoji = ENTRY_EMOJI))
+    }
+
+    pub fn embed(&self, past: bool) -> Option<CreateEmbed> {
+        if !self.style.embed {
+            return None;
+        }
+        let language = self.style.language;
+        let mut footer = match self.style.anonymous {
+            true => Vec::new(),
+            false => vec![t!(language, "view-participants", count = self.participants)],
+        };
+        footer.extend(self.entry_hint(past));
+        footer.extend(
+            self.commitment
+                .map(|hash| t!(language, "view-commitment", hash)),
+        );
+        let mut embed = CreateEmbed::new()
+            .title(self.title)
+            .description(self.description);
+        if !footer.is_empty() {
+            embed = embed.footer(CreateEmbedFooter::new(footer.join(" · ")));
+        }
+        let prizes = self.prizes.lines();
+        if !prizes.is_empty() {
+            embed = embed.field(t!(language, "view-prizes"), prizes.join("\n"), false);
+        }
+        let requirements = self.requirements.lines(language);
+        if !requirements.is_empty() {
+            embed = embed.field(
+                t!(language, "view-requirements"),
+                requirements.join("\n"),
+                false,
+            );
+        }
+        if let Some(host) = self.host {
+            embed = embed.field(t!(language, "view-host"), host.mention().to_string(), false);
+        }
+        if let Some((label, time)) = self.end(past) {
+            embed = embed.field(label, time, false);
+        }
+        if let Some(image) = &self.style.image {
+            embed = embed.image(image);
+        }
+        if let Some(color) = self.style.color {
+            embed = embed.color(color);
+        }
+        Some(embed)
+    }
+
+    pub fn create_message(&self, past: bool) -> CreateMessage {
+        CreateMessage::new()
+            .content(self.content(past))
+            .embeds(self.embed(past).into_iter().collect())
+    }
+
+    pub fn edit_message(&self, past: bool) -> EditMessage {
+        EditMessage::new()
+            .content(self.content(past))
+            .embeds(self.embed(past).into_iter().collect())
     }
 }
 
@@ -104,14 +970,33 @@ impl From<Giveaway> for RealGiveaway {
             participants: value
                 .participants
                 .into_iter()
-                .map(|user| UserId::from(user))
+                .map(|(user, entry)| (UserId::from(user), entry))
                 .collect(),
-            winners: value.winners,
+            prizes: value.prizes,
             channel: ChannelId::from(value.channel),
             message: MessageId::from(value.message),
             time: value
                 .time
                 .map(|ts| DateTime::from_timestamp(ts, 0).unwrap().to_utc()),
+            requirements: value.requirements,
+            style: value.style,
+            created: value
+                .created
+                .map(|ts| DateTime::from_timestamp(ts, 0).unwrap().to_utc()),
+            claim_hours: value.claim_hours,
+            host: value.host.map(UserId::from),
+            notify_losers: value.notify_losers,
+            auto_extend: value.auto_extend,
+            winner_role: value.winner_role,
+            secret_codes: value.secret_codes,
+            event: value.event,
+            question: value.question,
+            mirrors: value
+                .mirrors
+                .into_iter()
+                .map(|(channel, message)| (ChannelId::new(channel), MessageId::new(message)))
+                .collect(),
+            commitment: value.commitment,
         }
     }
 }
@@ -124,12 +1009,29 @@ impl From<RealGiveaway> for Giveaway {
             participants: value
                 .participants
                 .into_iter()
-                .map(|user| user.get())
+                .map(|(user, entry)| (user.get(), entry))
                 .collect(),
-            winners: value.winners,
+            prizes: value.prizes,
             channel: value.channel.get(),
             message: value.message.get(),
             time: value.time.map(|time| time.timestamp()),
+            requirements: value.requirements,
+            style: value.style,
+            created: value.created.map(|time| time.timestamp()),
+            claim_hours: value.claim_hours,
+            host: value.host.map(|host| host.get()),
+            notify_losers: value.notify_losers,
+            auto_extend: value.auto_extend,
+            winner_role: value.winner_role,
+            secret_codes: value.secret_codes,
+            event: value.event,
+            question: value.question,
+            mirrors: value
+                .mirrors
+                .into_iter()
+                .map(|(channel, message)| (channel.get(), message.get()))
+                .collect(),
+            commitment: value.commitment,
         }
     }
 }
@@ -139,12 +1041,38 @@ impl From<RealGiveaway> for Giveaway {
 )]
 pub struct GiveawayId(pub u64);
 
+impl Display for GiveawayId {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl FromStr for GiveawayId {
+    type Err = ParseIntError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.trim().trim_matches('`').parse().map(Self)
+    }
+}
+
 #[derive(Debug, Clone, Copy, Hash, PartialEq, Eq, Serialize, Deserialize)]
 pub enum UserAction {
     Add(GiveawayId),
     Remove(GiveawayId),
     Finish(GiveawayId),
     Cancel(GiveawayId),
-    ClearAll(Option<ChannelId>),
-    Clear(Option<(GuildId, UserId)>),
+    Reroll(GiveawayId),
+    Claim(GiveawayId),
+    /// Opens the participant list, or switches to the given page
+    Participants(GiveawayId, Option<u32>),
+    /// Confirms or aborts the stored clear job
+    Clear(u64, bool),
+    EndAll(Option<GuildId>),
+    CancelAll(Option<GuildId>),
+    /// Opens the modal for the secret codes of a new giveaway
+    Codes(GiveawayId),
+    /// Switches the page of /audit
+    Audit(u32),
+    /// Confirms or aborts /mydata delete
+    DeleteData(bool),
 }